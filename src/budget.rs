@@ -0,0 +1,138 @@
+use crate::gap_estimate::format_duration;
+use std::fmt;
+use std::time::Duration;
+
+/// Exit code of a run whose budget ran out before anything was found
+pub const BUDGET_EXHAUSTED_EXIT_CODE: i32 = 3;
+/// Exit code of a run that failed, like the errors found before the search
+pub const ERROR_EXIT_CODE: i32 = 1;
+
+/// Limits of a run, for scheduled jobs: none by default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    /// Wall-clock time from the start of the search
+    pub time: Option<Duration>,
+    /// Keys derived by all the workbenches together
+    pub keys: Option<u64>,
+}
+
+/// Why a search ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    /// Every address asked for was found
+    Found,
+    /// Ctrl+C, or quit in the TUI
+    Interrupted,
+    /// The time budget ran out
+    TimeBudget(Duration),
+    /// The key budget ran out
+    KeyBudget(u64),
+    /// Every path of the search was derived
+    Exhausted,
+    /// A found address could not be written to the --output file
+    OutputFailed,
+}
+
+impl EndReason {
+    /// Short name for the JSON events
+    pub fn name(&self) -> &'static str {
+        match self {
+            EndReason::Found => "found",
+            EndReason::Interrupted => "interrupted",
+            EndReason::TimeBudget(_) => "time_budget",
+            EndReason::KeyBudget(_) => "key_budget",
+            EndReason::Exhausted => "exhausted",
+            EndReason::OutputFailed => "output_failed",
+        }
+    }
+
+    /// 0, `ERROR_EXIT_CODE` when a result could not be saved, or
+    /// `BUDGET_EXHAUSTED_EXIT_CODE` when a budget ended a run that found
+    /// nothing
+    pub fn exit_code(&self, found_any: bool) -> i32 {
+        match self {
+            EndReason::OutputFailed => ERROR_EXIT_CODE,
+            EndReason::TimeBudget(_) | EndReason::KeyBudget(_) if !found_any => {
+                BUDGET_EXHAUSTED_EXIT_CODE
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EndReason::Found => write!(f, "found the addresses asked for"),
+            EndReason::Interrupted => write!(f, "interrupted"),
+            EndReason::TimeBudget(time) => write!(
+                f,
+                "time budget of {} ran out (--deadline)",
+                format_duration(time.as_secs_f64())
+            ),
+            EndReason::KeyBudget(keys) => {
+                write!(f, "key budget of {} keys ran out (--max-keys)", keys)
+            }
+            EndReason::Exhausted => write!(f, "searched every path"),
+            EndReason::OutputFailed => write!(f, "failed to save a result (--output)"),
+        }
+    }
+}
+
+/// A number of keys, optionally with a k, M, G or T multiplier: "500000",
+/// "250M", "1.5G"
+pub fn parse_key_count(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let (number, multiplier) = match text.char_indices().last() {
+        Some((i, 'k')) => (&text[..i], 1e3),
+        Some((i, 'M')) => (&text[..i], 1e6),
+        Some((i, 'G')) => (&text[..i], 1e9),
+        Some((i, 'T')) => (&text[..i], 1e12),
+        _ => (text, 1.0),
+    };
+    let invalid = || {
+        format!(
+            "Invalid key count '{}': expected a number, optionally followed by k, M, G or T",
+            text
+        )
+    };
+    let keys = match number.parse::<u64>() {
+        Ok(keys) if multiplier == 1.0 => keys as f64,
+        _ => number.parse::<f64>().map_err(|_| invalid())? * multiplier,
+    };
+    if !keys.is_finite() || keys < 1.0 || keys >= u64::MAX as f64 {
+        return Err(format!("Key count '{}' is out of range, from 1", text));
+    }
+    Ok(keys.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_count() {
+        assert_eq!(parse_key_count("500000"), Ok(500_000));
+        assert_eq!(parse_key_count("250M"), Ok(250_000_000));
+        assert_eq!(parse_key_count("1.5G"), Ok(1_500_000_000));
+        assert_eq!(parse_key_count("2k"), Ok(2_000));
+        assert!(parse_key_count("0").is_err());
+        assert!(parse_key_count("-5").is_err());
+        assert!(parse_key_count("5X").is_err());
+        assert!(parse_key_count("").is_err());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let time = EndReason::TimeBudget(Duration::from_secs(60));
+        assert_eq!(time.exit_code(false), BUDGET_EXHAUSTED_EXIT_CODE);
+        assert_eq!(time.exit_code(true), 0);
+        assert_eq!(
+            EndReason::KeyBudget(1000).exit_code(false),
+            BUDGET_EXHAUSTED_EXIT_CODE
+        );
+        assert_eq!(EndReason::Interrupted.exit_code(false), 0);
+        assert_eq!(EndReason::Exhausted.exit_code(false), 0);
+        assert_eq!(EndReason::OutputFailed.exit_code(true), ERROR_EXIT_CODE);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::path_template::PathTemplate;
+use crate::prefix::Prefix;
+use crate::seeds::{self, Seeds};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "address-artisan checkpoint v1";
+
+/// Where one workbench stands: its seeds, its part of the counter space and
+/// the counter below which every path is processed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchCheckpoint {
+    pub bench_id: String,
+    pub seeds: Seeds,
+    pub counter_range: Range<u64>,
+    pub processed_counter: u64,
+}
+
+impl BenchCheckpoint {
+    /// What is left to search
+    pub fn remaining_range(&self) -> Range<u64> {
+        self.processed_counter..self.counter_range.end
+    }
+}
+
+/// State of a search, saved periodically so an interrupted run can resume
+/// where it stopped. A resumed run must search the same xpub, prefixes, path
+/// template and max depth on the same devices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    fingerprint: String,
+    prefixes: Vec<String>,
+    template: PathTemplate,
+    max_depth: u32,
+    benches: Vec<BenchCheckpoint>,
+}
+
+impl Checkpoint {
+    /// A fresh run: every bench shares the seeds and gets its own part of the
+    /// searched counters, in bench order
+    pub fn new(
+        fingerprint: [u8; 4],
+        prefixes: &[Prefix],
+        template: PathTemplate,
+        max_depth: u32,
+        seeds: Seeds,
+        search_range: &Range<u64>,
+        bench_ids: &[String],
+    ) -> Self {
+        let counter_ranges = seeds::counter_ranges(search_range, bench_ids.len());
+        let benches = bench_ids
+            .iter()
+            .zip(counter_ranges)
+            .map(|(bench_id, counter_range)| BenchCheckpoint {
+                bench_id: bench_id.clone(),
+                seeds,
+                processed_counter: counter_range.start,
+                counter_range,
+            })
+            .collect();
+
+        Checkpoint {
+            fingerprint: hex_fingerprint(fingerprint),
+            prefixes: prefixes.iter().map(prefix_key).collect(),
+            template,
+            max_depth,
+            benches,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read checkpoint '{}': {}", path.display(), e))?;
+        Self::parse(&content)
+            .map_err(|e| format!("Checkpoint '{}' is invalid: {}", path.display(), e))
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut lines = content.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("not an address-artisan checkpoint".to_string());
+        }
+
+        let mut fingerprint = None;
+        let mut template = None;
+        let mut max_depth = None;
+        let mut prefixes = Vec::new();
+        let mut benches = Vec::new();
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "fingerprint" => fingerprint = Some(value.to_string()),
+                "path_template" => template = Some(PathTemplate::parse(value)?),
+                "max_depth" => {
+                    max_depth = Some(value.parse().map_err(|_| format!("bad line: {}", line))?)
+                }
+                "prefix" => prefixes.push(value.to_string()),
+                "bench" => {
+                    benches.push(parse_bench(value).ok_or_else(|| format!("bad line: {}", line))?)
+                }
+                _ => return Err(format!("unknown line: {}", line)),
+            }
+        }
+
+        Ok(Checkpoint {
+            fingerprint: fingerprint.ok_or("missing fingerprint")?,
+            prefixes,
+            template: template.ok_or("missing path_template")?,
+            max_depth: max_depth.ok_or("missing max_depth")?,
+            benches,
+        })
+    }
+
+    /// Refuses to resume a search with other parameters: its counters would
+    /// point to other paths
+    pub fn check_run(
+        &self,
+        fingerprint: [u8; 4],
+        prefixes: &[Prefix],
+        template: &PathTemplate,
+        max_depth: u32,
+        bench_ids: &[String],
+    ) -> Result<(), String> {
+        let fingerprint = hex_fingerprint(fingerprint);
+        if self.fingerprint != fingerprint {
+            return Err(format!(
+                "was written for xpub {}, not for this one ({})",
+                self.fingerprint, fingerprint
+            ));
+        }
+
+        let prefixes: Vec<String> = prefixes.iter().map(prefix_key).collect();
+        if self.prefixes != prefixes {
+            return Err(format!(
+                "was written for prefixes {}, not for {}",
+                self.prefixes.join(", "),
+                prefixes.join(", ")
+            ));
+        }
+
+        if self.template != *template {
+            return Err(format!(
+                "was written for path template {}, not for {}",
+                self.template, template
+            ));
+        }
+
+        if self.max_depth != max_depth {
+            return Err(format!(
+                "was written for max depth {}, not for {}",
+                self.max_depth, max_depth
+            ));
+        }
+
+        let checkpoint_bench_ids: Vec<&str> = self
+            .benches
+            .iter()
+            .map(|bench| bench.bench_id.as_str())
+            .collect();
+        if checkpoint_bench_ids != bench_ids {
+            return Err(format!(
+                "was written for devices {}, not for {}. Select the same devices",
+                checkpoint_bench_ids.join(", "),
+                bench_ids.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn bench(&self, bench_id: &str) -> Option<&BenchCheckpoint> {
+        self.benches.iter().find(|bench| bench.bench_id == bench_id)
+    }
+
+    pub fn template(&self) -> PathTemplate {
+        self.template
+    }
+
+    /// Seeds shown to the user, those of the first bench
+    pub fn seeds(&self) -> Option<Seeds> {
+        self.benches.first().map(|bench| bench.seeds)
+    }
+
+    /// Records that the bench processed every path below `processed_counter`
+    pub fn record_progress(&mut self, bench_id: &str, processed_counter: u64) {
+        if let Some(bench) = self
+            .benches
+            .iter_mut()
+            .find(|bench| bench.bench_id == bench_id)
+        {
+            bench.processed_counter =
+                processed_counter.clamp(bench.processed_counter, bench.counter_range.end);
+        }
+    }
+
+    /// Writes a temporary file next to `path` and renames it over it, so a
+    /// crash while saving leaves the previous checkpoint intact
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp_path = PathBuf::from(path);
+        tmp_path.as_mut_os_string().push(".tmp");
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+impl std::fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "fingerprint {}", self.fingerprint)?;
+        writeln!(f, "path_template {}", self.template)?;
+        writeln!(f, "max_depth {}", self.max_depth)?;
+        for prefix in &self.prefixes {
+            writeln!(f, "prefix {}", prefix)?;
+        }
+        for bench in &self.benches {
+            writeln!(
+                f,
+                "bench {} {} {} {} {} {}",
+                bench.seeds.seed0,
+                bench.seeds.seed1,
+                bench.counter_range.start,
+                bench.counter_range.end,
+                bench.processed_counter,
+                bench.bench_id
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `<seed0> <seed1> <range start> <range end> <processed counter> <bench id>`,
+/// the bench id last as it may hold spaces
+fn parse_bench(value: &str) -> Option<BenchCheckpoint> {
+    let mut fields = value.splitn(6, ' ');
+    let mut next_number = || fields.next()?.parse::<u64>().ok();
+    let seed0 = u32::try_from(next_number()?).ok()?;
+    let seed1 = u32::try_from(next_number()?).ok()?;
+    let counter_range = next_number()?..next_number()?;
+    let processed_counter = next_number()?;
+    let bench_id = fields.next().filter(|bench_id| !bench_id.is_empty())?;
+
+    if !(counter_range.start..=counter_range.end).contains(&processed_counter) {
+        return None;
+    }
+
+    Some(BenchCheckpoint {
+        bench_id: bench_id.to_string(),
+        seeds: Seeds { seed0, seed1 },
+        counter_range,
+        processed_counter,
+    })
+}
+
+fn hex_fingerprint(fingerprint: [u8; 4]) -> String {
+    fingerprint.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Case-insensitive prefixes match other addresses than the exact ones
+fn prefix_key(prefix: &Prefix) -> String {
+    if prefix.case_insensitive {
+        format!("{} case-insensitive", prefix.as_str())
+    } else {
+        prefix.as_str().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extended_public_key_path_walker::counter_space;
+    use crate::network::Network;
+
+    const FINGERPRINT: [u8; 4] = [0xd3, 0x4d, 0xb3, 0x3f];
+
+    fn bench_ids() -> Vec<String> {
+        vec!["0_NVIDIA GeForce RTX 4090".to_string(), "CPU".to_string()]
+    }
+
+    fn checkpoint() -> Checkpoint {
+        let prefixes = vec![
+            Prefix::new("1A", Network::Mainnet).unwrap(),
+            Prefix::new_case_insensitive("bc1qxy", Network::Mainnet).unwrap(),
+        ];
+        Checkpoint::new(
+            FINGERPRINT,
+            &prefixes,
+            PathTemplate::default(),
+            1000,
+            Seeds { seed0: 1, seed1: 2 },
+            &(0..counter_space(&PathTemplate::default(), 1000)),
+            &bench_ids(),
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "address-artisan-{}-{}.checkpoint",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("save");
+        let mut checkpoint = checkpoint();
+        checkpoint.record_progress("CPU", checkpoint.benches[1].counter_range.start + 500);
+
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(
+            loaded.bench("CPU").unwrap().remaining_range(),
+            checkpoint.benches[1].counter_range.start + 500..u64::MAX
+        );
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("\nprefix bc1qxy case-insensitive\n"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_record_progress_never_goes_back_or_past_the_range() {
+        let mut checkpoint = checkpoint();
+        let end = checkpoint.benches[0].counter_range.end;
+
+        checkpoint.record_progress("0_NVIDIA GeForce RTX 4090", 1000);
+        checkpoint.record_progress("0_NVIDIA GeForce RTX 4090", 10);
+        assert_eq!(checkpoint.benches[0].processed_counter, 1000);
+
+        checkpoint.record_progress("0_NVIDIA GeForce RTX 4090", end + 1);
+        assert_eq!(checkpoint.benches[0].processed_counter, end);
+        assert!(checkpoint.benches[0].remaining_range().is_empty());
+    }
+
+    #[test]
+    fn test_check_run() {
+        let checkpoint = checkpoint();
+        let template = PathTemplate::default();
+        let prefixes = vec![
+            Prefix::new("1A", Network::Mainnet).unwrap(),
+            Prefix::new_case_insensitive("bc1qxy", Network::Mainnet).unwrap(),
+        ];
+
+        assert!(checkpoint
+            .check_run(FINGERPRINT, &prefixes, &template, 1000, &bench_ids())
+            .is_ok());
+        assert!(checkpoint
+            .check_run([0, 0, 0, 0], &prefixes, &template, 1000, &bench_ids())
+            .unwrap_err()
+            .contains("xpub d34db33f, not for this one (00000000)"));
+        assert!(checkpoint
+            .check_run(FINGERPRINT, &prefixes[..1], &template, 1000, &bench_ids())
+            .unwrap_err()
+            .contains("prefixes"));
+        assert!(checkpoint
+            .check_run(FINGERPRINT, &prefixes, &template, 2000, &bench_ids())
+            .unwrap_err()
+            .contains("max depth 1000, not for 2000"));
+        assert!(checkpoint
+            .check_run(
+                FINGERPRINT,
+                &prefixes,
+                &PathTemplate::parse("s0/a/0/i").unwrap(),
+                1000,
+                &bench_ids()
+            )
+            .unwrap_err()
+            .contains("path template s0/s1/b/a/0/i, not for s0/a/0/i"));
+        assert!(checkpoint
+            .check_run(
+                FINGERPRINT,
+                &prefixes,
+                &template,
+                1000,
+                &["CPU".to_string()]
+            )
+            .unwrap_err()
+            .contains("Select the same devices"));
+    }
+
+    #[test]
+    fn test_refuses_invalid_files() {
+        assert!(Checkpoint::parse("some notes\n")
+            .unwrap_err()
+            .contains("not an address-artisan checkpoint"));
+
+        let mut content = checkpoint().to_string();
+        content.push_str("bench 1 2 100 200 300 CPU\n");
+        assert!(Checkpoint::parse(&content)
+            .unwrap_err()
+            .contains("bad line"));
+
+        let content: String = checkpoint()
+            .to_string()
+            .lines()
+            .filter(|line| !line.starts_with("path_template "))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(Checkpoint::parse(&content)
+            .unwrap_err()
+            .contains("missing path_template"));
+    }
+}
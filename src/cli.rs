@@ -1,32 +1,137 @@
-use crate::prefix::Prefix;
-use clap::Parser;
+use crate::budget::{self, Budget};
+use crate::constants::DEFAULT_MAX_DEPTH;
+use crate::cpu_throttle::{
+    CpuThrottle, DEFAULT_BACKGROUND_CPU_LIMIT, MAX_CPU_LIMIT, MIN_CPU_LIMIT,
+};
+use crate::descriptor::Descriptor;
+use crate::extended_public_key_path_walker::counter_space;
+use crate::gap_estimate;
+use crate::key_version::KeyVersion;
+use crate::longest_match;
+use crate::network::Network;
+use crate::path_template::PathTemplate;
+use crate::prefix::{AddressType, Prefix};
+use crate::quota::{self, Quota};
+use crate::seeds::Seeds;
+use crate::shard::Shard;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(
     version,
     about,
-    long_about = "A tool for generating vanity Bitcoin addresses (P2PKH and P2WPKH)."
+    long_about = "A tool for generating vanity Bitcoin addresses (P2PKH, P2SH-P2WPKH, P2WPKH and P2TR).",
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
 )]
 pub struct Cli {
     #[arg(
         short = 'p',
         long = "prefix",
-        help = "Prefix(es) for the address (P2PKH: '1abc...' or P2WPKH: 'bc1qaaa...'). Can specify multiple: --prefix 1A 1B or --prefix 1A,1B",
+        help = "Prefix(es) for the address (P2PKH: '1abc', P2SH-P2WPKH: '3abc', P2WPKH: 'bc1qaaa' or P2TR: 'bc1paaa'). Use '...' to also match the end: '1Bob...Artisan'. Can specify multiple: --prefix 1A 1B or --prefix 1A,1B. Append ':N' for a quota of N addresses of that prefix: --prefix 1Bob:1,1A:3",
         num_args = 1..,
         value_delimiter = ',',
-        value_parser = Cli::validate_prefix
+        value_parser = Cli::validate_pattern
     )]
+    pub patterns: Vec<String>,
+    #[arg(
+        long = "prefix-file",
+        help = "Wordlist file of prefixes, one pattern per line like --prefix, searched together with any --prefix, quotas included. Blank lines and lines starting with '#' are skipped"
+    )]
+    pub prefix_file: Option<PathBuf>,
+    #[arg(
+        short = 'i',
+        long = "case-insensitive",
+        help = "Match the prefix(es) in any casing: 1love also finds 1LoVe, 1LOVE, ...",
+        default_value = "false"
+    )]
+    pub case_insensitive: bool,
+    #[arg(
+        long = "longest",
+        help = "Longest-match mode: search for addresses starting like this target, e.g. 1BitcoinEater, keep a leaderboard of the longest matches and ask for longer ones as it fills. Runs until the whole target is found, --deadline or Ctrl+C",
+        conflicts_with_all = ["patterns", "prefix_file", "case_insensitive"],
+        value_parser = Cli::validate_pattern
+    )]
+    pub longest: Option<String>,
+    #[arg(
+        long = "min-match",
+        help = "Leading characters of the --longest target a candidate needs at first (default: the first length rarer than 1 in 100,000 addresses)",
+        requires = "longest",
+        conflicts_with_all = ["patterns", "prefix_file"]
+    )]
+    pub min_match: Option<usize>,
+    #[arg(
+        long = "deadline",
+        help = "Time budget: stop the search after this long, in seconds or a number followed by s, m, h or d, e.g. 45m or 1.5h. A run whose budget ran out before finding anything exits with code 3",
+        value_parser = gap_estimate::parse_duration
+    )]
+    pub deadline: Option<Duration>,
+    #[arg(
+        long = "max-keys",
+        help = "Key budget: stop the search after about this many keys, counted over all the devices, e.g. 500000, 250M or 1.5G. A run whose budget ran out before finding anything exits with code 3",
+        value_parser = budget::parse_key_count
+    )]
+    pub max_keys: Option<u64>,
+    /// Built from `patterns` and the prefix file once all the arguments are
+    /// known
+    #[arg(skip)]
     pub prefixes: Vec<Prefix>,
-    #[arg(short = 'x', long = "xpub", help = "Xpub", value_parser = Cli::validate_xpub)]
+    /// The ':N' quota of each prefix, if any
+    #[arg(skip)]
+    pub quotas: Vec<Option<u32>>,
+    #[arg(
+        short = 'x',
+        long = "xpub",
+        help = "Xpub, or its SLIP-132 form (ypub, zpub, ...)",
+        required_unless_present = "descriptor",
+        value_parser = Cli::validate_xpub
+    )]
+    pub xpub_arg: Option<String>,
+    #[arg(
+        short = 'd',
+        long = "descriptor",
+        help = "Output descriptor of the wallet instead of --xpub, e.g. \"wpkh([d34db33f/84'/0'/0']xpub.../<0;1>/*)#checksum\". Found addresses then show their full path from the master key",
+        conflicts_with = "xpub_arg",
+        value_parser = Descriptor::parse
+    )]
+    pub descriptor: Option<Descriptor>,
+    /// Taken from `xpub_arg` or from the descriptor's key
+    #[arg(skip)]
     pub xpub: String,
+    #[arg(
+        long = "network",
+        help = "Network of the xpub and the addresses: testnet and signet use tpub keys and m/n, 2 and tb1 addresses, regtest uses bcrt1",
+        value_enum,
+        default_value_t = Network::Mainnet
+    )]
+    pub network: Network,
     #[arg(
         short = 'm',
         long = "max-depth",
         help = "Max depth for the path's last index",
-        default_value = "1000",
+        default_value_t = DEFAULT_MAX_DEPTH,
         value_parser = Cli::validate_max_depth
     )]
     pub max_depth: u32,
+    #[arg(
+        long = "gap-limit",
+        help = "Bounded-gap strategy: search only address indexes below this gap limit, so every hit is found by a wallet scanning that many empty addresses, and report the expected time to a hit against the default max depth",
+        conflicts_with = "max_depth",
+        value_parser = Cli::validate_gap_limit
+    )]
+    pub gap_limit: Option<u32>,
+    #[arg(
+        long = "path-template",
+        help = "Layout of the searched paths, levels separated by '/': s0 and s1 for the seeds, b and a for the counted levels, a number for a fixed level and i, always last, for the index below the max depth. E.g. s0/a/0/i or a/1/i",
+        default_value = "s0/s1/b/a/0/i",
+        value_parser = PathTemplate::parse
+    )]
+    pub path_template: PathTemplate,
     #[arg(
         short = 't',
         long = "cpu-threads",
@@ -35,6 +140,30 @@ pub struct Cli {
         value_parser = Cli::validate_cpu_threads
     )]
     pub cpu_threads: u32,
+    #[arg(
+        long = "pin-threads",
+        help = "Pin each CPU worker, and the GPU producer threads, to its own core of the detected topology: one thread per physical core before SMT siblings, spread over the NUMA nodes, each allocating its derivation state on its node"
+    )]
+    pub pin_threads: bool,
+    #[arg(
+        long = "reserve-gpu-cores",
+        help = "With a GPU searching, set N physical cores aside for the GPU producer threads, which derive the parent keys of each batch, and keep the CPU workbench off them, pinned or not (default: 0 = share every core)",
+        value_name = "N",
+        default_value = "0"
+    )]
+    pub reserve_gpu_cores: u32,
+    #[arg(
+        long = "background",
+        help = "Background mode for a shared workstation: run the CPU workers at the lowest scheduling priority (SCHED_IDLE on Linux, nice 19 on other Unixes) and cap them at 50% of each core unless --cpu-limit is given. The limit can be changed with + and - in the TUI"
+    )]
+    pub background: bool,
+    #[arg(
+        long = "cpu-limit",
+        help = "Cap the CPU workers at this percentage of each core, 5 to 100, by resting after each chunk of keys (default: 100, or 50 with --background). The limit can be changed with + and - in the TUI",
+        value_name = "PERCENT",
+        value_parser = Cli::validate_cpu_limit
+    )]
+    pub cpu_limit: Option<u32>,
     #[arg(
         short = 'g',
         long = "gpu",
@@ -53,16 +182,108 @@ pub struct Cli {
     #[arg(
         short = 'n',
         long = "num-addresses",
-        help = "Number of addresses to find before stopping automatically (default: 1, 0 = never stop)",
+        help = "Number of addresses to find before stopping automatically (default: 1, 0 = never stop). With per-prefix quotas, the quota of the prefixes without one",
         default_value = "1",
         value_parser = Cli::validate_num_addresses
     )]
     pub num_addresses: u32,
+    #[arg(
+        long = "json",
+        help = "Write JSON Lines events to stdout and logs to stderr instead of the TUI. Used automatically when stdout is not a terminal",
+        default_value = "false"
+    )]
+    pub json: bool,
+    #[arg(
+        long = "dry-run",
+        help = "Print the match probability of each prefix, the expected attempts and the expected time to a hit at a few rates, without starting the search"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        short = 'o',
+        long = "output",
+        help = "CSV file every found address is appended to as soon as it is confirmed. An existing file is only appended to if its results come from the same xpub"
+    )]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long = "seeds",
+        help = "First two path indexes of the search, as 'seed0,seed1' (default: random). A run with the same seeds, path template, max depth and devices searches the same paths again",
+        value_parser = Seeds::parse
+    )]
+    pub seeds: Option<Seeds>,
+    #[arg(
+        long = "run-key",
+        help = "Derive the seeds from this text instead of passing them with --seeds",
+        conflicts_with = "seeds"
+    )]
+    pub run_key: Option<String>,
+    #[arg(
+        long = "checkpoint",
+        help = "File the search progress is saved to every 30 seconds and on exit. Refused if it exists, unless --resume is given"
+    )]
+    pub checkpoint: Option<PathBuf>,
+    #[arg(
+        long = "resume",
+        help = "Continue the search saved in the --checkpoint file, with the same xpub, prefixes, path template, max depth and devices",
+        requires = "checkpoint",
+        conflicts_with_all = ["seeds", "run_key", "shard"],
+        default_value = "false"
+    )]
+    pub resume: bool,
+    #[arg(
+        long = "shard",
+        help = "Search only part of the paths, to split a search between machines: 'index/count' (from 0, e.g. 2/8) or an explicit counter range 'start..end'. Every machine needs the same --seeds or --run-key",
+        value_parser = Shard::parse
+    )]
+    pub shard: Option<Shard>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Combine the results files of several shards into one, without duplicates
+    Merge {
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "Results file the rows are appended to, created if needed"
+        )]
+        output: PathBuf,
+        #[arg(help = "Results files to merge", num_args = 1.., required = true)]
+        inputs: Vec<PathBuf>,
+    },
 }
 
 impl Cli {
     pub fn parse_args() -> Self {
-        let cli = Self::parse();
+        let mut cli = Self::parse();
+        if cli.command.is_some() {
+            return cli;
+        }
+        cli.xpub = match (&cli.xpub_arg, &cli.descriptor) {
+            (Some(xpub), _) => xpub.clone(),
+            (None, Some(descriptor)) => descriptor.xpub.clone(),
+            (None, None) => unreachable!("clap requires --xpub or --descriptor"),
+        };
+        if let Some(gap_limit) = cli.gap_limit {
+            cli.max_depth = gap_limit;
+        }
+        let prefixes = match &cli.longest {
+            Some(target) => longest_match::target_prefixes(target, cli.min_match, cli.network)
+                .map(|(min_match, prefixes)| {
+                    cli.min_match = Some(min_match);
+                    prefixes.into_iter().map(|prefix| (prefix, None)).collect()
+                })
+                .map_err(|e| format!("Error: Invalid --longest target '{}': {}", target, e)),
+            None => cli.build_prefixes(),
+        };
+        (cli.prefixes, cli.quotas) = match prefixes {
+            Ok(prefixes) => prefixes.into_iter().unzip(),
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(1);
+            }
+        };
         if let Err(msg) = cli.validate_conflicting_options() {
             eprintln!("{}", msg);
             std::process::exit(1);
@@ -70,7 +291,171 @@ impl Cli {
         cli
     }
 
+    /// Counters this machine searches: its shard or the whole counter space
+    pub fn search_range(&self) -> Range<u64> {
+        match &self.shard {
+            Some(shard) => shard
+                .counter_range(&self.path_template, self.max_depth)
+                .expect("The shard is validated with the arguments"),
+            None => 0..counter_space(&self.path_template, self.max_depth),
+        }
+    }
+
+    /// Time and keys the search may spend
+    pub fn budget(&self) -> Budget {
+        Budget {
+            time: self.deadline,
+            keys: self.max_keys,
+        }
+    }
+
+    /// CPU limit and priority of the CPU workbench
+    pub fn cpu_throttle(&self) -> CpuThrottle {
+        let default_limit = if self.background {
+            DEFAULT_BACKGROUND_CPU_LIMIT
+        } else {
+            MAX_CPU_LIMIT
+        };
+        CpuThrottle::new(self.cpu_limit.unwrap_or(default_limit), self.background)
+    }
+
+    /// Per-prefix quotas as soon as one prefix has one, the others taking
+    /// --num-addresses; otherwise --num-addresses of any prefixes
+    pub fn quota(&self) -> Quota {
+        if self.quotas.iter().all(Option::is_none) {
+            return Quota::Total(self.num_addresses);
+        }
+        Quota::PerPrefix(
+            self.quotas
+                .iter()
+                .map(|quota| quota.unwrap_or(self.num_addresses))
+                .collect(),
+        )
+    }
+
+    /// Seeds of the run: pinned, derived from the run key or random
+    pub fn run_seeds(&self) -> Seeds {
+        match (&self.seeds, &self.run_key) {
+            (Some(seeds), _) => *seeds,
+            (None, Some(run_key)) => Seeds::from_run_key(run_key),
+            (None, None) => Seeds::random(),
+        }
+    }
+
+    /// The --prefix patterns then the prefix file's with their quotas, each
+    /// pattern once: the first quota given for it counts
+    fn build_prefixes(&self) -> Result<Vec<(Prefix, Option<u32>)>, String> {
+        let mut seen = HashSet::new();
+        let mut prefixes = Vec::new();
+        for text in &self.patterns {
+            let (pattern, quota) = quota::split_quota(text)
+                .map_err(|e| format!("Error: Invalid prefix '{}': {}", text, e))?;
+            if seen.insert(pattern.to_string()) {
+                let prefix = self
+                    .build_prefix(pattern)
+                    .map_err(|e| format!("Error: Invalid prefix '{}': {}", pattern, e))?;
+                prefixes.push((prefix, quota));
+            }
+        }
+
+        if let Some(path) = &self.prefix_file {
+            for (line_number, text) in Self::read_prefix_file(path)? {
+                let invalid = |e: String| {
+                    format!(
+                        "Error: Invalid prefix '{}' on line {} of {}: {}",
+                        text,
+                        line_number,
+                        path.display(),
+                        e
+                    )
+                };
+                let (pattern, quota) = quota::split_quota(&text).map_err(invalid)?;
+                if seen.insert(pattern.to_string()) {
+                    let prefix = self.build_prefix(pattern).map_err(invalid)?;
+                    prefixes.push((prefix, quota));
+                }
+            }
+        }
+        Ok(prefixes)
+    }
+
+    fn build_prefix(&self, pattern: &str) -> Result<Prefix, String> {
+        if self.case_insensitive {
+            Prefix::new_case_insensitive(pattern, self.network)
+        } else {
+            Self::validate_prefix(pattern, self.network)
+        }
+    }
+
+    /// Patterns of a wordlist with their line numbers, from 1
+    fn read_prefix_file(path: &Path) -> Result<Vec<(usize, String)>, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error: Cannot read prefix file {}: {}", path.display(), e))?;
+        Ok(contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line_number, line)| (line_number, line.to_string()))
+            .collect())
+    }
+
     fn validate_conflicting_options(&self) -> Result<(), String> {
+        let key_version =
+            KeyVersion::of(&self.xpub).map_err(|e| format!("Error: Invalid xpub: {}", e))?;
+        if key_version.testnet != (self.network != Network::Mainnet) {
+            return Err(format!(
+                "Error: --network {} does not take '{}' keys.",
+                self.network.name(),
+                key_version.name
+            ));
+        }
+
+        // A wallet only shows the addresses of its key's script type
+        if key_version.is_multisig() {
+            return Err(format!(
+                "Error: '{}' keys belong to multisig wallets, their addresses need every cosigner's key.",
+                key_version.name
+            ));
+        }
+        if let Some(prefix) = self
+            .prefixes
+            .iter()
+            .find(|prefix| !key_version.supports(&prefix.address_type))
+        {
+            return Err(format!(
+                "Error: Prefix '{}' does not match the '{}' key: its wallet only shows {} addresses.",
+                prefix.as_str(),
+                key_version.name,
+                key_version.script_type.name()
+            ));
+        }
+
+        // The descriptor's script tells which addresses its wallet shows
+        if let Some(descriptor) = &self.descriptor {
+            if let Some(prefix) = self
+                .prefixes
+                .iter()
+                .find(|prefix| prefix.address_type != descriptor.address_type)
+            {
+                return Err(format!(
+                    "Error: Prefix '{}' does not match the descriptor: its wallet only shows {} addresses.",
+                    prefix.as_str(),
+                    descriptor.address_type.name()
+                ));
+            }
+        }
+
+        // Shards of random seeds would search unrelated paths
+        if let Some(shard) = &self.shard {
+            if self.seeds.is_none() && self.run_key.is_none() {
+                return Err("Error: --shard needs the seeds shared by every machine: pass the same --seeds or --run-key to all of them.".to_string());
+            }
+            shard
+                .counter_range(&self.path_template, self.max_depth)
+                .map_err(|e| format!("Error: {}.", e))?;
+        }
+
         // Check for conflicting --gpu-only and -t/--cpu-threads
         if self.gpu_only && self.cpu_threads != 0 {
             return Err(
@@ -80,16 +465,28 @@ impl Cli {
             );
         }
 
-        // Check prefix count limits
-        if self.prefixes.is_empty() {
-            return Err("Error: At least one prefix must be provided.".to_string());
+        // P2TR matching needs the tweaked output key, the GPU kernel only
+        // computes hash160s
+        let gpu_requested = self.gpu_only || self.gpu.is_some();
+        if gpu_requested
+            && self
+                .prefixes
+                .iter()
+                .any(|prefix| prefix.address_type == AddressType::P2TR)
+        {
+            return Err(
+                "Error: P2TR (bc1p) prefixes are only searched on the CPU.\n       \
+                Remove --gpu/--gpu-only to search for them."
+                    .to_string(),
+            );
         }
 
-        if self.prefixes.len() > 256 {
-            return Err(format!(
-                "Error: Maximum 256 prefixes allowed, but {} were provided.",
-                self.prefixes.len()
-            ));
+        // Check prefix count
+        if self.prefixes.is_empty() {
+            return Err(
+                "Error: At least one prefix must be provided with --prefix or --prefix-file."
+                    .to_string(),
+            );
         }
 
         Ok(())
@@ -111,8 +508,38 @@ impl Cli {
         Ok(max_depth_int)
     }
 
-    fn validate_prefix(prefix: &str) -> Result<Prefix, String> {
-        Prefix::new(prefix)
+    fn validate_gap_limit(gap_limit: &str) -> Result<u32, String> {
+        match Cli::validate_max_depth(gap_limit)? {
+            0 => Err("Gap limit must be at least 1".to_string()),
+            gap_limit => Ok(gap_limit),
+        }
+    }
+
+    fn validate_prefix(prefix: &str, network: Network) -> Result<Prefix, String> {
+        Prefix::new(prefix, network)
+    }
+
+    /// Accepts anything that is a valid pattern in at least one casing on
+    /// at least one network: both are only known after all the arguments
+    /// are parsed. Reports the error of the network the pattern starts like
+    /// otherwise, or the mainnet one.
+    fn validate_pattern(text: &str) -> Result<String, String> {
+        let (pattern, _) = quota::split_quota(text)?;
+        let mut errors = Vec::new();
+        for &network in Network::value_variants() {
+            let result = Self::validate_prefix(pattern, network)
+                .or_else(|e| Prefix::new_case_insensitive(pattern, network).map_err(|_| e));
+            match result {
+                Ok(_) => return Ok(text.to_string()),
+                Err(e) => errors.push((network, e)),
+            }
+        }
+
+        let position = errors
+            .iter()
+            .position(|(network, _)| network.has_leading_chars(pattern))
+            .unwrap_or(0);
+        Err(errors.swap_remove(position).1)
     }
 
     fn validate_xpub(xpub: &str) -> Result<String, String> {
@@ -120,8 +547,11 @@ impl Cli {
         if xpub.is_empty() {
             return Err("Xpub cannot be empty".to_string());
         }
-        if !xpub.starts_with("xpub") {
-            return Err("Xpub should start with 'xpub'.".to_string());
+        if !KeyVersion::has_known_name(xpub) {
+            return Err(format!(
+                "Xpub should start with one of: {}.",
+                KeyVersion::names().join(", ")
+            ));
         }
 
         for c in xpub.chars() {
@@ -141,6 +571,21 @@ impl Cli {
         Ok(threads_int)
     }
 
+    fn validate_cpu_limit(percent: &str) -> Result<u32, String> {
+        let percent = percent.trim_end_matches('%');
+        let limit: u32 = percent.parse().map_err(|e: std::num::ParseIntError| {
+            format!("Invalid CPU limit '{}': {}", percent, e)
+        })?;
+        if !(MIN_CPU_LIMIT..=MAX_CPU_LIMIT).contains(&limit) {
+            return Err(format!(
+                "CPU limit must be between {}% and {}%",
+                MIN_CPU_LIMIT, MAX_CPU_LIMIT
+            ));
+        }
+
+        Ok(limit)
+    }
+
     fn validate_gpu_id(id: &str) -> Result<usize, String> {
         let id_int: usize = id
             .parse()
@@ -167,39 +612,51 @@ impl Cli {
 mod tests {
     use super::*;
 
+    /// The arguments as `parse_args` takes them, without exiting on errors
+    fn parse_cli(args: &[&str]) -> Cli {
+        let mut cli = Cli::try_parse_from(["address-artisan"].iter().chain(args)).unwrap();
+        cli.xpub = match (&cli.xpub_arg, &cli.descriptor) {
+            (Some(xpub), _) => xpub.clone(),
+            (None, Some(descriptor)) => descriptor.xpub.clone(),
+            (None, None) => unreachable!("clap requires --xpub or --descriptor"),
+        };
+        (cli.prefixes, cli.quotas) = cli.build_prefixes().unwrap().into_iter().unzip();
+        cli
+    }
+
     // prefix tests
     #[test]
     fn test_validate_prefix_starts_with_1() {
         let prefix = "123456789ABCDE";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_ok());
     }
 
     #[test]
     fn test_validate_prefix_starts_with_1_failed() {
         let prefix = "23456789ABCDE";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_validate_prefix_empty() {
         let prefix = "";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_validate_prefix_invalid_character() {
         let prefix = "123456789ABCDE!";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_validate_prefix_invalid_base58() {
         let prefix = "123456789ABClE";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_err());
     }
 
@@ -207,7 +664,7 @@ mod tests {
     #[test]
     fn test_validate_prefix_p2wpkh_valid() {
         let prefix = "bc1qaaa";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_ok());
         let prefix_obj = result.unwrap();
         assert_eq!(prefix_obj.address_type, crate::prefix::AddressType::P2WPKH);
@@ -216,14 +673,14 @@ mod tests {
     #[test]
     fn test_validate_prefix_p2wpkh_invalid_char() {
         let prefix = "bc1qabc"; // 'b' and 'c' not valid in bech32
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_validate_prefix_p2wpkh_all() {
         let prefix = "bc1q";
-        let result = Cli::validate_prefix(prefix);
+        let result = Cli::validate_prefix(prefix, Network::Mainnet);
         assert!(result.is_ok());
     }
 
@@ -409,13 +866,39 @@ mod tests {
     #[test]
     fn test_validate_conflicting_options_gpu_only_with_cpu_threads() {
         let cli = Cli {
-            prefixes: vec![Prefix::new("1A").unwrap()],
+            patterns: vec![],
+            case_insensitive: false,
+            prefix_file: None,
+            longest: None,
+            min_match: None,
+            deadline: None,
+            max_keys: None,
+            quotas: vec![],
+            prefixes: vec![Prefix::new("1A", Network::Mainnet).unwrap()],
             xpub: "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn".to_string(),
+            xpub_arg: None,
+            descriptor: None,
+            network: Network::Mainnet,
             max_depth: 1000,
+            gap_limit: None,
             cpu_threads: 4,
+            pin_threads: false,
+            reserve_gpu_cores: 0,
+            background: false,
+            cpu_limit: None,
             gpu: None,
             gpu_only: true,
             num_addresses: 1,
+            json: false,
+            dry_run: false,
+            output: None,
+            seeds: None,
+            run_key: None,
+            checkpoint: None,
+            resume: false,
+            shard: None,
+            path_template: PathTemplate::default(),
+            command: None,
         };
 
         let result = cli.validate_conflicting_options();
@@ -428,13 +911,39 @@ mod tests {
     #[test]
     fn test_validate_conflicting_options_gpu_only_with_zero_threads() {
         let cli = Cli {
-            prefixes: vec![Prefix::new("1A").unwrap()],
+            patterns: vec![],
+            case_insensitive: false,
+            prefix_file: None,
+            longest: None,
+            min_match: None,
+            deadline: None,
+            max_keys: None,
+            quotas: vec![],
+            prefixes: vec![Prefix::new("1A", Network::Mainnet).unwrap()],
             xpub: "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn".to_string(),
+            xpub_arg: None,
+            descriptor: None,
+            network: Network::Mainnet,
             max_depth: 1000,
+            gap_limit: None,
             cpu_threads: 0, // 0 means auto-detect, which is valid with gpu_only
+            pin_threads: false,
+            reserve_gpu_cores: 0,
+            background: false,
+            cpu_limit: None,
             gpu: None,
             gpu_only: true,
             num_addresses: 1,
+            json: false,
+            dry_run: false,
+            output: None,
+            seeds: None,
+            run_key: None,
+            checkpoint: None,
+            resume: false,
+            shard: None,
+            path_template: PathTemplate::default(),
+            command: None,
         };
 
         let result = cli.validate_conflicting_options();
@@ -444,13 +953,39 @@ mod tests {
     #[test]
     fn test_validate_conflicting_options_no_gpu_only_with_threads() {
         let cli = Cli {
-            prefixes: vec![Prefix::new("1A").unwrap()],
+            patterns: vec![],
+            case_insensitive: false,
+            prefix_file: None,
+            longest: None,
+            min_match: None,
+            deadline: None,
+            max_keys: None,
+            quotas: vec![],
+            prefixes: vec![Prefix::new("1A", Network::Mainnet).unwrap()],
             xpub: "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn".to_string(),
+            xpub_arg: None,
+            descriptor: None,
+            network: Network::Mainnet,
             max_depth: 1000,
+            gap_limit: None,
             cpu_threads: 4,
+            pin_threads: false,
+            reserve_gpu_cores: 0,
+            background: false,
+            cpu_limit: None,
             gpu: None,
             gpu_only: false,
             num_addresses: 1,
+            json: false,
+            dry_run: false,
+            output: None,
+            seeds: None,
+            run_key: None,
+            checkpoint: None,
+            resume: false,
+            shard: None,
+            path_template: PathTemplate::default(),
+            command: None,
         };
 
         let result = cli.validate_conflicting_options();
@@ -461,13 +996,39 @@ mod tests {
     #[test]
     fn test_validate_multiple_prefixes_valid() {
         let cli = Cli {
-            prefixes: vec![Prefix::new("1A").unwrap(), Prefix::new("1B").unwrap()],
+            patterns: vec![],
+            case_insensitive: false,
+            prefix_file: None,
+            longest: None,
+            min_match: None,
+            deadline: None,
+            max_keys: None,
+            quotas: vec![],
+            prefixes: vec![Prefix::new("1A", Network::Mainnet).unwrap(), Prefix::new("1B", Network::Mainnet).unwrap()],
             xpub: "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn".to_string(),
+            xpub_arg: None,
+            descriptor: None,
+            network: Network::Mainnet,
             max_depth: 1000,
+            gap_limit: None,
             cpu_threads: 4,
+            pin_threads: false,
+            reserve_gpu_cores: 0,
+            background: false,
+            cpu_limit: None,
             gpu: None,
             gpu_only: false,
             num_addresses: 1,
+            json: false,
+            dry_run: false,
+            output: None,
+            seeds: None,
+            run_key: None,
+            checkpoint: None,
+            resume: false,
+            shard: None,
+            path_template: PathTemplate::default(),
+            command: None,
         };
 
         let result = cli.validate_conflicting_options();
@@ -477,13 +1038,39 @@ mod tests {
     #[test]
     fn test_validate_empty_prefixes() {
         let cli = Cli {
+            patterns: vec![],
+            case_insensitive: false,
+            prefix_file: None,
+            longest: None,
+            min_match: None,
+            deadline: None,
+            max_keys: None,
+            quotas: vec![],
             prefixes: vec![],
             xpub: "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn".to_string(),
+            xpub_arg: None,
+            descriptor: None,
+            network: Network::Mainnet,
             max_depth: 1000,
+            gap_limit: None,
             cpu_threads: 4,
+            pin_threads: false,
+            reserve_gpu_cores: 0,
+            background: false,
+            cpu_limit: None,
             gpu: None,
             gpu_only: false,
             num_addresses: 1,
+            json: false,
+            dry_run: false,
+            output: None,
+            seeds: None,
+            run_key: None,
+            checkpoint: None,
+            resume: false,
+            shard: None,
+            path_template: PathTemplate::default(),
+            command: None,
         };
 
         let result = cli.validate_conflicting_options();
@@ -494,25 +1081,466 @@ mod tests {
     }
 
     #[test]
-    fn test_validate_too_many_prefixes() {
-        let mut prefixes = Vec::new();
-        for _ in 0..257 {
-            // Generate 257 prefixes
-            prefixes.push(Prefix::new("1A").unwrap());
+    fn test_validate_thousands_of_prefixes() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let mut cli = parse_cli(&["-p", "1A", "-x", xpub]);
+        cli.prefixes = vec![Prefix::new("1A", Network::Mainnet).unwrap(); 10_000];
+
+        assert!(cli.validate_conflicting_options().is_ok());
+    }
+
+    #[test]
+    fn test_validate_pattern_accepts_any_casing() {
+        // 'l' is not base58, but 'L' is
+        assert!(Cli::validate_prefix("1love", Network::Mainnet).is_err());
+        assert_eq!(Cli::validate_pattern("1love").unwrap(), "1love");
+        assert!(Cli::validate_pattern("1l0ve").is_err());
+    }
+
+    #[test]
+    fn test_build_prefixes_case_insensitive() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let mut cli = parse_cli(&["-p", "1love,1Bob", "-i", "-x", xpub]);
+
+        assert_eq!(cli.prefixes.len(), 2);
+        assert!(cli.prefixes.iter().all(|prefix| prefix.case_insensitive));
+
+        cli.case_insensitive = false;
+        assert!(cli
+            .build_prefixes()
+            .unwrap_err()
+            .contains("Invalid prefix '1love'"));
+    }
+
+    #[test]
+    fn test_build_prefixes_from_file() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let path = std::env::temp_dir().join(format!(
+            "address-artisan-prefixes-{}.txt",
+            std::process::id()
+        ));
+        fs::write(&path, "# names\n1Bob\n\n  1Ann  \n1Bob\nbc1qxy\n").unwrap();
+
+        let mut cli = parse_cli(&[
+            "-p",
+            "1Ann,1Eve",
+            "--prefix-file",
+            path.to_str().unwrap(),
+            "-x",
+            xpub,
+        ]);
+
+        // --prefix first, every pattern once
+        let patterns: Vec<&str> = cli.prefixes.iter().map(Prefix::as_str).collect();
+        assert_eq!(patterns, vec!["1Ann", "1Eve", "1Bob", "bc1qxy"]);
+
+        fs::write(&path, "1Bob\n1B0b\n").unwrap();
+        let err = cli.build_prefixes().unwrap_err();
+        assert!(
+            err.contains("Invalid prefix '1B0b' on line 2 of"),
+            "{}",
+            err
+        );
+
+        fs::remove_file(&path).unwrap();
+        cli.prefix_file = Some(path);
+        assert!(cli
+            .build_prefixes()
+            .unwrap_err()
+            .contains("Cannot read prefix file"));
+    }
+
+    #[test]
+    fn test_validate_p2tr_prefix_rejects_gpu() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let mut cli = parse_cli(&["-p", "bc1pxyz", "-x", xpub, "--gpu"]);
+
+        assert!(cli
+            .validate_conflicting_options()
+            .unwrap_err()
+            .contains("only searched on the CPU"));
+
+        cli.gpu = None;
+        assert!(cli.validate_conflicting_options().is_ok());
+    }
+
+    #[test]
+    fn test_validate_network_matches_xpub() {
+        let tpub = "tpubDCthgcrNduJHHyErmegKUF38GTioPpkwG7yiANbqpWmxuSPGVi1jj54VVTQ3TzPT77eGkaEk9XGkSkjpBHjM8gm2V4gQrBhnBAihiFHJuRe";
+        let mut cli = parse_cli(&["-p", "tb1q", "-x", tpub, "--network", "testnet"]);
+        assert!(cli.validate_conflicting_options().is_ok());
+
+        cli.network = Network::Mainnet;
+        assert!(cli
+            .validate_conflicting_options()
+            .unwrap_err()
+            .contains("--network mainnet does not take 'tpub' keys"));
+    }
+
+    #[test]
+    fn test_validate_pattern_accepts_any_network() {
+        assert!(Cli::validate_prefix("tb1qxy", Network::Mainnet).is_err());
+        assert_eq!(Cli::validate_pattern("tb1qxy").unwrap(), "tb1qxy");
+        assert!(Cli::validate_pattern("xb1qxy")
+            .unwrap_err()
+            .contains("'1' (P2PKH)"));
+        // The error of the network whose addresses start like the pattern
+        assert_eq!(
+            Cli::validate_pattern("mA").unwrap_err(),
+            "No address can start with 'mA'"
+        );
+    }
+
+    #[test]
+    fn test_validate_xpub_accepts_tpub() {
+        assert!(Cli::validate_xpub("tpubDCthgcrNduJHHyErmegKUF38GTio").is_ok());
+        assert!(Cli::validate_xpub("qpubDCthgcrNduJHHyErmegKUF38GTio").is_err());
+    }
+
+    #[test]
+    fn test_validate_key_script_type() {
+        let zpub = "zpub6rFq6u6y1V6FaudwXoagq7Gtm3brGbcYbneJGjuFQGPWVTjY8qMJckD7S3B8H9mrQbkwtm5dWxNUG5zn1QW5HAMS1Tc289Gnr4xVFyENzte";
+        let mut cli = parse_cli(&["-p", "bc1qxy", "-x", zpub]);
+        assert!(cli.validate_conflicting_options().is_ok());
+
+        cli.prefixes = vec![Prefix::new("1A", Network::Mainnet).unwrap()];
+        assert!(cli
+            .validate_conflicting_options()
+            .unwrap_err()
+            .contains("only shows P2WPKH addresses"));
+
+        cli.xpub = "ypub6XRZoES3roYmjcSphSo4d2BPb5TQKyd3gg85VM1N2G1dSMvJtBBjzgYyQqDYHF7vzxe99HV54J1vNoPDHi64Uvfq97ubYETJaLtqsQ44fGS".to_string();
+        cli.prefixes = vec![Prefix::new("3A", Network::Mainnet).unwrap()];
+        assert!(cli.validate_conflicting_options().is_ok());
+
+        cli.xpub = "Zpub739vE8qQaSed1UoKUU3ffBchUqe7UxJ8v4HyC1Ann3Dv7eJSuEjahs53Ek8bqazke4pvmM5kQAkyPFcYJdf2ReU6qviRXYjnjoEMqu843VX".to_string();
+        assert!(cli
+            .validate_conflicting_options()
+            .unwrap_err()
+            .contains("multisig"));
+    }
+
+    #[test]
+    fn test_validate_xpub_accepts_slip132() {
+        for key in [
+            "ypub6XRZoES3",
+            "zpub6rFq6u6y",
+            "Zpub739vE8qQ",
+            "vpub5YrYZ3gr",
+        ] {
+            assert!(Cli::validate_xpub(key).is_ok(), "{}", key);
         }
+        assert!(Cli::validate_xpub("apub6rFq6u6y")
+            .unwrap_err()
+            .contains("xpub, ypub, zpub"));
+    }
 
-        let cli = Cli {
-            prefixes,
-            xpub: "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn".to_string(),
-            max_depth: 1000,
-            cpu_threads: 4,
-            gpu: None,
-            gpu_only: false,
-            num_addresses: 1,
+    #[test]
+    fn test_validate_descriptor_address_type() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let descriptor = format!("wpkh([d34db33f/84'/0'/0']{}/<0;1>/*)#mams3jda", xpub);
+        let mut cli = parse_cli(&["-p", "bc1qxy", "-d", &descriptor]);
+        assert_eq!(cli.xpub, xpub);
+        assert!(cli.validate_conflicting_options().is_ok());
+
+        cli.prefixes = vec![Prefix::new("bc1pxy", Network::Mainnet).unwrap()];
+        assert!(cli
+            .validate_conflicting_options()
+            .unwrap_err()
+            .contains("does not match the descriptor: its wallet only shows P2WPKH addresses"));
+    }
+
+    #[test]
+    fn test_descriptor_conflicts_with_xpub() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let descriptor = format!("pkh({})#00ws5atg", xpub);
+
+        let cli = Cli::try_parse_from(["address-artisan", "-p", "1A", "--descriptor", &descriptor])
+            .unwrap();
+        assert_eq!(cli.descriptor.unwrap().xpub, xpub);
+        assert!(Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "--descriptor",
+            &descriptor,
+            "--xpub",
+            xpub
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["address-artisan", "-p", "1A"]).is_err());
+    }
+
+    #[test]
+    fn test_run_seeds() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+
+        let cli =
+            Cli::try_parse_from(["address-artisan", "-p", "1A", "-x", xpub, "--seeds", "5,7"])
+                .unwrap();
+        assert_eq!(cli.run_seeds(), Seeds { seed0: 5, seed1: 7 });
+
+        let cli = Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "-x",
+            xpub,
+            "--run-key",
+            "abc",
+        ])
+        .unwrap();
+        assert_eq!(cli.run_seeds(), Seeds::from_run_key("abc"));
+        assert_eq!(cli.run_seeds(), cli.run_seeds());
+
+        assert!(Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "-x",
+            xpub,
+            "--seeds",
+            "5,7",
+            "--run-key",
+            "abc"
+        ])
+        .is_err());
+    }
+    #[test]
+    fn test_resume_requires_checkpoint_and_keeps_its_seeds() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+
+        let cli = Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "-x",
+            xpub,
+            "--checkpoint",
+            "search.checkpoint",
+            "--resume",
+        ])
+        .unwrap();
+        assert!(cli.resume);
+
+        assert!(
+            Cli::try_parse_from(["address-artisan", "-p", "1A", "-x", xpub, "--resume"]).is_err()
+        );
+        assert!(Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "-x",
+            xpub,
+            "--checkpoint",
+            "search.checkpoint",
+            "--resume",
+            "--seeds",
+            "5,7"
+        ])
+        .is_err());
+    }
+    #[test]
+    fn test_shard_needs_shared_seeds() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let parse = |args: &[&str]| {
+            let mut cli = Cli::try_parse_from(
+                ["address-artisan", "-p", "1A", "-x", xpub]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap();
+            cli.xpub = xpub.to_string();
+            (cli.prefixes, cli.quotas) = cli.build_prefixes().unwrap().into_iter().unzip();
+            cli
         };
 
-        let result = cli.validate_conflicting_options();
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Maximum 256 prefixes allowed"));
+        let cli = parse(&["--shard", "1/4", "--run-key", "abc"]);
+        assert!(cli.validate_conflicting_options().is_ok());
+        assert_eq!(
+            cli.search_range(),
+            Shard::Index { index: 1, count: 4 }
+                .counter_range(&PathTemplate::default(), 1000)
+                .unwrap()
+        );
+
+        assert!(parse(&["--shard", "1/4"])
+            .validate_conflicting_options()
+            .unwrap_err()
+            .contains("pass the same --seeds or --run-key"));
+        assert!(parse(&[
+            "--shard",
+            "0..18446744073709551615",
+            "-m",
+            "1",
+            "--seeds",
+            "5,7"
+        ])
+        .validate_conflicting_options()
+        .unwrap_err()
+        .contains("goes past"));
+        assert_eq!(parse(&[]).search_range(), 0..u64::MAX);
+    }
+
+    #[test]
+    fn test_per_prefix_quotas() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let parse = |args: &[&str]| {
+            let mut cli =
+                Cli::try_parse_from(["address-artisan", "-x", xpub].iter().chain(args)).unwrap();
+            (cli.prefixes, cli.quotas) = cli.build_prefixes().unwrap().into_iter().unzip();
+            cli
+        };
+
+        let cli = parse(&["-p", "1Bob:1,1A:3,1C,1A:5", "-n", "2"]);
+        let patterns: Vec<&str> = cli.prefixes.iter().map(Prefix::as_str).collect();
+        assert_eq!(patterns, vec!["1Bob", "1A", "1C"]);
+        assert_eq!(cli.quota(), Quota::PerPrefix(vec![1, 3, 2]));
+
+        assert_eq!(
+            parse(&["-p", "1Bob,1A", "-n", "2"]).quota(),
+            Quota::Total(2)
+        );
+        assert!(Cli::try_parse_from(["address-artisan", "-x", xpub, "-p", "1A:0"]).is_err());
+    }
+
+    #[test]
+    fn test_longest_mode_arguments() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let parse =
+            |args: &[&str]| Cli::try_parse_from(["address-artisan", "-x", xpub].iter().chain(args));
+
+        let cli = parse(&[
+            "--longest",
+            "1BitcoinEater",
+            "--min-match",
+            "5",
+            "--deadline",
+            "1.5h",
+            "--max-keys",
+            "2G",
+        ])
+        .unwrap();
+        assert_eq!(cli.longest.as_deref(), Some("1BitcoinEater"));
+        assert_eq!(cli.min_match, Some(5));
+        assert_eq!(
+            cli.budget(),
+            Budget {
+                time: Some(Duration::from_secs(5400)),
+                keys: Some(2_000_000_000)
+            }
+        );
+
+        // The target replaces the prefixes
+        assert!(parse(&["--longest", "1BitcoinEater", "-p", "1A"]).is_err());
+        assert!(parse(&["-p", "1A", "--min-match", "5"]).is_err());
+        assert!(parse(&["-p", "1A", "--deadline", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_background_mode() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["address-artisan", "-p", "1A", "-x", xpub]
+                    .iter()
+                    .chain(args),
+            )
+        };
+
+        let throttle = parse(&[]).unwrap().cpu_throttle();
+        assert_eq!(throttle.limit(), 100);
+        assert!(!throttle.is_background());
+
+        let throttle = parse(&["--background"]).unwrap().cpu_throttle();
+        assert_eq!(throttle.limit(), 50);
+        assert!(throttle.is_background());
+
+        let throttle = parse(&["--background", "--cpu-limit", "20%"])
+            .unwrap()
+            .cpu_throttle();
+        assert_eq!(throttle.limit(), 20);
+        assert_eq!(
+            parse(&["--cpu-limit", "75"])
+                .unwrap()
+                .cpu_throttle()
+                .limit(),
+            75
+        );
+
+        assert!(parse(&["--cpu-limit", "0"]).is_err());
+        assert!(parse(&["--cpu-limit", "101"]).is_err());
+        assert!(parse(&["--cpu-limit", "half"]).is_err());
+    }
+
+    #[test]
+    fn test_gap_limit() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["address-artisan", "-p", "1A", "-x", xpub]
+                    .iter()
+                    .chain(args),
+            )
+        };
+
+        let cli = parse(&["--gap-limit", "20"]).unwrap();
+        assert_eq!(cli.gap_limit, Some(20));
+        assert_eq!(cli.max_depth, DEFAULT_MAX_DEPTH);
+        assert!(parse(&["--gap-limit", "20", "-m", "100"]).is_err());
+        assert!(parse(&["--gap-limit", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_path_template() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let cli = Cli::try_parse_from(["address-artisan", "-p", "1A", "-x", xpub]).unwrap();
+        assert_eq!(cli.path_template, PathTemplate::default());
+
+        let cli = Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "-x",
+            xpub,
+            "--path-template",
+            "s0/a/0/i",
+        ])
+        .unwrap();
+        assert_eq!(cli.path_template.to_string(), "s0/a/0/i");
+        assert_eq!(cli.search_range(), 0..1000 * (1 << 31));
+
+        assert!(Cli::try_parse_from([
+            "address-artisan",
+            "-p",
+            "1A",
+            "-x",
+            xpub,
+            "--path-template",
+            "s0/i/0",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_merge_command_needs_no_search_arguments() {
+        let cli = Cli::try_parse_from([
+            "address-artisan",
+            "merge",
+            "-o",
+            "all.csv",
+            "a.csv",
+            "b.csv",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Merge { output, inputs }) => {
+                assert_eq!(output, PathBuf::from("all.csv"));
+                assert_eq!(inputs, vec![PathBuf::from("a.csv"), PathBuf::from("b.csv")]);
+            }
+            None => panic!("Expected the merge command"),
+        }
+
+        assert!(Cli::try_parse_from(["address-artisan", "merge", "-o", "all.csv"]).is_err());
     }
 }
@@ -0,0 +1,449 @@
+use crate::opencl::g_tables::{g_tables, WINDOW_COUNT, WINDOW_SIZE};
+use std::sync::OnceLock;
+
+/// 2^256 - p: reducing mod p folds the bits above 256 back in times this
+const P_COMPLEMENT: u64 = 0x1_0000_03D1;
+/// The field prime p = 2^256 - 2^32 - 977, little-endian limbs
+const P: [u64; 4] = [
+    0xFFFF_FFFE_FFFF_FC2F,
+    0xFFFF_FFFF_FFFF_FFFF,
+    0xFFFF_FFFF_FFFF_FFFF,
+    0xFFFF_FFFF_FFFF_FFFF,
+];
+
+/// An element of the secp256k1 base field, always below p. Not constant
+/// time: only public keys go through it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FieldElement([u64; 4]);
+
+impl FieldElement {
+    const ZERO: FieldElement = FieldElement([0; 4]);
+    const ONE: FieldElement = FieldElement([1, 0, 0, 0]);
+
+    /// `bytes` must be below p, as coordinates of valid points are
+    pub fn from_be_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let offset = 32 - 8 * (i + 1);
+            *limb = u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        }
+        FieldElement(limbs)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let offset = 32 - 8 * (i + 1);
+            bytes[offset..offset + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    fn is_odd(&self) -> bool {
+        self.0[0] & 1 == 1
+    }
+
+    /// `value` if it is below p, `value - p` otherwise, for values below
+    /// 2^256 + p given as their low 256 bits and the carry above them
+    fn reduce_once(value: [u64; 4], carry: bool) -> Self {
+        // value >= p exactly when value + (2^256 - p) overflows 256 bits
+        let (reduced, overflow) = add_limbs(&value, &[P_COMPLEMENT, 0, 0, 0]);
+        FieldElement(if carry || overflow { reduced } else { value })
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let (sum, carry) = add_limbs(&self.0, &other.0);
+        Self::reduce_once(sum, carry)
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        let (difference, borrow) = sub_limbs(&self.0, &other.0);
+        if borrow {
+            // + p, the 2^256 wraps away
+            FieldElement(sub_limbs(&difference, &[P_COMPLEMENT, 0, 0, 0]).0)
+        } else {
+            FieldElement(difference)
+        }
+    }
+
+    fn double(&self) -> Self {
+        self.add(self)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut product = [0u64; 8];
+        for i in 0..4 {
+            let mut carry = 0u128;
+            for j in 0..4 {
+                let value = product[i + j] as u128 + self.0[i] as u128 * other.0[j] as u128 + carry;
+                product[i + j] = value as u64;
+                carry = value >> 64;
+            }
+            product[i + 4] = carry as u64;
+        }
+
+        // 2^256 = 2^256 - p (mod p): fold the high half in twice
+        let mut folded = [0u64; 4];
+        let mut carry = 0u128;
+        for i in 0..4 {
+            let value = product[i] as u128 + product[i + 4] as u128 * P_COMPLEMENT as u128 + carry;
+            folded[i] = value as u64;
+            carry = value >> 64;
+        }
+        let (folded, overflow) =
+            add_limbs(&folded, &widen(carry as u64 as u128 * P_COMPLEMENT as u128));
+        // An overflow leaves a small value: adding 2^256 - p once more fits
+        let (folded, _) = if overflow {
+            add_limbs(&folded, &[P_COMPLEMENT, 0, 0, 0])
+        } else {
+            (folded, false)
+        };
+        Self::reduce_once(folded, false)
+    }
+
+    fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    /// self^(p - 2), by Fermat's little theorem. `self` must not be zero.
+    fn invert(&self) -> Self {
+        let mut exponent = P;
+        exponent[0] -= 2;
+
+        let mut result = FieldElement::ONE;
+        for limb in exponent.iter().rev() {
+            for bit in (0..64).rev() {
+                result = result.square();
+                if (limb >> bit) & 1 == 1 {
+                    result = result.mul(self);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn add_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut sum = [0u64; 4];
+    let mut carry = false;
+    for i in 0..4 {
+        let (value, overflow_a) = a[i].overflowing_add(b[i]);
+        let (value, overflow_b) = value.overflowing_add(carry as u64);
+        sum[i] = value;
+        carry = overflow_a || overflow_b;
+    }
+    (sum, carry)
+}
+
+fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut difference = [0u64; 4];
+    let mut borrow = false;
+    for i in 0..4 {
+        let (value, underflow_a) = a[i].overflowing_sub(b[i]);
+        let (value, underflow_b) = value.overflowing_sub(borrow as u64);
+        difference[i] = value;
+        borrow = underflow_a || underflow_b;
+    }
+    (difference, borrow)
+}
+
+fn widen(value: u128) -> [u64; 4] {
+    [value as u64, (value >> 64) as u64, 0, 0]
+}
+
+/// A point of the curve in affine coordinates, never the point at infinity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AffinePoint {
+    pub x: FieldElement,
+    pub y: FieldElement,
+}
+
+impl AffinePoint {
+    /// From the 65-byte SEC1 encoding, `0x04 || x || y`
+    pub fn from_uncompressed(bytes: &[u8; 65]) -> Self {
+        AffinePoint {
+            x: FieldElement::from_be_bytes(bytes[1..33].try_into().unwrap()),
+            y: FieldElement::from_be_bytes(bytes[33..65].try_into().unwrap()),
+        }
+    }
+
+    pub fn serialize(&self) -> [u8; 33] {
+        let mut bytes = [0u8; 33];
+        bytes[0] = if self.y.is_odd() { 0x03 } else { 0x02 };
+        bytes[1..].copy_from_slice(&self.x.to_be_bytes());
+        bytes
+    }
+
+    pub fn serialize_uncompressed(&self) -> [u8; 65] {
+        let mut bytes = [0u8; 65];
+        bytes[0] = 0x04;
+        bytes[1..33].copy_from_slice(&self.x.to_be_bytes());
+        bytes[33..].copy_from_slice(&self.y.to_be_bytes());
+        bytes
+    }
+}
+
+/// A point in Jacobian coordinates, (X / Z^2, Y / Z^3) in affine ones: adds
+/// without the field inversion an affine addition needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JacobianPoint {
+    x: FieldElement,
+    y: FieldElement,
+    z: FieldElement,
+}
+
+impl JacobianPoint {
+    pub const INFINITY: JacobianPoint = JacobianPoint {
+        x: FieldElement::ONE,
+        y: FieldElement::ONE,
+        z: FieldElement::ZERO,
+    };
+
+    pub fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    /// self + `other`, 8 multiplications and 3 squarings
+    pub fn add_affine(&self, other: &AffinePoint) -> JacobianPoint {
+        if self.is_infinity() {
+            return JacobianPoint::from(*other);
+        }
+
+        let z1z1 = self.z.square();
+        let u2 = other.x.mul(&z1z1);
+        let s2 = other.y.mul(&self.z).mul(&z1z1);
+        let h = u2.sub(&self.x);
+        let r = s2.sub(&self.y);
+        if h.is_zero() {
+            return if r.is_zero() {
+                self.double()
+            } else {
+                JacobianPoint::INFINITY
+            };
+        }
+
+        let hh = h.square();
+        let hhh = h.mul(&hh);
+        let v = self.x.mul(&hh);
+        let x = r.square().sub(&hhh).sub(&v.double());
+        let y = r.mul(&v.sub(&x)).sub(&self.y.mul(&hhh));
+        let z = self.z.mul(&h);
+        JacobianPoint { x, y, z }
+    }
+
+    /// 2 * self, for a curve with a = 0. secp256k1 has no point of order
+    /// two: the result is only infinity for infinity.
+    pub fn double(&self) -> JacobianPoint {
+        if self.is_infinity() {
+            return *self;
+        }
+
+        let a = self.x.square();
+        let b = self.y.square();
+        let c = b.square();
+        let d = self.x.add(&b).square().sub(&a).sub(&c).double();
+        let e = a.double().add(&a);
+        let x = e.square().sub(&d.double());
+        let y = e.mul(&d.sub(&x)).sub(&c.double().double().double());
+        let z = self.y.mul(&self.z).double();
+        JacobianPoint { x, y, z }
+    }
+}
+
+impl From<AffinePoint> for JacobianPoint {
+    fn from(point: AffinePoint) -> Self {
+        JacobianPoint {
+            x: point.x,
+            y: point.y,
+            z: FieldElement::ONE,
+        }
+    }
+}
+
+/// Affine points of many Jacobian ones with a single field inversion
+/// (Montgomery's trick), None for the point at infinity
+pub fn batch_normalize(points: &[JacobianPoint]) -> Vec<Option<AffinePoint>> {
+    // products[i]: the product of the non-zero z of points[..=i]
+    let mut products = Vec::with_capacity(points.len());
+    let mut product = FieldElement::ONE;
+    for point in points {
+        if !point.is_infinity() {
+            product = product.mul(&point.z);
+        }
+        products.push(product);
+    }
+
+    let mut inverse = product.invert();
+    let mut affine = vec![None; points.len()];
+    for i in (0..points.len()).rev() {
+        let point = &points[i];
+        if point.is_infinity() {
+            continue;
+        }
+        // 1 / z_i, then 1 / (z_0 ... z_(i-1)) for the next one
+        let previous = if i == 0 {
+            FieldElement::ONE
+        } else {
+            products[i - 1]
+        };
+        let z_inverse = inverse.mul(&previous);
+        inverse = inverse.mul(&point.z);
+
+        let z_inverse2 = z_inverse.square();
+        affine[i] = Some(AffinePoint {
+            x: point.x.mul(&z_inverse2),
+            y: point.y.mul(&z_inverse2.mul(&z_inverse)),
+        });
+    }
+    affine
+}
+
+static COMB_TABLES: OnceLock<Vec<AffinePoint>> = OnceLock::new();
+
+/// The GPU's fixed-base tables as CPU field elements:
+/// `tables[w * 256 + d] = d * 256^w * G`
+fn comb_tables() -> &'static [AffinePoint] {
+    COMB_TABLES.get_or_init(|| {
+        g_tables()
+            .iter()
+            .map(|point| {
+                let to_field = |limbs: &[u32; 8]| {
+                    let mut bytes = [0u8; 32];
+                    for (chunk, limb) in bytes.chunks_exact_mut(4).zip(limbs) {
+                        chunk.copy_from_slice(&limb.to_be_bytes());
+                    }
+                    // The infinity sentinel has x = p, it is never added
+                    FieldElement::from_be_bytes(&bytes)
+                };
+                AffinePoint {
+                    x: to_field(&point.x.limbs),
+                    y: to_field(&point.y.limbs),
+                }
+            })
+            .collect()
+    })
+}
+
+/// scalar * G for a big-endian `scalar` below the group order: one table
+/// point added per non-zero byte, no doubling
+pub fn multiply_generator(scalar: &[u8; 32]) -> JacobianPoint {
+    let tables = comb_tables();
+    let mut result = JacobianPoint::INFINITY;
+    for window in 0..WINDOW_COUNT {
+        let digit = scalar[31 - window] as usize;
+        if digit != 0 {
+            result = result.add_affine(&tables[window * WINDOW_SIZE + digit]);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    fn generator_multiple(scalar: &[u8; 32]) -> AffinePoint {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_byte_array(*scalar).unwrap();
+        AffinePoint::from_uncompressed(
+            &PublicKey::from_secret_key(&secp, &secret).serialize_uncompressed(),
+        )
+    }
+
+    fn scalar(seed: u8) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seed
+                .wrapping_mul(31)
+                .wrapping_add((i as u8).wrapping_mul(97));
+        }
+        bytes[0] &= 0x7f; // below the group order
+        bytes
+    }
+
+    #[test]
+    fn test_field_arithmetic() {
+        let minus_one = FieldElement(sub_limbs(&P, &[1, 0, 0, 0]).0);
+        let two = FieldElement::ONE.double();
+
+        assert_eq!(minus_one.add(&two), FieldElement::ONE);
+        assert_eq!(FieldElement::ONE.sub(&two), minus_one);
+        // (-1)^2 = 1
+        assert_eq!(minus_one.square(), FieldElement::ONE);
+
+        let x = FieldElement::from_be_bytes(&scalar(7));
+        assert_eq!(x.mul(&x.invert()), FieldElement::ONE);
+        assert_eq!(x.mul(&minus_one).add(&x), FieldElement::ZERO);
+        assert_eq!(FieldElement::from_be_bytes(&x.to_be_bytes()), x);
+    }
+
+    #[test]
+    fn test_multiply_generator_matches_libsecp256k1() {
+        for seed in 0..20 {
+            let scalar = scalar(seed);
+            let affine = batch_normalize(&[multiply_generator(&scalar)])[0].unwrap();
+            assert_eq!(affine, generator_multiple(&scalar), "seed {}", seed);
+        }
+
+        // A single non-zero byte, and every byte set
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let g = generator_multiple(&one);
+        assert_eq!(batch_normalize(&[multiply_generator(&one)])[0], Some(g));
+        let mut high = [0xffu8; 32];
+        high[0] = 0x7f;
+        assert_eq!(
+            batch_normalize(&[multiply_generator(&high)])[0],
+            Some(generator_multiple(&high))
+        );
+    }
+
+    #[test]
+    fn test_addition_edge_cases() {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let g = generator_multiple(&one);
+        let mut two = [0u8; 32];
+        two[31] = 2;
+
+        // G + G doubles
+        let doubled = JacobianPoint::INFINITY.add_affine(&g).add_affine(&g);
+        assert_eq!(
+            batch_normalize(&[doubled])[0],
+            Some(generator_multiple(&two))
+        );
+
+        // G + (-G) is infinity
+        let minus_g = AffinePoint {
+            x: g.x,
+            y: FieldElement::ZERO.sub(&g.y),
+        };
+        let infinity = JacobianPoint::INFINITY.add_affine(&g).add_affine(&minus_g);
+        assert!(infinity.is_infinity());
+    }
+
+    #[test]
+    fn test_batch_normalize() {
+        let points: Vec<JacobianPoint> = (0..10)
+            .map(|seed| multiply_generator(&scalar(seed)))
+            .chain([JacobianPoint::INFINITY])
+            .collect();
+        let affine = batch_normalize(&points);
+
+        for (seed, point) in (0..10).zip(&affine) {
+            assert_eq!(*point, Some(generator_multiple(&scalar(seed))));
+        }
+        assert_eq!(affine[10], None);
+        assert_eq!(
+            affine[3].unwrap().serialize(),
+            PublicKey::from_slice(&affine[3].unwrap().serialize_uncompressed())
+                .unwrap()
+                .serialize()
+        );
+    }
+}
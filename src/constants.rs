@@ -1 +1,2 @@
 pub const NON_HARDENED_MAX_INDEX: u32 = 0x7FFFFFFF;
+pub const DEFAULT_MAX_DEPTH: u32 = 1000;
@@ -0,0 +1,156 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Lowest CPU limit, in percent
+pub const MIN_CPU_LIMIT: u32 = 5;
+pub const MAX_CPU_LIMIT: u32 = 100;
+/// CPU limit of the background mode unless one is given
+pub const DEFAULT_BACKGROUND_CPU_LIMIT: u32 = 50;
+/// Percentage points the TUI keys change the limit by
+pub const CPU_LIMIT_STEP: u32 = 10;
+/// A rest is cut into slices this long, so a stop is not held up by it
+const REST_SLICE: Duration = Duration::from_millis(50);
+
+/// How much of each core the CPU workbench may use, shared with the TUI
+/// which adjusts it while searching
+#[derive(Clone, Debug)]
+pub struct CpuThrottle {
+    /// Percentage of the time the workers derive keys
+    limit: Arc<AtomicU32>,
+    /// Whether the workers run at the lowest scheduling priority
+    background: bool,
+}
+
+impl Default for CpuThrottle {
+    fn default() -> Self {
+        CpuThrottle::new(MAX_CPU_LIMIT, false)
+    }
+}
+
+impl CpuThrottle {
+    pub fn new(limit: u32, background: bool) -> Self {
+        CpuThrottle {
+            limit: Arc::new(AtomicU32::new(limit.clamp(MIN_CPU_LIMIT, MAX_CPU_LIMIT))),
+            background,
+        }
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    pub fn is_background(&self) -> bool {
+        self.background
+    }
+
+    /// Moves the limit by `delta` percentage points, within the bounds, and
+    /// returns the new limit
+    pub fn adjust(&self, delta: i32) -> u32 {
+        let limit = (self.limit() as i32 + delta).clamp(MIN_CPU_LIMIT as i32, MAX_CPU_LIMIT as i32);
+        self.limit.store(limit as u32, Ordering::Relaxed);
+        limit as u32
+    }
+
+    /// The part of `period` to spend deriving: a throttled chunk is shorter,
+    /// so a chunk and its rest together last about `period`
+    pub fn busy_target(&self, period: Duration) -> Duration {
+        period * self.limit() / MAX_CPU_LIMIT
+    }
+
+    /// How long to rest after deriving for `busy` to keep to the limit
+    pub fn rest_after(&self, busy: Duration) -> Duration {
+        let limit = self.limit();
+        busy * (MAX_CPU_LIMIT - limit) / limit
+    }
+
+    /// Rests after a chunk that took `busy`, returning early on a stop
+    pub fn rest(&self, busy: Duration, stop_signal: &AtomicBool) {
+        let mut remaining = self.rest_after(busy);
+        while !remaining.is_zero() && !stop_signal.load(Ordering::Relaxed) {
+            let slice = remaining.min(REST_SLICE);
+            thread::sleep(slice);
+            remaining -= slice;
+        }
+    }
+
+    /// In background mode, lowers the calling thread to the idle scheduling
+    /// class on Linux, to the lowest nice value on other Unixes. Best effort:
+    /// other platforms, or a refused change, run at the normal priority.
+    pub fn lower_current_thread_priority(&self) {
+        if self.background {
+            lower_current_thread_priority();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lower_current_thread_priority() {
+    // Pid 0 is the calling thread, which has its own policy and nice value
+    let param = libc::sched_param { sched_priority: 0 };
+    let idle = unsafe { libc::sched_setscheduler(0, libc::SCHED_IDLE, &param) } == 0;
+    if !idle {
+        unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) };
+    }
+}
+
+#[cfg(all(not(windows), not(target_os = "linux")))]
+fn lower_current_thread_priority() {
+    // Applies to the whole process, as the threads share its nice value
+    unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) };
+}
+
+#[cfg(windows)]
+fn lower_current_thread_priority() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_unthrottled_never_rests() {
+        let throttle = CpuThrottle::default();
+        assert_eq!(throttle.limit(), 100);
+        assert_eq!(throttle.rest_after(Duration::from_secs(1)), Duration::ZERO);
+        assert_eq!(
+            throttle.busy_target(Duration::from_secs(1)),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_duty_cycle_keeps_to_the_limit() {
+        let throttle = CpuThrottle::new(25, true);
+        let period = Duration::from_millis(1000);
+        let busy = throttle.busy_target(period);
+        assert_eq!(busy, Duration::from_millis(250));
+        // Busy 250 ms out of every 1000 ms
+        assert_eq!(throttle.rest_after(busy), Duration::from_millis(750));
+        assert!(throttle.is_background());
+    }
+
+    #[test]
+    fn test_adjust_stays_within_bounds() {
+        let throttle = CpuThrottle::new(0, false);
+        assert_eq!(throttle.limit(), MIN_CPU_LIMIT);
+
+        // Shared between the clones: the TUI adjusts the workers' limit
+        let workers = throttle.clone();
+        assert_eq!(throttle.adjust(CPU_LIMIT_STEP as i32), 15);
+        assert_eq!(workers.limit(), 15);
+        assert_eq!(throttle.adjust(-100), MIN_CPU_LIMIT);
+        assert_eq!(throttle.adjust(1000), MAX_CPU_LIMIT);
+    }
+
+    #[test]
+    fn test_rest_returns_on_stop() {
+        let throttle = CpuThrottle::new(MIN_CPU_LIMIT, false);
+        let stop_signal = AtomicBool::new(true);
+        let start = Instant::now();
+        // A 19 s rest, cut short by the stop
+        throttle.rest(Duration::from_secs(1), &stop_signal);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
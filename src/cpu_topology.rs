@@ -0,0 +1,311 @@
+use core_affinity::CoreId;
+use std::fs;
+
+/// Where the threads of a workbench run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadPlacement {
+    /// Logical CPUs set aside for the workbench, one per physical core
+    /// first. Empty when nothing is set aside.
+    pub cores: Vec<usize>,
+    /// Whether each thread is pinned to one of `cores`, rather than free
+    /// to run on any of them
+    pub pin: bool,
+}
+
+impl ThreadPlacement {
+    /// Keeps the calling thread, the `thread_index`-th of the workbench, on
+    /// the workbench's cores: pinned to its own core, or free to move among
+    /// them. Memory a pinned thread touches first afterwards is allocated on
+    /// the NUMA node of its core. False when the thread could not be placed.
+    pub fn place_current_thread(&self, thread_index: usize) -> bool {
+        if self.cores.is_empty() {
+            return true;
+        }
+        // Best effort: on an unsupported platform, or when the cores cannot
+        // be set, the thread just runs anywhere
+        if self.pin {
+            let id = self.cores[thread_index % self.cores.len()];
+            fits_cpu_set(id) && core_affinity::set_for_current(CoreId { id })
+        } else {
+            confine_current_thread(&self.cores)
+        }
+    }
+}
+
+/// Whether an affinity mask has room for `core`: `CPU_SET` panics past
+/// `CPU_SETSIZE`, for core_affinity too
+#[cfg(target_os = "linux")]
+fn fits_cpu_set(core: usize) -> bool {
+    core < libc::CPU_SETSIZE as usize
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fits_cpu_set(_core: usize) -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+fn confine_current_thread(cores: &[usize]) -> bool {
+    // Pid 0 is the calling thread, which has its own affinity mask. The
+    // kernel refuses an empty mask.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &core in cores.iter().filter(|&&core| fits_cpu_set(core)) {
+            libc::CPU_SET(core, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+// core_affinity sets a single core elsewhere
+#[cfg(not(target_os = "linux"))]
+fn confine_current_thread(_cores: &[usize]) -> bool {
+    false
+}
+
+/// The cores of the CPU workbench and of the GPU workbenches' producer
+/// threads, which would compete for them otherwise
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorePlan {
+    pub cpu: ThreadPlacement,
+    pub gpu: ThreadPlacement,
+}
+
+/// Logical CPUs this process may run on, by NUMA node and physical core
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CpuTopology {
+    /// `nodes[node][core]`: the SMT siblings of a physical core
+    nodes: Vec<Vec<Vec<usize>>>,
+}
+
+impl CpuTopology {
+    #[cfg(test)]
+    fn from_nodes(nodes: Vec<Vec<Vec<usize>>>) -> Self {
+        CpuTopology { nodes }
+    }
+
+    /// From sysfs on Linux. Elsewhere, or when sysfs is unreadable, every
+    /// allowed CPU is its own core of a single node.
+    pub fn detect() -> Self {
+        let allowed: Vec<usize> = core_affinity::get_core_ids()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|core| core.id)
+            .collect();
+
+        let node_cpus = Self::read_node_cpus().unwrap_or_else(|| vec![allowed.clone()]);
+        let nodes = node_cpus
+            .into_iter()
+            .map(|cpus| {
+                let mut cores: Vec<Vec<usize>> = Vec::new();
+                for cpu in cpus.into_iter().filter(|cpu| allowed.contains(cpu)) {
+                    let siblings = Self::read_siblings(cpu).unwrap_or_else(|| vec![cpu]);
+                    match cores.iter_mut().find(|core| siblings.contains(&core[0])) {
+                        Some(core) => core.push(cpu),
+                        None => cores.push(vec![cpu]),
+                    }
+                }
+                cores
+            })
+            .filter(|cores| !cores.is_empty())
+            .collect();
+
+        CpuTopology { nodes }
+    }
+
+    fn read_node_cpus() -> Option<Vec<Vec<usize>>> {
+        let mut nodes: Vec<(usize, Vec<usize>)> = fs::read_dir("/sys/devices/system/node")
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let node: usize = entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix("node")?
+                    .parse()
+                    .ok()?;
+                let cpulist = fs::read_to_string(entry.path().join("cpulist")).ok()?;
+                Some((node, parse_cpu_list(&cpulist).ok()?))
+            })
+            .collect();
+        nodes.sort();
+        (!nodes.is_empty()).then(|| nodes.into_iter().map(|(_, cpus)| cpus).collect())
+    }
+
+    fn read_siblings(cpu: usize) -> Option<Vec<usize>> {
+        let path = format!(
+            "/sys/devices/system/cpu/cpu{}/topology/thread_siblings_list",
+            cpu
+        );
+        parse_cpu_list(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    #[cfg(test)]
+    fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[cfg(test)]
+    fn physical_core_count(&self) -> usize {
+        self.nodes.iter().map(Vec::len).sum()
+    }
+
+    /// Physical cores taking turns across the nodes, so consecutive CPU
+    /// workers spread their memory traffic over every node
+    fn interleaved_cores(&self) -> Vec<&Vec<usize>> {
+        let most_cores = self.nodes.iter().map(Vec::len).max().unwrap_or(0);
+        (0..most_cores)
+            .flat_map(|i| self.nodes.iter().filter_map(move |cores| cores.get(i)))
+            .collect()
+    }
+
+    /// The last `reserved_gpu_cores` physical cores for the GPU producer
+    /// threads, the others for the CPU workbench: one thread per physical
+    /// core before any SMT sibling
+    pub fn plan(&self, reserved_gpu_cores: u32, pin: bool) -> CorePlan {
+        let cores = self.interleaved_cores();
+        // The CPU workbench keeps at least one core
+        let reserved = (reserved_gpu_cores as usize).min(cores.len().saturating_sub(1));
+        let (cpu_cores, gpu_cores) = cores.split_at(cores.len() - reserved);
+
+        let most_siblings = cpu_cores.iter().map(|core| core.len()).max().unwrap_or(0);
+        let cpu = (0..most_siblings)
+            .flat_map(|sibling| {
+                cpu_cores
+                    .iter()
+                    .filter_map(move |core| core.get(sibling).copied())
+            })
+            .collect();
+        let gpu = gpu_cores.iter().map(|core| core[0]).collect();
+
+        CorePlan {
+            cpu: ThreadPlacement { cores: cpu, pin },
+            gpu: ThreadPlacement { cores: gpu, pin },
+        }
+    }
+}
+
+/// A Linux CPU list, e.g. "0-3,8,10-11"
+pub fn parse_cpu_list(text: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("Invalid CPU list '{}'", text.trim());
+    let mut cpus = Vec::new();
+    for part in text.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.parse().map_err(|_| invalid())?;
+                let last: usize = last.parse().map_err(|_| invalid())?;
+                if first > last {
+                    return Err(invalid());
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.parse().map_err(|_| invalid())?),
+        }
+    }
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two nodes of two physical cores, each with two SMT threads
+    fn dual_socket() -> CpuTopology {
+        CpuTopology::from_nodes(vec![
+            vec![vec![0, 4], vec![1, 5]],
+            vec![vec![2, 6], vec![3, 7]],
+        ])
+    }
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n"),
+            Ok(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_cpu_list("5"), Ok(vec![5]));
+        assert_eq!(parse_cpu_list(""), Ok(vec![]));
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+
+    #[test]
+    fn test_plan_spreads_over_nodes_and_physical_cores() {
+        let topology = dual_socket();
+        assert_eq!(topology.node_count(), 2);
+        assert_eq!(topology.physical_core_count(), 4);
+
+        let plan = topology.plan(0, true);
+        // Node 0, node 1, node 0, node 1, then the SMT siblings
+        assert_eq!(plan.cpu.cores, vec![0, 2, 1, 3, 4, 6, 5, 7]);
+        assert!(plan.gpu.cores.is_empty());
+    }
+
+    #[test]
+    fn test_plan_reserves_whole_cores_for_the_gpu() {
+        let plan = dual_socket().plan(1, false);
+        assert_eq!(plan.gpu.cores, vec![3]);
+        // Neither CPU 3 nor its sibling 7 is left to the CPU workbench
+        assert_eq!(plan.cpu.cores, vec![0, 2, 1, 4, 6, 5]);
+        assert!(!plan.cpu.pin);
+
+        // At least one core stays with the CPU workbench
+        let plan = dual_socket().plan(10, true);
+        assert_eq!(plan.gpu.cores, vec![2, 1, 3]);
+        assert_eq!(plan.cpu.cores, vec![0, 4]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unpinned_thread_is_confined_to_its_cores() {
+        let allowed: Vec<usize> = core_affinity::get_core_ids()
+            .unwrap()
+            .into_iter()
+            .map(|core| core.id)
+            .collect();
+        let placement = ThreadPlacement {
+            cores: allowed[..1].to_vec(),
+            pin: false,
+        };
+        // In a thread of its own, so the test thread keeps its affinity
+        let confined: Vec<usize> = std::thread::spawn(move || {
+            assert!(placement.place_current_thread(5));
+            core_affinity::get_core_ids()
+                .unwrap()
+                .into_iter()
+                .map(|core| core.id)
+                .collect()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(confined, allowed[..1]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cores_past_the_cpu_set_are_not_placed() {
+        let allowed = core_affinity::get_core_ids().unwrap();
+        // The thread keeps running where it could before
+        let (placed, kept) = std::thread::spawn(|| {
+            let placed = [true, false].map(|pin| {
+                ThreadPlacement {
+                    cores: vec![5000],
+                    pin,
+                }
+                .place_current_thread(0)
+            });
+            (placed, core_affinity::get_core_ids().unwrap())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(placed, [false, false]);
+        assert_eq!(kept, allowed);
+    }
+
+    #[test]
+    fn test_detect_finds_the_allowed_cpus() {
+        let topology = CpuTopology::detect();
+        assert!(topology.physical_core_count() > 0);
+        assert!(topology.node_count() > 0);
+    }
+}
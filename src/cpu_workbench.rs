@@ -1,8 +1,11 @@
 use crate::events::EventSender;
 use crate::extended_public_key_deriver::{ExtendedPublicKeyDeriver, KeyDeriver};
 use crate::extended_public_key_path_walker::{ExtendedPublicKeyPathWalker, PathWalker};
+use crate::prefix_table::PrefixTables;
 use crate::workbench::Workbench;
 use crate::workbench_config::WorkbenchConfig;
+use std::collections::BTreeSet;
+use std::ops::Range;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
@@ -13,6 +16,48 @@ const MAX_CHUNK_SIZE: u32 = 200_000;
 const TARGET_BATCH_DURATION_MS: u32 = 1_000;
 const MAX_UPPER_ADJUSTMENT_FACTOR: f32 = 1.20;
 const REPORT_INTERVAL: Duration = Duration::from_millis(1000);
+/// Address indexes of one parent derived together, sharing one field
+/// inversion
+const DERIVE_BATCH_SIZE: u32 = 1024;
+
+/// Hands the counter range out to the threads chunk by chunk and knows the
+/// counter below which every chunk is done: threads finish their chunks out
+/// of order
+struct CounterChunks {
+    next: u64,
+    end: u64,
+    in_flight: BTreeSet<u64>,
+}
+
+impl CounterChunks {
+    fn new(range: &Range<u64>) -> Self {
+        CounterChunks {
+            next: range.start,
+            end: range.end,
+            in_flight: BTreeSet::new(),
+        }
+    }
+
+    /// The next chunk of at most `size` counters, `None` once the range is
+    /// handed out
+    fn claim(&mut self, size: u64) -> Option<Range<u64>> {
+        if self.next >= self.end {
+            return None;
+        }
+        let start = self.next;
+        self.next = start + size.min(self.end - start);
+        self.in_flight.insert(start);
+        Some(start..self.next)
+    }
+
+    fn complete(&mut self, chunk: &Range<u64>) {
+        self.in_flight.remove(&chunk.start);
+    }
+
+    fn processed_counter(&self) -> u64 {
+        self.in_flight.first().copied().unwrap_or(self.next)
+    }
+}
 
 pub struct CPUWorkbench {
     config: WorkbenchConfig,
@@ -21,7 +66,7 @@ pub struct CPUWorkbench {
     event_sender: EventSender,
     stop_signal: Arc<AtomicBool>,
 
-    next_counter: Arc<AtomicU64>,
+    counter_chunks: Arc<Mutex<CounterChunks>>,
     global_generated: Arc<AtomicU64>,
 
     worker_handles: Mutex<Vec<JoinHandle<()>>>,
@@ -34,12 +79,13 @@ impl CPUWorkbench {
         event_sender: EventSender,
         stop_signal: Arc<AtomicBool>,
     ) -> Self {
+        let counter_chunks = CounterChunks::new(&config.counter_range);
         Self {
             config,
             num_threads,
             event_sender,
             stop_signal,
-            next_counter: Arc::new(AtomicU64::new(0)),
+            counter_chunks: Arc::new(Mutex::new(counter_chunks)),
             global_generated: Arc::new(AtomicU64::new(0)),
             worker_handles: Mutex::new(Vec::new()),
         }
@@ -52,53 +98,135 @@ impl Workbench for CPUWorkbench {
         self.event_sender.started(Instant::now());
 
         let mut handles = self.worker_handles.lock().unwrap();
+        let tables = Arc::new(PrefixTables::new(&self.config.prefixes));
 
-        for _ in 0..self.num_threads {
+        for thread_index in 0..self.num_threads {
             let config = self.config.clone();
+            let tables = Arc::clone(&tables);
             let stop_signal = Arc::clone(&self.stop_signal);
-            let next_counter = Arc::clone(&self.next_counter);
+            let counter_chunks = Arc::clone(&self.counter_chunks);
             let global_generated = Arc::clone(&self.global_generated);
             let event_sender = self.event_sender.clone();
 
             let handle = thread::spawn(move || {
-                let path_walker =
-                    ExtendedPublicKeyPathWalker::new(config.seed0, config.seed1, config.max_depth);
+                // Placed before anything is allocated, so a pinned
+                // deriver's state lands on the NUMA node of the core
+                config.placement.place_current_thread(thread_index as usize);
+                config.throttle.lower_current_thread_priority();
+
+                let path_walker = ExtendedPublicKeyPathWalker::new(
+                    config.seed0,
+                    config.seed1,
+                    config.max_depth,
+                    config.template,
+                );
                 let mut xpub_deriver = ExtendedPublicKeyDeriver::new(&config.xpub);
+                let mut hashes = Vec::with_capacity(DERIVE_BATCH_SIZE as usize);
+                let mut output_keys = Vec::with_capacity(DERIVE_BATCH_SIZE as usize);
                 let mut current_chunk_size = MIN_CHUNK_SIZE;
                 let mut generated_since_last_report = 0u64;
                 let mut last_report_time = Instant::now();
 
+                // P2TR prefixes match the tweaked output key, P2SH-P2WPKH
+                // the redeem script hash and every other type the hash160:
+                // only derive what some prefix needs
+                let needs_output_key = !tables.output_key.is_empty();
+                let needs_script_hash = !tables.script_hash.is_empty();
+                let needs_hash160 = !tables.pubkey_hash.is_empty() || needs_script_hash;
+
                 while !stop_signal.load(Ordering::Relaxed) {
                     let batch_start = Instant::now();
-                    let start_counter =
-                        next_counter.fetch_add(current_chunk_size as u64, Ordering::Relaxed);
+                    let Some(chunk) = counter_chunks
+                        .lock()
+                        .unwrap()
+                        .claim(current_chunk_size as u64)
+                    else {
+                        break;
+                    };
+                    let start_counter = chunk.start;
+                    let chunk_size = chunk.end - chunk.start;
 
-                    for path in
-                        path_walker.iter_from_counter(start_counter, current_chunk_size as u64)
+                    for children in path_walker.iter_parents_from_counter(start_counter, chunk_size)
                     {
-                        if let Ok(pubkey_hash) = xpub_deriver.get_pubkey_hash_160(&path) {
-                            for (prefix_id, prefix) in config.prefixes.iter().enumerate() {
-                                if prefix.matches_pattern(&pubkey_hash) {
-                                    event_sender.potential_match(path, prefix_id as u8);
-                                }
+                        let parent = children.parent;
+                        for batch_start in
+                            children.indexes.clone().step_by(DERIVE_BATCH_SIZE as usize)
+                        {
+                            let indexes = batch_start
+                                ..(batch_start + DERIVE_BATCH_SIZE).min(children.indexes.end);
+
+                            // A failed range leaves its buffer empty: no match
+                            hashes.clear();
+                            output_keys.clear();
+                            if needs_hash160 {
+                                xpub_deriver
+                                    .get_children_hash_160(&parent, indexes.clone(), &mut hashes)
+                                    .unwrap_or_else(|_| hashes.clear());
+                            }
+                            if needs_output_key {
+                                xpub_deriver
+                                    .get_children_taproot_output_keys(
+                                        &parent,
+                                        indexes.clone(),
+                                        &mut output_keys,
+                                    )
+                                    .unwrap_or_else(|_| output_keys.clear());
                             }
-                        }
 
-                        generated_since_last_report += 1;
+                            for (position, index) in indexes.enumerate() {
+                                let pubkey_hash = hashes.get(position).copied().flatten();
+                                let script_hash =
+                                    pubkey_hash
+                                        .filter(|_| needs_script_hash)
+                                        .map(|pubkey_hash| {
+                                            xpub_deriver.get_script_hash_160(&pubkey_hash)
+                                        });
+                                let output_key = output_keys.get(position).copied().flatten();
+
+                                tables.for_each_match(
+                                    &config.prefixes,
+                                    pubkey_hash.as_ref(),
+                                    script_hash.as_ref(),
+                                    output_key.as_ref(),
+                                    |prefix_id| {
+                                        if config.prefix_filter.is_wanted(prefix_id) {
+                                            event_sender
+                                                .potential_match(parent.child(index), prefix_id);
+                                        }
+                                    },
+                                );
+
+                                generated_since_last_report += 1;
+                            }
+                        }
                     }
 
-                    global_generated.fetch_add(current_chunk_size as u64, Ordering::Relaxed);
+                    global_generated.fetch_add(chunk_size, Ordering::Relaxed);
+
+                    // Its matches are sent, the chunk is done
+                    let processed_counter = {
+                        let mut counter_chunks = counter_chunks.lock().unwrap();
+                        counter_chunks.complete(&chunk);
+                        counter_chunks.processed_counter()
+                    };
 
                     if last_report_time.elapsed() >= REPORT_INTERVAL {
                         event_sender.progress(generated_since_last_report);
+                        event_sender.processed(processed_counter);
                         generated_since_last_report = 0;
                         last_report_time = Instant::now();
                     }
 
-                    let batch_duration = (batch_start.elapsed().as_millis() as u32).max(1); // prevent division by zero
+                    let busy = batch_start.elapsed();
+                    let batch_duration = (busy.as_millis() as u32).max(1); // prevent division by zero
 
-                    let ideal_chunk =
-                        current_chunk_size * TARGET_BATCH_DURATION_MS / batch_duration;
+                    // Under a CPU limit, a chunk is followed by a rest and
+                    // sized so both fit the target duration
+                    let target_duration = config
+                        .throttle
+                        .busy_target(Duration::from_millis(TARGET_BATCH_DURATION_MS as u64));
+                    let target_duration_ms = (target_duration.as_millis() as u32).max(1);
+                    let ideal_chunk = current_chunk_size * target_duration_ms / batch_duration;
                     let new_chunk = if ideal_chunk > current_chunk_size {
                         (current_chunk_size as f32 * MAX_UPPER_ADJUSTMENT_FACTOR)
                             .min(ideal_chunk as f32) as u32
@@ -107,11 +235,14 @@ impl Workbench for CPUWorkbench {
                     };
 
                     current_chunk_size = new_chunk.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+
+                    config.throttle.rest(busy, &stop_signal);
                 }
 
                 if generated_since_last_report > 0 {
                     event_sender.progress(generated_since_last_report);
                 }
+                event_sender.processed(counter_chunks.lock().unwrap().processed_counter());
             });
 
             handles.push(handle);
@@ -125,6 +256,15 @@ impl Workbench for CPUWorkbench {
         }
     }
 
+    fn is_done(&self) -> bool {
+        // A thread only returns on a stop, or once no chunk is left to claim
+        self.worker_handles
+            .lock()
+            .unwrap()
+            .iter()
+            .all(JoinHandle::is_finished)
+    }
+
     fn total_generated(&self) -> u64 {
         self.global_generated.load(Ordering::Relaxed)
     }
@@ -134,6 +274,8 @@ impl Workbench for CPUWorkbench {
 mod tests {
     use super::*;
     use crate::extended_public_key::ExtendedPubKey;
+    use crate::network::Network;
+    use crate::path_template::{KeyPath, PathTemplate};
     use crate::prefix::Prefix;
     use crate::workbench_config::WorkbenchConfig;
     use std::sync::mpsc;
@@ -152,8 +294,16 @@ mod tests {
     #[test]
     fn test_cpu_working_bench_creation() {
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (bench, stop_signal) = create_test_bench(config, 4);
 
@@ -164,8 +314,16 @@ mod tests {
     #[test]
     fn test_total_generated() {
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (bench, _) = create_test_bench(config, 4);
 
@@ -177,8 +335,16 @@ mod tests {
     #[test]
     fn test_threads_actually_process_addresses() {
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (bench, stop_signal) = create_test_bench(config, 2);
 
@@ -196,8 +362,16 @@ mod tests {
     #[test]
     fn test_wait_actually_waits_for_threads() {
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (bench, stop_signal) = create_test_bench(config, 2);
 
@@ -270,8 +444,16 @@ mod tests {
     #[test]
     fn test_cpu_workbench_sends_started_event_on_start() {
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (bench, stop_signal) = create_test_bench(config, 2);
 
@@ -288,8 +470,16 @@ mod tests {
         use std::sync::mpsc;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (tx, rx) = mpsc::channel();
         let event_sender = EventSender::new(tx, "test".to_string());
@@ -318,8 +508,16 @@ mod tests {
         use std::sync::mpsc;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (tx, _rx) = mpsc::channel();
         let event_sender = EventSender::new(tx, "test".to_string());
@@ -355,8 +553,16 @@ mod tests {
         use std::sync::mpsc;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefix = Prefix::new("1").unwrap();
-        let config = WorkbenchConfig::new(xpub, vec![prefix], 1000, 2000, 10000);
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
 
         let (tx, rx) = mpsc::channel();
         let event_sender = EventSender::new(tx, "test".to_string());
@@ -379,4 +585,195 @@ mod tests {
             "Should have sent events (match is likely with prefix '1')"
         );
     }
+
+    #[test]
+    fn test_cpu_workbench_matches_p2tr_prefix() {
+        use std::sync::mpsc;
+
+        let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
+        // "bc1p" matches every P2TR address, "1" every P2PKH address
+        let prefixes = vec![
+            Prefix::new("bc1p", Network::Mainnet).unwrap(),
+            Prefix::new("1", Network::Mainnet).unwrap(),
+        ];
+        let config = WorkbenchConfig::new(
+            xpub,
+            prefixes,
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
+
+        let (tx, rx) = mpsc::channel();
+        let event_sender = EventSender::new(tx, "test".to_string());
+        let stop_signal = Arc::new(AtomicBool::new(false));
+
+        let bench = CPUWorkbench::new(config, 1, event_sender, Arc::clone(&stop_signal));
+
+        bench.start();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        stop_signal.store(true, Ordering::Relaxed);
+        bench.wait();
+
+        let prefix_ids: Vec<u32> = rx
+            .try_iter()
+            .filter_map(|e| match e {
+                crate::events::WorkbenchEvent::PotentialMatch { prefix_id, .. } => Some(prefix_id),
+                _ => None,
+            })
+            .collect();
+
+        assert!(prefix_ids.contains(&0), "P2TR prefix should match");
+        assert!(prefix_ids.contains(&1), "P2PKH prefix should match");
+    }
+
+    #[test]
+    fn test_cpu_workbench_matches_p2sh_p2wpkh_prefix() {
+        use crate::ground_truth_validator::GroundTruthValidator;
+        use std::sync::mpsc;
+
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        // About 1 in 30 P2SH addresses starts with "3A"
+        let prefixes = vec![Prefix::new("3A", Network::Mainnet).unwrap()];
+        let config = WorkbenchConfig::new(
+            xpub,
+            prefixes.clone(),
+            1000,
+            2000,
+            10000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
+
+        let (tx, rx) = mpsc::channel();
+        let event_sender = EventSender::new(tx, "test".to_string());
+        let stop_signal = Arc::new(AtomicBool::new(false));
+
+        let bench = CPUWorkbench::new(config, 1, event_sender, Arc::clone(&stop_signal));
+
+        bench.start();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        stop_signal.store(true, Ordering::Relaxed);
+        bench.wait();
+
+        let paths: Vec<KeyPath> = rx
+            .try_iter()
+            .filter_map(|e| match e {
+                crate::events::WorkbenchEvent::PotentialMatch { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect();
+
+        assert!(!paths.is_empty(), "P2SH-P2WPKH prefix should match");
+        let validator = GroundTruthValidator::new(xpub_str, Network::Mainnet).unwrap();
+        for path in paths {
+            let address = validator
+                .validate_and_get_address(&prefixes[0], &path)
+                .unwrap();
+            assert!(address.is_some_and(|address| address.starts_with("3A")));
+        }
+    }
+
+    #[test]
+    fn test_cpu_workbench_stays_in_counter_range() {
+        use crate::events::WorkbenchEvent;
+        use std::collections::HashSet;
+
+        let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
+        // Every P2PKH address starts with '1': every path is a match
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            100,
+            950..1250,
+            PathTemplate::default(),
+        );
+
+        let (tx, rx) = mpsc::channel();
+        let event_sender = EventSender::new(tx, "test".to_string());
+        let stop_signal = Arc::new(AtomicBool::new(false));
+
+        let bench = CPUWorkbench::new(config, 2, event_sender, Arc::clone(&stop_signal));
+
+        bench.start();
+        bench.wait();
+        assert_eq!(bench.total_generated(), 300);
+
+        let paths: HashSet<KeyPath> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                WorkbenchEvent::PotentialMatch { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths.len(), 300);
+        assert!(paths.contains(&KeyPath::from_slice(&[1000, 2000, 0, 9, 0, 50])));
+        assert!(paths.contains(&KeyPath::from_slice(&[1000, 2000, 0, 12, 0, 49])));
+        assert!(!paths.contains(&KeyPath::from_slice(&[1000, 2000, 0, 12, 0, 50])));
+    }
+
+    #[test]
+    fn test_cpu_workbench_reports_processed_range_end() {
+        use crate::events::WorkbenchEvent;
+
+        let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
+        let config = WorkbenchConfig::new(
+            xpub,
+            vec![prefix],
+            1000,
+            2000,
+            100,
+            950..1250,
+            PathTemplate::default(),
+        );
+
+        let (tx, rx) = mpsc::channel();
+        let event_sender = EventSender::new(tx, "test".to_string());
+        let stop_signal = Arc::new(AtomicBool::new(false));
+
+        let bench = CPUWorkbench::new(config, 2, event_sender, Arc::clone(&stop_signal));
+
+        bench.start();
+        bench.wait();
+
+        let processed_counter = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                WorkbenchEvent::Processed {
+                    processed_counter, ..
+                } => Some(processed_counter),
+                _ => None,
+            })
+            .max();
+        assert_eq!(processed_counter, Some(1250));
+    }
+
+    #[test]
+    fn test_counter_chunks_processed_counter_waits_for_earlier_chunks() {
+        let mut counter_chunks = CounterChunks::new(&(100..350));
+
+        let first = counter_chunks.claim(100).unwrap();
+        let second = counter_chunks.claim(100).unwrap();
+        let third = counter_chunks.claim(100).unwrap();
+        assert_eq!(third, 300..350);
+        assert_eq!(counter_chunks.claim(100), None);
+        assert_eq!(counter_chunks.processed_counter(), 100);
+
+        // A later chunk done first does not move the processed counter
+        counter_chunks.complete(&second);
+        assert_eq!(counter_chunks.processed_counter(), 100);
+
+        counter_chunks.complete(&first);
+        assert_eq!(counter_chunks.processed_counter(), 300);
+
+        counter_chunks.complete(&third);
+        assert_eq!(counter_chunks.processed_counter(), 350);
+    }
 }
@@ -0,0 +1,344 @@
+use crate::path_template::KeyPath;
+use crate::prefix::AddressType;
+use std::fmt;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_GENERATOR: [u64; 5] = [
+    0xf5dee51989,
+    0xa9fdca3312,
+    0x1bab10e32d,
+    0x3706b1677a,
+    0x644d626ffd,
+];
+
+const HARDENED: u32 = 0x80000000;
+
+/// Key derivations after the key that the vanity path replaces: receive
+/// and change chains of the same account
+const REPLACEABLE_DERIVATIONS: [&str; 4] = ["", "/*", "/0/*", "/<0;1>/*"];
+
+/// Where a key comes from: the master key fingerprint and the path from the
+/// master key down to it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyOrigin {
+    pub fingerprint: [u8; 4],
+    pub path: Vec<u32>,
+}
+
+impl KeyOrigin {
+    /// Parses `d34db33f/84'/0'/0'`, the part between the brackets
+    fn parse(origin: &str) -> Result<Self, String> {
+        let mut parts = origin.split('/');
+        let fingerprint_hex = parts.next().unwrap_or_default();
+        if fingerprint_hex.len() != 8 {
+            return Err(format!(
+                "Key origin fingerprint '{}' should be 8 hex characters",
+                fingerprint_hex
+            ));
+        }
+        let mut fingerprint = [0u8; 4];
+        for (i, byte) in fingerprint.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&fingerprint_hex[i * 2..i * 2 + 2], 16).map_err(|_| {
+                format!(
+                    "Key origin fingerprint '{}' should be 8 hex characters",
+                    fingerprint_hex
+                )
+            })?;
+        }
+
+        let path = parts.map(parse_path_step).collect::<Result<_, _>>()?;
+
+        Ok(KeyOrigin { fingerprint, path })
+    }
+
+    /// The path as wallets show it: `m/84'/0'/0'`
+    pub fn path_string(&self) -> String {
+        format!("m{}", format_steps(&self.path))
+    }
+}
+
+/// Written as in descriptors, without the brackets
+impl fmt::Display for KeyOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.fingerprint {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, "{}", format_steps(&self.path))
+    }
+}
+
+/// Key of the searched wallet, as written in the descriptors of the found
+/// addresses: always a plain xpub/tpub, with its origin when known
+#[derive(Clone, Debug)]
+pub struct DescriptorKey {
+    pub origin: Option<KeyOrigin>,
+    pub xpub: String,
+}
+
+impl DescriptorKey {
+    /// Checksummed descriptor of the wallet holding a found address:
+    /// `wpkh([origin]xpub/seed0/seed1/b/a/0/*)` for the default template,
+    /// ranged over the address index, the last level of `path`
+    pub fn wallet_descriptor(&self, address_type: &AddressType, path: &KeyPath) -> String {
+        let origin = match &self.origin {
+            Some(origin) => format!("[{}]", origin),
+            None => String::new(),
+        };
+        let key = format!("{}{}{}/*", origin, self.xpub, format_steps(path.parent()));
+        let body = match address_type {
+            AddressType::P2PKH => format!("pkh({})", key),
+            AddressType::P2SHP2WPKH => format!("sh(wpkh({}))", key),
+            AddressType::P2WPKH => format!("wpkh({})", key),
+            AddressType::P2TR => format!("tr({})", key),
+        };
+        let body_checksum = checksum(&body).expect("descriptors are built from valid characters");
+        format!("{}#{}", body, body_checksum)
+    }
+}
+
+/// A single-key output descriptor, e.g.
+/// `wpkh([d34db33f/84'/0'/0']xpub.../<0;1>/*)#mams3jda`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Descriptor {
+    pub address_type: AddressType,
+    pub origin: Option<KeyOrigin>,
+    pub xpub: String,
+}
+
+impl Descriptor {
+    pub fn parse(descriptor: &str) -> Result<Self, String> {
+        let (body, descriptor_checksum) = descriptor
+            .rsplit_once('#')
+            .ok_or_else(|| "Descriptor is missing its '#' checksum".to_string())?;
+        if checksum(body)? != descriptor_checksum {
+            return Err("Invalid descriptor checksum".to_string());
+        }
+
+        let (address_type, key) = if let Some(key) = unwrap_script(body, "sh(wpkh(", "))") {
+            (AddressType::P2SHP2WPKH, key)
+        } else if let Some(key) = unwrap_script(body, "wpkh(", ")") {
+            (AddressType::P2WPKH, key)
+        } else if let Some(key) = unwrap_script(body, "pkh(", ")") {
+            (AddressType::P2PKH, key)
+        } else if let Some(key) = unwrap_script(body, "tr(", ")") {
+            (AddressType::P2TR, key)
+        } else {
+            return Err(
+                "Only pkh(), sh(wpkh()), wpkh() and tr() descriptors of a single key are supported"
+                    .to_string(),
+            );
+        };
+
+        let (origin, key) = match key.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest
+                    .split_once(']')
+                    .ok_or_else(|| "Key origin is missing its ']'".to_string())?;
+                (Some(KeyOrigin::parse(origin)?), key)
+            }
+            None => (None, key),
+        };
+
+        let (xpub, derivation) = key.split_at(key.find('/').unwrap_or(key.len()));
+        if !REPLACEABLE_DERIVATIONS.contains(&derivation) {
+            return Err(format!(
+                "Key derivation '{}' is not supported: the vanity path is derived right below the xpub, use '/<0;1>/*'",
+                derivation
+            ));
+        }
+
+        Ok(Descriptor {
+            address_type,
+            origin,
+            xpub: xpub.to_string(),
+        })
+    }
+}
+
+fn unwrap_script<'a>(body: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    body.strip_prefix(start)?.strip_suffix(end)
+}
+
+/// `/84'/0'/0'`, hardened steps marked with an apostrophe
+fn format_steps(path: &[u32]) -> String {
+    path.iter()
+        .map(|step| {
+            if step & HARDENED != 0 {
+                format!("/{}'", step & !HARDENED)
+            } else {
+                format!("/{}", step)
+            }
+        })
+        .collect()
+}
+
+/// Parses `84'`, `84h` or `0`
+fn parse_path_step(step: &str) -> Result<u32, String> {
+    let (index, hardened) = match step.strip_suffix(['\'', 'h', 'H']) {
+        Some(index) => (index, HARDENED),
+        None => (step, 0),
+    };
+    let index: u32 = index
+        .parse()
+        .map_err(|_| format!("Invalid key origin path step '{}'", step))?;
+    if index >= HARDENED {
+        return Err(format!("Key origin path step '{}' is too large", step));
+    }
+    Ok(index | hardened)
+}
+
+fn polymod(checksum: u64, value: u64) -> u64 {
+    let top = checksum >> 35;
+    let mut checksum = ((checksum & 0x7ffffffff) << 5) ^ value;
+    for (i, generator) in CHECKSUM_GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            checksum ^= generator;
+        }
+    }
+    checksum
+}
+
+/// BIP380 descriptor checksum
+fn checksum(body: &str) -> Result<String, String> {
+    let mut checksum = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for c in body.chars() {
+        let position = INPUT_CHARSET
+            .find(c)
+            .ok_or_else(|| format!("Invalid descriptor character '{}'", c))?
+            as u64;
+        checksum = polymod(checksum, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            checksum = polymod(checksum, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        checksum = polymod(checksum, class);
+    }
+    for _ in 0..8 {
+        checksum = polymod(checksum, 0);
+    }
+    checksum ^= 1;
+
+    Ok((0..8)
+        .map(|i| CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_XPUB: &str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+
+    #[test]
+    fn test_checksum_bip380_vectors() {
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(
+            checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").unwrap(),
+            "02wpgw69"
+        );
+    }
+
+    #[test]
+    fn test_parse_wpkh_with_origin() {
+        let descriptor = Descriptor::parse(&format!(
+            "wpkh([d34db33f/84'/0'/0']{}/<0;1>/*)#mams3jda",
+            TEST_XPUB
+        ))
+        .unwrap();
+        assert_eq!(descriptor.address_type, AddressType::P2WPKH);
+        assert_eq!(descriptor.xpub, TEST_XPUB);
+        let origin = descriptor.origin.unwrap();
+        assert_eq!(origin.fingerprint, [0xd3, 0x4d, 0xb3, 0x3f]);
+        assert_eq!(origin.path, vec![84 | HARDENED, HARDENED, HARDENED]);
+        assert_eq!(origin.path_string(), "m/84'/0'/0'");
+    }
+
+    #[test]
+    fn test_parse_script_types() {
+        let descriptor = Descriptor::parse(&format!(
+            "sh(wpkh([d34db33f/49h/0h/0h]{}/0/*))#lwuxv7js",
+            TEST_XPUB
+        ))
+        .unwrap();
+        assert_eq!(descriptor.address_type, AddressType::P2SHP2WPKH);
+        assert_eq!(descriptor.origin.unwrap().path_string(), "m/49'/0'/0'");
+
+        let descriptor = Descriptor::parse(&format!("pkh({})#00ws5atg", TEST_XPUB)).unwrap();
+        assert_eq!(descriptor.address_type, AddressType::P2PKH);
+        assert_eq!(descriptor.origin, None);
+
+        let tpub = "tpubDCthgcrNduJHHyErmegKUF38GTioPpkwG7yiANbqpWmxuSPGVi1jj54VVTQ3TzPT77eGkaEk9XGkSkjpBHjM8gm2V4gQrBhnBAihiFHJuRe";
+        let descriptor = Descriptor::parse(&format!(
+            "tr([d34db33f/86'/1'/0']{}/<0;1>/*)#luuw72cd",
+            tpub
+        ))
+        .unwrap();
+        assert_eq!(descriptor.address_type, AddressType::P2TR);
+        assert_eq!(descriptor.xpub, tpub);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let descriptor = format!("wpkh([d34db33f/84'/0'/0']{}/<0;1>/*)", TEST_XPUB);
+        assert!(Descriptor::parse(&descriptor)
+            .unwrap_err()
+            .contains("missing its '#' checksum"));
+        assert!(Descriptor::parse(&format!("{}#mams3jdb", descriptor))
+            .unwrap_err()
+            .contains("Invalid descriptor checksum"));
+
+        // hardened derivation below the xpub can't be derived from it
+        assert!(
+            Descriptor::parse(&format!("wpkh({}/1'/*)#lg9qdcph", TEST_XPUB))
+                .unwrap_err()
+                .contains("Key derivation '/1'/*' is not supported")
+        );
+
+        let multisig = "wsh(multi(1,xpub1,xpub2))";
+        assert!(
+            Descriptor::parse(&format!("{}#{}", multisig, checksum(multisig).unwrap()))
+                .unwrap_err()
+                .contains("single key")
+        );
+    }
+
+    #[test]
+    fn test_wallet_descriptor() {
+        let key = DescriptorKey {
+            origin: Some(KeyOrigin {
+                fingerprint: [0xd3, 0x4d, 0xb3, 0x3f],
+                path: vec![84 | HARDENED, HARDENED, HARDENED],
+            }),
+            xpub: TEST_XPUB.to_string(),
+        };
+        let path = KeyPath::from_slice(&[1949567566, 243133792, 0, 175, 0, 12]);
+
+        let descriptor = key.wallet_descriptor(&AddressType::P2WPKH, &path);
+        let (body, descriptor_checksum) = descriptor.rsplit_once('#').unwrap();
+        assert_eq!(
+            body,
+            format!(
+                "wpkh([d34db33f/84'/0'/0']{}/1949567566/243133792/0/175/0/*)",
+                TEST_XPUB
+            )
+        );
+        assert_eq!(checksum(body).unwrap(), descriptor_checksum);
+
+        let key = DescriptorKey {
+            origin: None,
+            xpub: TEST_XPUB.to_string(),
+        };
+        assert!(key
+            .wallet_descriptor(&AddressType::P2SHP2WPKH, &path)
+            .starts_with(&format!("sh(wpkh({}/1949567566/", TEST_XPUB)));
+    }
+}
@@ -20,6 +20,17 @@ impl DeviceInfo {
         }
     }
 
+    /// Name of the device's workbench, unique within a run: GPUs are
+    /// prefixed with their index
+    pub fn bench_id(&self) -> String {
+        match self {
+            DeviceInfo::Gpu {
+                name, device_index, ..
+            } => format!("{}_{}", device_index, name),
+            DeviceInfo::Cpu { name, .. } => name.clone(),
+        }
+    }
+
     pub fn with_threads(self, threads: u32) -> Self {
         match self {
             DeviceInfo::Cpu { name, .. } => DeviceInfo::Cpu { name, threads },
@@ -6,6 +6,8 @@ pub struct DeviceConfig {
     pub cpu_threads: u32,
     pub gpu_ids: Option<Vec<usize>>,
     pub gpu_only: bool,
+    /// Physical cores the CPU workbench leaves to the GPU producers
+    pub reserved_gpu_cores: u32,
 }
 
 impl From<&Cli> for DeviceConfig {
@@ -14,6 +16,7 @@ impl From<&Cli> for DeviceConfig {
             cpu_threads: cli.cpu_threads,
             gpu_ids: cli.gpu.clone(),
             gpu_only: cli.gpu_only,
+            reserved_gpu_cores: cli.reserve_gpu_cores,
         }
     }
 }
@@ -32,7 +35,28 @@ impl DeviceSelector {
 
         Self::validate_gpu_availability(&config, &available_gpus)?;
 
-        Ok(Self::filter_devices(all_devices, &config, &available_gpus))
+        let devices = Self::filter_devices(all_devices, &config, &available_gpus);
+        Ok(Self::reserve_gpu_cores(devices, &config))
+    }
+
+    /// The auto-detected CPU thread count less the cores reserved for the
+    /// GPU producers, when a GPU searches too. An explicit count is kept.
+    fn reserve_gpu_cores(devices: Vec<DeviceInfo>, config: &DeviceConfig) -> Vec<DeviceInfo> {
+        let has_gpu = devices
+            .iter()
+            .any(|device| matches!(device, DeviceInfo::Gpu { .. }));
+        if config.cpu_threads != 0 || config.reserved_gpu_cores == 0 || !has_gpu {
+            return devices;
+        }
+        devices
+            .into_iter()
+            .map(|device| match device.threads() {
+                Some(threads) => {
+                    device.with_threads(threads.saturating_sub(config.reserved_gpu_cores).max(1))
+                }
+                None => device,
+            })
+            .collect()
     }
 
     fn configure_cpu_threads(devices: Vec<DeviceInfo>, thread_count: u32) -> Vec<DeviceInfo> {
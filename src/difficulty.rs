@@ -0,0 +1,266 @@
+use crate::constants::DEFAULT_MAX_DEPTH;
+use crate::gap_estimate::{format_estimate, GapEstimator};
+use crate::prefix::{range_share, AddressType, Prefix};
+use crate::prefix_table::RangeTable;
+
+/// Rates the dry run gives ETAs for, from a few CPU cores to a GPU farm
+const DRY_RUN_RATES: [f64; 3] = [1e6, 1e8, 1e10];
+/// Prefixes listed one by one in the summaries shown during a search, a
+/// wordlist can hold thousands
+pub const LISTED_PREFIXES: usize = 10;
+
+/// How hard the prefixes are to find
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    /// Match probability of each prefix, in prefix order
+    pub probabilities: Vec<f64>,
+    /// Chance that a key matches any of the prefixes
+    pub combined: f64,
+}
+
+impl Difficulty {
+    pub fn new(prefixes: &[Prefix]) -> Self {
+        Difficulty {
+            probabilities: prefixes.iter().map(Prefix::match_probability).collect(),
+            combined: combined_probability(prefixes),
+        }
+    }
+
+    /// Addresses to derive on average before a hit
+    pub fn expected_attempts(&self) -> f64 {
+        1.0 / self.combined
+    }
+
+    /// Expected seconds to the next hit, None before a rate is measured
+    pub fn eta_secs(&self, addresses_per_sec: f64) -> Option<f64> {
+        (addresses_per_sec > 0.0).then(|| self.expected_attempts() / addresses_per_sec)
+    }
+
+    /// One line per prefix up to `max_listed`, then the combined line, e.g.
+    /// `1Sat (P2PKH): 1 in 11.3 million addresses`
+    pub fn summary_lines(&self, prefixes: &[Prefix], max_listed: usize) -> Vec<String> {
+        let mut lines: Vec<String> = prefixes
+            .iter()
+            .zip(&self.probabilities)
+            .take(max_listed)
+            .map(|(prefix, probability)| {
+                format!(
+                    "{} ({}): {}",
+                    prefix.as_str(),
+                    prefix.address_type.name(),
+                    format_odds(*probability)
+                )
+            })
+            .collect();
+        if prefixes.len() > max_listed {
+            lines.push(format!("... and {} more", prefixes.len() - max_listed));
+        }
+        if prefixes.len() > 1 {
+            lines.push(format!("Combined: {}", format_odds(self.combined)));
+        }
+        lines
+    }
+}
+
+/// The ranges of the prefixes of each address type are split where they
+/// overlap, so nested prefixes and words starting alike count once. The
+/// addresses of a key match independently of each other.
+fn combined_probability(prefixes: &[Prefix]) -> f64 {
+    let address_types = [
+        AddressType::P2PKH,
+        AddressType::P2WPKH,
+        AddressType::P2SHP2WPKH,
+        AddressType::P2TR,
+    ];
+    let miss: f64 = address_types
+        .iter()
+        .map(|address_type| {
+            let of_type = || {
+                prefixes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, prefix)| prefix.address_type == *address_type)
+            };
+            let hash160_entries: Vec<([u8; 20], [u8; 20], u32)> = of_type()
+                .flat_map(|(prefix_id, prefix)| {
+                    prefix
+                        .ranges
+                        .iter()
+                        .map(move |range| (range.low, range.high, prefix_id as u32))
+                })
+                .collect();
+            let output_key_entries: Vec<([u8; 32], [u8; 32], u32)> = of_type()
+                .flat_map(|(prefix_id, prefix)| {
+                    prefix
+                        .output_key_ranges
+                        .iter()
+                        .map(move |range| (range.low, range.high, prefix_id as u32))
+                })
+                .collect();
+            let share = union_share(prefixes, &hash160_entries)
+                + union_share(prefixes, &output_key_entries);
+            1.0 - share.min(1.0)
+        })
+        .product();
+    1.0 - miss
+}
+
+/// Share of the keys in the union of the ranges, each part counting as
+/// much as one of the prefixes covering it also matches its suffix
+fn union_share<const N: usize>(prefixes: &[Prefix], entries: &[([u8; N], [u8; N], u32)]) -> f64 {
+    let table = RangeTable::new(entries);
+    table
+        .ranges()
+        .iter()
+        .map(|range| {
+            let first = range.first_candidate as usize;
+            let suffix_miss: f64 = table.candidates()
+                [first..first + range.candidate_count as usize]
+                .iter()
+                .map(|&prefix_id| 1.0 - prefixes[prefix_id as usize].suffix_share())
+                .product();
+            range_share(&range.low, &range.high) * (1.0 - suffix_miss)
+        })
+        .sum()
+}
+
+/// Prints the estimates of a search without starting it
+pub fn print_dry_run(prefixes: &[Prefix], gap_estimator: Option<&GapEstimator>) {
+    let difficulty = Difficulty::new(prefixes);
+    for line in difficulty.summary_lines(prefixes, usize::MAX) {
+        println!("{}", line);
+    }
+
+    let etas: Vec<String> = DRY_RUN_RATES
+        .iter()
+        .filter_map(|rate| {
+            let eta = difficulty.eta_secs(*rate)?;
+            Some(format!(
+                "{} at {} addr/s",
+                format_estimate(eta),
+                format_count(*rate)
+            ))
+        })
+        .collect();
+    println!("Expected time to a hit: {}", etas.join(", "));
+
+    // The ratio does not depend on the rate
+    if let Some(estimate) = gap_estimator.and_then(|estimator| estimator.estimate(1.0)) {
+        println!(
+            "Hits below index {} take {:.1}x longer with max depth {} than with --gap-limit {}",
+            estimate.gap_limit,
+            estimate.default_layout_secs / estimate.bounded_secs,
+            DEFAULT_MAX_DEPTH,
+            estimate.gap_limit
+        );
+    }
+}
+
+/// `1 in 1,024 addresses`
+pub fn format_odds(probability: f64) -> String {
+    if probability <= 0.0 {
+        return "never matches".to_string();
+    }
+    format!("1 in {} addresses", format_count(1.0 / probability))
+}
+
+/// Rounds a count for reading: `1,024`, `11.3 million`, `100 million`,
+/// `4.2e21`
+pub fn format_count(count: f64) -> String {
+    const UNITS: [(f64, &str); 4] = [
+        (1e15, "quadrillion"),
+        (1e12, "trillion"),
+        (1e9, "billion"),
+        (1e6, "million"),
+    ];
+
+    if count >= 1e18 {
+        return format!("{:.1e}", count);
+    }
+    if let Some((scale, unit)) = UNITS.iter().find(|(scale, _)| count >= *scale) {
+        let scaled = format!("{:.1}", count / scale);
+        return format!("{} {}", scaled.trim_end_matches(".0"), unit);
+    }
+
+    let digits = format!("{:.0}", count);
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+
+    #[test]
+    fn test_longer_prefixes_are_harder() {
+        let short = Difficulty::new(&[Prefix::new("1Sat", Network::Mainnet).unwrap()]);
+        let long = Difficulty::new(&[Prefix::new("1Satoshi", Network::Mainnet).unwrap()]);
+
+        // Every base58 character is about 58 times harder
+        let ratio = long.expected_attempts() / short.expected_attempts();
+        assert!(ratio > 58f64.powi(4) * 0.9 && ratio < 58f64.powi(4) * 1.1);
+    }
+
+    #[test]
+    fn test_combined_probability_and_eta() {
+        let prefixes = vec![
+            Prefix::new("bc1qxy", Network::Mainnet).unwrap(),
+            Prefix::new("bc1qza", Network::Mainnet).unwrap(),
+        ];
+        let difficulty = Difficulty::new(&prefixes);
+
+        assert_eq!(difficulty.expected_attempts(), 512.0);
+        assert_eq!(difficulty.eta_secs(256.0), Some(2.0));
+        assert_eq!(difficulty.eta_secs(0.0), None);
+        assert_eq!(
+            difficulty.summary_lines(&prefixes, LISTED_PREFIXES),
+            vec![
+                "bc1qxy (P2WPKH): 1 in 1,024 addresses",
+                "bc1qza (P2WPKH): 1 in 1,024 addresses",
+                "Combined: 1 in 512 addresses",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_prefixes_count_once() {
+        let short = Prefix::new("1A", Network::Mainnet).unwrap();
+        let prefixes = vec![Prefix::new("1Ab", Network::Mainnet).unwrap(), short.clone()];
+        let difficulty = Difficulty::new(&prefixes);
+
+        // Every address starting with "1Ab" also starts with "1A"
+        let expected = short.match_probability();
+        assert!((difficulty.combined - expected).abs() < expected * 1e-12);
+        assert!(difficulty.probabilities[0] < difficulty.probabilities[1]);
+    }
+
+    #[test]
+    fn test_long_prefix_lists_are_cut() {
+        let prefixes: Vec<Prefix> = ["1A", "1B", "1C"]
+            .iter()
+            .map(|pattern| Prefix::new(pattern, Network::Mainnet).unwrap())
+            .collect();
+        let lines = Difficulty::new(&prefixes).summary_lines(&prefixes, 1);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("1A (P2PKH): "));
+        assert_eq!(lines[1], "... and 2 more");
+        assert!(lines[2].starts_with("Combined: "));
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(58.0), "58");
+        assert_eq!(format_count(195_112.0), "195,112");
+        assert_eq!(format_count(11_316_496.0), "11.3 million");
+        assert_eq!(format_count(1e8), "100 million");
+        assert_eq!(format_count(4.2e21), "4.2e21");
+    }
+}
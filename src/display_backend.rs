@@ -1,19 +1,56 @@
+use crate::budget::EndReason;
+use crate::descriptor::KeyOrigin;
+use crate::gap_estimate::GapEstimate;
+use crate::longest_match::Leaderboard;
+use crate::path_template::{KeyPath, PathLevel, PathTemplate};
 use crate::prefix::Prefix;
+use crate::seeds::Seeds;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::time::{Duration, Instant};
 
 pub trait UiBackend: Send {
-    fn start(&mut self, prefixes: &[Prefix], max_depth: u32, cpu_threads: u32);
-    fn workbench_starting(&mut self, bench_id: &str);
+    /// `key_origin` is known when the key came from a descriptor
+    fn start(
+        &mut self,
+        prefixes: &[Prefix],
+        key_origin: Option<&KeyOrigin>,
+        seeds: Seeds,
+        template: &PathTemplate,
+        max_depth: u32,
+        cpu_threads: u32,
+    );
+    /// `counter_range` is the part of the search the workbench is given
+    fn workbench_starting(&mut self, bench_id: &str, counter_range: &Range<u64>);
     fn workbench_started(&mut self, bench_id: &str);
 
     fn log_status(&mut self, bench_stats: &HashMap<String, BenchStats>);
 
-    fn log_found_address(&mut self, bench_id: &str, address: &str, path: &[u32; 6], prefix_id: u8);
+    /// Sent with the status when the search is bounded by a gap limit
+    fn log_gap_estimate(&mut self, estimate: &GapEstimate);
+
+    /// `descriptor` is the checksummed descriptor of the wallet holding the
+    /// address, ranged over its index, the last level of `path`
+    fn log_found_address(
+        &mut self,
+        bench_id: &str,
+        address: &str,
+        path: &KeyPath,
+        prefix_id: u32,
+        descriptor: &str,
+    );
+
+    /// Sent in the longest-match mode after every candidate that makes the
+    /// leaderboard, itself logged as a found address first
+    fn log_leaderboard(&mut self, leaderboard: &Leaderboard);
 
     fn log_derivation_error(&mut self);
 
-    fn log_false_positive(&mut self, bench_id: &str, path: &[u32; 6]);
+    fn log_false_positive(&mut self, bench_id: &str, path: &KeyPath);
+
+    /// An error the user must see, e.g. a found address that could not be
+    /// saved, shown without disturbing the display
+    fn log_error(&mut self, message: &str);
 
     fn stop_requested(&mut self);
 
@@ -21,7 +58,31 @@ pub trait UiBackend: Send {
 
     fn workbench_stopped(&mut self, bench_id: &str, total_generated: u64, elapsed: Duration);
 
-    fn final_status(&mut self);
+    /// `reason` is the stop condition that ended the search
+    fn final_status(&mut self, reason: EndReason);
+}
+
+/// Formats a BIP32 derivation path of `template` for display: the full path
+/// from the master key when the key origin is known, otherwise the wallet
+/// path below the xpub, without the address index and the receiving branch
+/// 0 the wallet adds itself
+pub fn format_derivation_path(
+    path: &KeyPath,
+    template: &PathTemplate,
+    key_origin: Option<&KeyOrigin>,
+) -> String {
+    let steps =
+        |levels: &[u32]| -> String { levels.iter().map(|level| format!("/{}", level)).collect() };
+    match key_origin {
+        Some(origin) => format!("{}{}", origin.path_string(), steps(path)),
+        None => {
+            let wallet_levels = match template.levels() {
+                [.., PathLevel::Fixed(0), PathLevel::Index] => path.len() - 2,
+                _ => path.len() - 1,
+            };
+            format!("xpub'{}", steps(&path[..wallet_levels]))
+        }
+    }
 }
 
 /// Statistics for a single workbench
@@ -43,4 +104,59 @@ impl BenchStats {
     pub fn runtime_secs(&self) -> u64 {
         self.start_time.elapsed().as_secs()
     }
+
+    /// Average rate since the workbench started, 0 during its first second
+    pub fn addresses_per_sec(&self) -> u64 {
+        self.total_generated
+            .checked_div(self.runtime_secs())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_derivation_path() {
+        let template = PathTemplate::default();
+        let path = KeyPath::from_slice(&[1949567566, 243133792, 0, 175, 0, 12]);
+        assert_eq!(
+            format_derivation_path(&path, &template, None),
+            "xpub'/1949567566/243133792/0/175"
+        );
+
+        let origin = KeyOrigin {
+            fingerprint: [0xd3, 0x4d, 0xb3, 0x3f],
+            path: vec![0x80000054, 0x80000000, 0x80000000],
+        };
+        assert_eq!(
+            format_derivation_path(&path, &template, Some(&origin)),
+            "m/84'/0'/0'/1949567566/243133792/0/175/0/12"
+        );
+
+        // Other templates keep every level but the address index: only the
+        // receiving branch 0 is implied
+        let template = PathTemplate::parse("a/1/i").unwrap();
+        let path = KeyPath::from_slice(&[4, 1, 12]);
+        assert_eq!(format_derivation_path(&path, &template, None), "xpub'/4/1");
+        assert_eq!(
+            format_derivation_path(&path, &template, Some(&origin)),
+            "m/84'/0'/0'/4/1/12"
+        );
+
+        let template = PathTemplate::parse("s0/s1/b/a/i").unwrap();
+        let path = KeyPath::from_slice(&[7, 8, 0, 0, 12]);
+        assert_eq!(
+            format_derivation_path(&path, &template, None),
+            "xpub'/7/8/0/0"
+        );
+
+        let template = PathTemplate::parse("s0/a/7/i").unwrap();
+        let path = KeyPath::from_slice(&[7, 3, 7, 12]);
+        assert_eq!(
+            format_derivation_path(&path, &template, None),
+            "xpub'/7/3/7"
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use crate::path_template::KeyPath;
 use std::sync::mpsc::Sender;
 use std::time::{Duration, Instant};
 
@@ -13,8 +14,14 @@ pub enum WorkbenchEvent {
     },
     PotentialMatch {
         bench_id: String,
-        path: [u32; 6],
-        prefix_id: u8,
+        path: KeyPath,
+        prefix_id: u32,
+    },
+    /// Every counter below `processed_counter` is derived and its matches
+    /// are sent
+    Processed {
+        bench_id: String,
+        processed_counter: u64,
     },
     Stopped {
         bench_id: String,
@@ -55,7 +62,7 @@ impl EventSender {
             .ok();
     }
 
-    pub fn potential_match(&self, path: [u32; 6], prefix_id: u8) {
+    pub fn potential_match(&self, path: KeyPath, prefix_id: u32) {
         self.inner
             .send(WorkbenchEvent::PotentialMatch {
                 bench_id: self.bench_id.clone(),
@@ -65,6 +72,15 @@ impl EventSender {
             .ok();
     }
 
+    pub fn processed(&self, processed_counter: u64) {
+        self.inner
+            .send(WorkbenchEvent::Processed {
+                bench_id: self.bench_id.clone(),
+                processed_counter,
+            })
+            .ok();
+    }
+
     pub fn stopped(&self, total_generated: u64, elapsed: Duration) {
         self.inner
             .send(WorkbenchEvent::Stopped {
@@ -127,7 +143,7 @@ mod tests {
     fn test_event_sender_potential_match() {
         let (tx, rx) = mpsc::channel();
         let sender = EventSender::new(tx, "test-bench".to_string());
-        let path = [1, 2, 3, 4, 5, 6];
+        let path = KeyPath::from_slice(&[1, 2, 3, 4, 5, 6]);
 
         sender.potential_match(path, 0);
 
@@ -146,6 +162,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_event_sender_processed() {
+        let (tx, rx) = mpsc::channel();
+        let sender = EventSender::new(tx, "test-bench".to_string());
+
+        sender.processed(4096);
+
+        let event = rx.recv().unwrap();
+        match event {
+            WorkbenchEvent::Processed {
+                bench_id,
+                processed_counter,
+            } => {
+                assert_eq!(bench_id, "test-bench");
+                assert_eq!(processed_counter, 4096);
+            }
+            _ => panic!("Expected Processed event"),
+        }
+    }
+
     #[test]
     fn test_event_sender_stopped() {
         let (tx, rx) = mpsc::channel();
@@ -178,7 +214,7 @@ mod tests {
         sender.started(now);
         sender.progress(100);
         sender.progress(200);
-        sender.potential_match([1, 2, 3, 4, 5, 6], 0);
+        sender.potential_match(KeyPath::from_slice(&[1, 2, 3, 4, 5, 6]), 0);
         sender.stopped(300, Duration::from_secs(5));
 
         let events: Vec<_> = rx.iter().take(5).collect();
@@ -221,16 +257,18 @@ mod tests {
 
         sender.started(Instant::now());
         sender.progress(50);
-        sender.potential_match([0; 6], 0);
+        sender.potential_match(KeyPath::from_slice(&[0; 6]), 0);
+        sender.processed(100);
         sender.stopped(100, Duration::from_secs(1));
 
-        let events: Vec<_> = rx.iter().take(4).collect();
+        let events: Vec<_> = rx.iter().take(5).collect();
 
         for event in events {
             let bench_id = match event {
                 WorkbenchEvent::Started { bench_id, .. } => bench_id,
                 WorkbenchEvent::Progress { bench_id, .. } => bench_id,
                 WorkbenchEvent::PotentialMatch { bench_id, .. } => bench_id,
+                WorkbenchEvent::Processed { bench_id, .. } => bench_id,
                 WorkbenchEvent::Stopped { bench_id, .. } => bench_id,
             };
             assert_eq!(bench_id, "test123");
@@ -246,7 +284,8 @@ mod tests {
 
         sender.started(Instant::now());
         sender.progress(100);
-        sender.potential_match([0; 6], 0);
+        sender.potential_match(KeyPath::from_slice(&[0; 6]), 0);
+        sender.processed(100);
         sender.stopped(100, Duration::from_secs(1));
     }
 }
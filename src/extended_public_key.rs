@@ -1,3 +1,4 @@
+use ripemd::Ripemd160;
 use secp256k1::PublicKey;
 use sha2::{Digest, Sha256};
 
@@ -47,6 +48,12 @@ impl ExtendedPubKey {
             chain_code,
         })
     }
+
+    /// BIP32 fingerprint: the first 4 bytes of the key's hash160
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let hash = Ripemd160::digest(Sha256::digest(self.public_key.serialize()));
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
 }
 
 #[cfg(test)]
@@ -77,6 +84,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_fingerprint_matches_bitcoin() {
+        let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let expected = xpub.parse::<bitcoin::bip32::Xpub>().unwrap().fingerprint();
+        assert_eq!(
+            ExtendedPubKey::from_str(xpub).unwrap().fingerprint(),
+            expected.to_bytes()
+        );
+    }
+
     #[test]
     fn test_from_str_invalid_length() {
         let xpub = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYm";
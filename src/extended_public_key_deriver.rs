@@ -1,17 +1,45 @@
+use crate::comb_multiplier::{batch_normalize, multiply_generator, AffinePoint, JacobianPoint};
 use crate::constants::NON_HARDENED_MAX_INDEX;
 use crate::extended_public_key::ExtendedPubKey;
+use crate::multi_hash160::MultiHash160;
+use crate::path_template::KeyPath;
 use hmac::{Hmac, Mac};
 use lru::LruCache;
 use ripemd::Ripemd160;
-use secp256k1::{PublicKey, Secp256k1};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
 use sha2::{Digest, Sha256, Sha512};
 use std::num::NonZeroUsize;
+use std::ops::Range;
 
 type ExtendedKeyResult = Result<([u8; 32], [u8; 32], [u8; 32]), String>;
 
 pub trait KeyDeriver {
+    #[cfg(test)]
     fn get_pubkey_hash_160(&mut self, path: &[u32]) -> Result<[u8; 20], String>;
+    #[cfg(test)]
     fn get_pubkey(&mut self, path: &[u32]) -> Result<[u8; 33], String>;
+    #[cfg(test)]
+    fn get_taproot_output_key(&mut self, path: &[u32]) -> Result<[u8; 32], String>;
+    fn get_script_hash_160(&mut self, pubkey_hash: &[u8; 20]) -> [u8; 20];
+
+    /// Hash160s of the children `indexes` of `parent_path` into `hashes`,
+    /// None where a derivation fails. The parent is looked up once for the
+    /// whole range.
+    fn get_children_hash_160(
+        &mut self,
+        parent_path: &[u32],
+        indexes: Range<u32>,
+        hashes: &mut Vec<Option<[u8; 20]>>,
+    ) -> Result<(), String>;
+
+    /// BIP86 output keys of the children `indexes` of `parent_path` into
+    /// `output_keys`, as `get_children_hash_160`
+    fn get_children_taproot_output_keys(
+        &mut self,
+        parent_path: &[u32],
+        indexes: Range<u32>,
+        output_keys: &mut Vec<Option<[u8; 32]>>,
+    ) -> Result<(), String>;
 }
 
 pub struct ExtendedPublicKeyDeriver {
@@ -20,6 +48,15 @@ pub struct ExtendedPublicKeyDeriver {
     base_xpub: ExtendedPubKey,
     sha256_hasher: Sha256,
     ripemd_hasher: Ripemd160,
+    /// The parent of the last children derived
+    parent: Option<(KeyPath, ParentKey)>,
+    /// The last children derived, kept for the other address types of the
+    /// same range
+    children: Option<(KeyPath, Range<u32>)>,
+    child_keys: Vec<Option<AffinePoint>>,
+    multi_hash160: MultiHash160,
+    compressed_keys: Vec<[u8; 33]>,
+    key_hashes: Vec<[u8; 20]>,
 }
 
 const MAX_CACHE_SIZE: usize = 10_000;
@@ -32,37 +69,25 @@ impl ExtendedPublicKeyDeriver {
             base_xpub: base_xpub.clone(),
             sha256_hasher: Sha256::new(),
             ripemd_hasher: Ripemd160::new(),
+            parent: None,
+            children: None,
+            child_keys: Vec::new(),
+            multi_hash160: MultiHash160::detect(),
+            compressed_keys: Vec::new(),
+            key_hashes: Vec::new(),
         }
     }
 
     fn derive_child(&self, parent: &ExtendedPubKey, index: u32) -> Result<ExtendedPubKey, String> {
-        let mut data = [0u8; 37]; // 33 bytes pubkey + 4 bytes index
-        data[0..33].copy_from_slice(&parent.public_key.serialize());
-        data[33..37].copy_from_slice(&index.to_be_bytes());
-
-        let mut hmac = Hmac::<Sha512>::new_from_slice(&parent.chain_code)
-            .map_err(|e| format!("HMAC error: {}", e))?;
-        hmac.update(&data);
-        let result = hmac.finalize().into_bytes();
-
-        let il = &result[0..32];
-        let ir = &result[32..];
-
-        let mut il_array = [0u8; 32];
-        il_array.copy_from_slice(il);
-        let tweak = secp256k1::SecretKey::from_byte_array(il_array)
-            .map_err(|e| format!("Invalid tweak: {}", e))?;
-
-        let child_pubkey = parent
-            .public_key
-            .combine(&PublicKey::from_secret_key(&self.secp, &tweak))
+        let parent_key = ParentKey::new(parent);
+        let (child_point, chain_code) = child_point(&parent_key, index)?;
+        let child = batch_normalize(&[child_point])[0]
+            .ok_or("Failed to derive child key: point at infinity")?;
+        let public_key = PublicKey::from_slice(&child.serialize_uncompressed())
             .map_err(|e| format!("Failed to derive child key: {}", e))?;
 
-        let mut chain_code = [0u8; 32];
-        chain_code.copy_from_slice(ir);
-
         Ok(ExtendedPubKey {
-            public_key: child_pubkey,
+            public_key,
             chain_code,
         })
     }
@@ -77,14 +102,195 @@ impl ExtendedPublicKeyDeriver {
         }
         self.derive_child(parent, index)
     }
+
+    /// Public keys of the children `indexes` of `parent_path` into
+    /// `child_keys`. Each goes through the comb tables and all of them are
+    /// normalized with one shared inversion.
+    fn derive_children(&mut self, parent_path: &[u32], indexes: Range<u32>) -> Result<(), String> {
+        let parent_path = KeyPath::from_slice(parent_path);
+        let children = (parent_path, indexes.clone());
+        if self.children.as_ref() == Some(&children) {
+            return Ok(());
+        }
+        self.children = None;
+
+        if indexes.end > NON_HARDENED_MAX_INDEX + 1 {
+            return Err(format!(
+                "{} is reserved for hardened derivation",
+                NON_HARDENED_MAX_INDEX + 1
+            ));
+        }
+        if self.parent.as_ref().map(|(path, _)| path) != Some(&parent_path) {
+            let xpub = self.get_derived_xpub(&parent_path)?;
+            self.parent = Some((parent_path, ParentKey::new(&xpub)));
+        }
+        let (_, parent) = self.parent.as_ref().unwrap();
+
+        // A failed derivation normalizes as infinity, None as well
+        let points: Vec<JacobianPoint> = indexes
+            .map(|index| {
+                child_point(parent, index).map_or(JacobianPoint::INFINITY, |(point, _)| point)
+            })
+            .collect();
+        self.child_keys = batch_normalize(&points);
+        self.children = Some(children);
+        Ok(())
+    }
+
+    /// BIP86 output key of `public_key`, see `get_taproot_output_key`
+    fn taproot_output_key_of(&mut self, public_key: &AffinePoint) -> Result<[u8; 32], String> {
+        let public_key = PublicKey::from_slice(&public_key.serialize_uncompressed())
+            .map_err(|e| format!("Invalid public key: {}", e))?;
+        self.taproot_tweak(&public_key)
+    }
+
+    fn taproot_tweak(&mut self, public_key: &PublicKey) -> Result<[u8; 32], String> {
+        let (internal_key, _) = public_key.x_only_public_key();
+
+        let tag_hash = Sha256::digest(b"TapTweak");
+        self.sha256_hasher.reset();
+        self.sha256_hasher.update(tag_hash);
+        self.sha256_hasher.update(tag_hash);
+        self.sha256_hasher.update(internal_key.serialize());
+        let tweak_hash: [u8; 32] = self.sha256_hasher.finalize_reset().into();
+
+        let tweak =
+            Scalar::from_be_bytes(tweak_hash).map_err(|e| format!("Invalid tweak: {}", e))?;
+        let (output_key, _) = internal_key
+            .add_tweak(&self.secp, &tweak)
+            .map_err(|e| format!("Failed to tweak key: {}", e))?;
+
+        Ok(output_key.serialize())
+    }
+}
+
+/// What the child derivations of one parent share
+struct ParentKey {
+    compressed: [u8; 33],
+    point: AffinePoint,
+    chain_code: [u8; 32],
+}
+
+impl ParentKey {
+    fn new(xpub: &ExtendedPubKey) -> Self {
+        ParentKey {
+            compressed: xpub.public_key.serialize(),
+            point: AffinePoint::from_uncompressed(&xpub.public_key.serialize_uncompressed()),
+            chain_code: xpub.chain_code,
+        }
+    }
+}
+
+/// BIP32 CKDpub, parent + IL * G with IL * G from the comb tables, left in
+/// Jacobian coordinates for a batch normalization. Also the child chain code.
+fn child_point(parent: &ParentKey, index: u32) -> Result<(JacobianPoint, [u8; 32]), String> {
+    let mut data = [0u8; 37]; // 33 bytes pubkey + 4 bytes index
+    data[0..33].copy_from_slice(&parent.compressed);
+    data[33..37].copy_from_slice(&index.to_be_bytes());
+
+    let mut hmac = Hmac::<Sha512>::new_from_slice(&parent.chain_code)
+        .map_err(|e| format!("HMAC error: {}", e))?;
+    hmac.update(&data);
+    let result = hmac.finalize().into_bytes();
+
+    let mut il = [0u8; 32];
+    il.copy_from_slice(&result[0..32]);
+    // IL must be a valid non-zero scalar, below the group order
+    SecretKey::from_byte_array(il).map_err(|e| format!("Invalid tweak: {}", e))?;
+
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&result[32..]);
+
+    Ok((
+        multiply_generator(&il).add_affine(&parent.point),
+        chain_code,
+    ))
 }
 
 impl KeyDeriver for ExtendedPublicKeyDeriver {
+    #[cfg(test)]
     fn get_pubkey_hash_160(&mut self, path: &[u32]) -> Result<[u8; 20], String> {
         let pubkey = self.get_pubkey(path)?;
+        Ok(self.hash_160(&pubkey))
+    }
 
+    #[cfg(test)]
+    fn get_pubkey(&mut self, path: &[u32]) -> Result<[u8; 33], String> {
+        let derived_xpub = self.get_derived_xpub(path)?;
+        Ok(derived_xpub.public_key.serialize())
+    }
+
+    /// BIP86 output key: the x-only child key tweaked with
+    /// tagged_hash("TapTweak", x), as there is no script path
+    #[cfg(test)]
+    fn get_taproot_output_key(&mut self, path: &[u32]) -> Result<[u8; 32], String> {
+        let derived_xpub = self.get_derived_xpub(path)?;
+        self.taproot_tweak(&derived_xpub.public_key)
+    }
+
+    /// Hash160 of the P2SH-P2WPKH redeem script `OP_0 <pubkey_hash>`, what
+    /// nested SegWit addresses encode
+    fn get_script_hash_160(&mut self, pubkey_hash: &[u8; 20]) -> [u8; 20] {
+        let mut redeem_script = [0u8; 22];
+        redeem_script[0] = 0x00; // OP_0
+        redeem_script[1] = 0x14; // push 20 bytes
+        redeem_script[2..].copy_from_slice(pubkey_hash);
+        self.hash_160(&redeem_script)
+    }
+
+    fn get_children_hash_160(
+        &mut self,
+        parent_path: &[u32],
+        indexes: Range<u32>,
+        hashes: &mut Vec<Option<[u8; 20]>>,
+    ) -> Result<(), String> {
+        self.derive_children(parent_path, indexes)?;
+
+        // Every key goes through the SIMD lanes, a failed one as zeros
+        self.compressed_keys.clear();
+        self.compressed_keys.extend(
+            self.child_keys
+                .iter()
+                .map(|key| key.map_or([0u8; 33], |key| key.serialize())),
+        );
+        self.key_hashes
+            .resize(self.compressed_keys.len(), [0u8; 20]);
+        self.multi_hash160
+            .hash_160(&self.compressed_keys, &mut self.key_hashes);
+
+        hashes.clear();
+        hashes.extend(
+            self.child_keys
+                .iter()
+                .zip(&self.key_hashes)
+                .map(|(key, hash)| key.map(|_| *hash)),
+        );
+        Ok(())
+    }
+
+    fn get_children_taproot_output_keys(
+        &mut self,
+        parent_path: &[u32],
+        indexes: Range<u32>,
+        output_keys: &mut Vec<Option<[u8; 32]>>,
+    ) -> Result<(), String> {
+        self.derive_children(parent_path, indexes)?;
+        let child_keys = std::mem::take(&mut self.child_keys);
+        output_keys.clear();
+        output_keys.extend(
+            child_keys
+                .iter()
+                .map(|key| key.and_then(|key| self.taproot_output_key_of(&key).ok())),
+        );
+        self.child_keys = child_keys;
+        Ok(())
+    }
+}
+
+impl ExtendedPublicKeyDeriver {
+    fn hash_160(&mut self, data: &[u8]) -> [u8; 20] {
         self.sha256_hasher.reset();
-        self.sha256_hasher.update(pubkey);
+        self.sha256_hasher.update(data);
         let hash = self.sha256_hasher.finalize_reset();
 
         self.ripemd_hasher.reset();
@@ -93,16 +299,9 @@ impl KeyDeriver for ExtendedPublicKeyDeriver {
 
         let mut result = [0u8; 20];
         result.copy_from_slice(&hash);
-        Ok(result)
-    }
-
-    fn get_pubkey(&mut self, path: &[u32]) -> Result<[u8; 33], String> {
-        let derived_xpub = self.get_derived_xpub(path)?;
-        Ok(derived_xpub.public_key.serialize())
+        result
     }
-}
 
-impl ExtendedPublicKeyDeriver {
     fn get_derived_xpub(&mut self, path: &[u32]) -> Result<ExtendedPubKey, String> {
         if path.is_empty() {
             return Ok(self.base_xpub.clone());
@@ -152,6 +351,118 @@ impl ExtendedPublicKeyDeriver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
+
+    /// The derivation before the comb tables: a generic multiplication and
+    /// a point add per child, each normalized on its own
+    fn reference_child(
+        secp: &Secp256k1<secp256k1::All>,
+        parent: &ExtendedPubKey,
+        index: u32,
+    ) -> PublicKey {
+        let mut data = [0u8; 37];
+        data[0..33].copy_from_slice(&parent.public_key.serialize());
+        data[33..37].copy_from_slice(&index.to_be_bytes());
+        let mut hmac = Hmac::<Sha512>::new_from_slice(&parent.chain_code).unwrap();
+        hmac.update(&data);
+        let result = hmac.finalize().into_bytes();
+
+        let tweak = SecretKey::from_byte_array(result[0..32].try_into().unwrap()).unwrap();
+        parent
+            .public_key
+            .combine(&PublicKey::from_secret_key(secp, &tweak))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_children_match_reference_derivation() {
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+        let secp = Secp256k1::new();
+
+        let parent_path = [131, 1342, 0, 0, 0];
+        let parent = deriver.get_derived_xpub(&parent_path).unwrap();
+        let mut hashes = vec![None; 3];
+        let mut output_keys = Vec::new();
+        deriver
+            .get_children_hash_160(&parent_path, 5..45, &mut hashes)
+            .unwrap();
+        deriver
+            .get_children_taproot_output_keys(&parent_path, 5..45, &mut output_keys)
+            .unwrap();
+        assert_eq!(hashes.len(), 40);
+        assert_eq!(output_keys.len(), 40);
+
+        for (index, (hash, output_key)) in (5..45).zip(hashes.iter().zip(&output_keys)) {
+            let expected = reference_child(&secp, &parent, index);
+            let path = [131, 1342, 0, 0, 0, index];
+            assert_eq!(hash.unwrap(), deriver.hash_160(&expected.serialize()));
+            assert_eq!(hash.unwrap(), deriver.get_pubkey_hash_160(&path).unwrap());
+            assert_eq!(
+                output_key.unwrap(),
+                deriver.get_taproot_output_key(&path).unwrap()
+            );
+        }
+
+        // Children of the xpub itself, and an empty range
+        deriver
+            .get_children_hash_160(&[], 7..8, &mut hashes)
+            .unwrap();
+        assert_eq!(hashes, vec![deriver.get_pubkey_hash_160(&[7]).ok()]);
+        deriver
+            .get_children_hash_160(&[1], 0..0, &mut hashes)
+            .unwrap();
+        assert!(hashes.is_empty());
+
+        assert!(deriver
+            .get_children_hash_160(&[1], 0x7FFF_FFFF..0x8000_0001, &mut hashes)
+            .is_err());
+    }
+
+    /// Compares the children derivation with the previous one, per child
+    /// key of a cached parent. Run with
+    /// `cargo test --release bench_children_hash_160 -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_children_hash_160() {
+        const KEYS: u32 = 200_000;
+        const BATCH_SIZE: u32 = 1024;
+
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+        let secp = Secp256k1::new();
+        let parent_path = [131, 1342, 0, 0];
+        let parent = deriver.get_derived_xpub(&parent_path).unwrap();
+
+        let start = Instant::now();
+        let mut reference_hash = [0u8; 20];
+        for index in 0..KEYS {
+            let child = reference_child(&secp, &parent, index);
+            reference_hash = deriver.hash_160(&child.serialize());
+        }
+        let reference = start.elapsed();
+
+        let start = Instant::now();
+        let mut hashes = Vec::new();
+        for batch_start in (0..KEYS).step_by(BATCH_SIZE as usize) {
+            let indexes = batch_start..(batch_start + BATCH_SIZE).min(KEYS);
+            deriver
+                .get_children_hash_160(&parent_path, indexes, &mut hashes)
+                .unwrap();
+        }
+        let batch = start.elapsed();
+
+        assert_eq!(hashes.last().copied().flatten(), Some(reference_hash));
+        let rate = |elapsed: std::time::Duration| KEYS as f64 / elapsed.as_secs_f64();
+        println!(
+            "from_secret_key + combine: {:.0} keys/s, comb + batch inversion: {:.0} keys/s ({:.2}x)",
+            rate(reference),
+            rate(batch),
+            reference.as_secs_f64() / batch.as_secs_f64()
+        );
+    }
 
     #[test]
     fn test_get_extended_key_returns_valid_data() {
@@ -201,4 +512,46 @@ mod tests {
         assert_eq!(result1.1, result2.1);
         assert_eq!(result1.2, result2.2);
     }
+
+    #[test]
+    fn test_get_taproot_output_key_matches_bitcoin_p2tr() {
+        use bitcoin::key::TapTweak;
+
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+
+        let path = [131, 1342, 0, 0, 0, 7];
+        let output_key = deriver.get_taproot_output_key(&path).unwrap();
+
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let pubkey =
+            bitcoin::secp256k1::PublicKey::from_slice(&deriver.get_pubkey(&path).unwrap()).unwrap();
+        let (internal_key, _) = pubkey.x_only_public_key();
+        let (expected, _) = internal_key.tap_tweak(&secp, None);
+
+        assert_eq!(output_key, expected.to_x_only_public_key().serialize());
+    }
+
+    #[test]
+    fn test_get_script_hash_160_matches_bitcoin_p2shwpkh() {
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+
+        let path = [131, 1342, 0, 0, 0, 7];
+        let pubkey_hash = deriver.get_pubkey_hash_160(&path).unwrap();
+        let script_hash = deriver.get_script_hash_160(&pubkey_hash);
+
+        let pubkey =
+            bitcoin::secp256k1::PublicKey::from_slice(&deriver.get_pubkey(&path).unwrap()).unwrap();
+        let address = bitcoin::Address::p2shwpkh(
+            &bitcoin::CompressedPublicKey(pubkey),
+            bitcoin::NetworkKind::Main,
+        );
+        let expected = address.script_pubkey();
+
+        // OP_HASH160 <script_hash> OP_EQUAL
+        assert_eq!(&expected.as_bytes()[2..22], &script_hash);
+    }
 }
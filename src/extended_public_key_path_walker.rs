@@ -1,69 +1,108 @@
 use crate::constants::NON_HARDENED_MAX_INDEX;
+use crate::path_template::{KeyPath, PathTemplate};
+use crate::seeds::Seeds;
+use std::ops::Range;
+
+/// Values of `a` (and `b`) per counter round: every non-hardened index,
+/// as in the GPU kernel
+const NON_HARDENED_COUNT: u64 = NON_HARDENED_MAX_INDEX as u64 + 1;
+
+/// Number of counters whose paths stay non-hardened, capped to what a u64
+/// counter can hold: `max_depth` address indexes for every value of the
+/// template's `a` and `b` levels
+pub fn counter_space(template: &PathTemplate, max_depth: u32) -> u64 {
+    (max_depth as u64).saturating_mul(NON_HARDENED_COUNT.pow(template.outer_counted_levels()))
+}
+
+/// The address indexes `indexes` below one parent path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChildRange {
+    pub parent: KeyPath,
+    pub indexes: Range<u32>,
+}
 
 pub trait PathWalker {
-    type Iterator: Iterator<Item = [u32; 6]>;
-    fn iter_from_counter(&self, start_counter: u64, chunk_size: u64) -> Self::Iterator;
+    type Iterator: Iterator<Item = ChildRange>;
+
+    /// The counters `start_counter..start_counter + chunk_size`, as runs of
+    /// address indexes that share a parent, in counter order
+    fn iter_parents_from_counter(&self, start_counter: u64, chunk_size: u64) -> Self::Iterator;
+
+    /// The path of every counter, one by one
+    #[cfg(test)]
+    fn iter_from_counter(
+        &self,
+        start_counter: u64,
+        chunk_size: u64,
+    ) -> impl Iterator<Item = KeyPath> {
+        self.iter_parents_from_counter(start_counter, chunk_size)
+            .flat_map(|children| {
+                let parent = children.parent;
+                children.indexes.map(move |index| parent.child(index))
+            })
+    }
 }
 
 pub struct ExtendedPublicKeyPathWalker {
-    seed0: u32,
-    seed1: u32,
+    seeds: Seeds,
     max_depth: u32,
+    template: PathTemplate,
 }
 
 impl ExtendedPublicKeyPathWalker {
-    pub fn new(seed0: u32, seed1: u32, max_depth: u32) -> Self {
+    pub fn new(seed0: u32, seed1: u32, max_depth: u32, template: PathTemplate) -> Self {
         Self {
-            seed0,
-            seed1,
+            seeds: Seeds { seed0, seed1 },
             max_depth,
+            template,
         }
     }
 }
 
 impl PathWalker for ExtendedPublicKeyPathWalker {
-    type Iterator = PathIterator;
+    type Iterator = ParentIterator;
 
-    fn iter_from_counter(&self, start_counter: u64, chunk_size: u64) -> PathIterator {
-        PathIterator {
-            seed0: self.seed0,
-            seed1: self.seed1,
+    fn iter_parents_from_counter(&self, start_counter: u64, chunk_size: u64) -> ParentIterator {
+        ParentIterator {
+            seeds: self.seeds,
             max_depth: self.max_depth,
+            template: self.template,
             current_counter: start_counter,
             end_counter: start_counter + chunk_size,
         }
     }
 }
 
-pub struct PathIterator {
-    seed0: u32,
-    seed1: u32,
+pub struct ParentIterator {
+    seeds: Seeds,
     max_depth: u32,
+    template: PathTemplate,
     current_counter: u64,
     end_counter: u64,
 }
 
-impl PathIterator {
-    fn counter_to_path(&self, counter: u64) -> [u32; 6] {
-        let index = (counter % self.max_depth as u64) as u32;
-        let a = ((counter / self.max_depth as u64) % NON_HARDENED_MAX_INDEX as u64) as u32;
-        let b = (counter / (self.max_depth as u64 * NON_HARDENED_MAX_INDEX as u64)) as u32;
-
-        [self.seed0, self.seed1, b, a, 0, index]
-    }
-}
-
-impl Iterator for PathIterator {
-    type Item = [u32; 6];
+impl Iterator for ParentIterator {
+    type Item = ChildRange;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_counter >= self.end_counter {
             return None;
         }
 
-        let path = self.counter_to_path(self.current_counter);
-        self.current_counter += 1;
-        Some(path)
+        let max_depth = self.max_depth as u64;
+        let counter = self.current_counter;
+        let index = (counter % max_depth) as u32;
+        let a = ((counter / max_depth) % NON_HARDENED_COUNT) as u32;
+        let b = (counter / (max_depth * NON_HARDENED_COUNT)) as u32;
+
+        // Up to the last address index of the parent, or the end of the run
+        let count = (max_depth - index as u64).min(self.end_counter - counter);
+        self.current_counter += count;
+
+        Some(ChildRange {
+            parent: self.template.parent_path(&self.seeds, b, a),
+            indexes: index..index + count as u32,
+        })
     }
 }
 
@@ -73,9 +112,9 @@ mod tests {
 
     #[test]
     fn test_simple_iteration() {
-        let walker = ExtendedPublicKeyPathWalker::new(1000, 2000, 100);
+        let walker = ExtendedPublicKeyPathWalker::new(1000, 2000, 100, PathTemplate::default());
 
-        let paths: Vec<[u32; 6]> = walker.iter_from_counter(0, 3).collect();
+        let paths: Vec<KeyPath> = walker.iter_from_counter(0, 3).collect();
 
         assert_eq!(paths.len(), 3);
         assert_eq!(paths[0], [1000, 2000, 0, 0, 0, 0]);
@@ -85,23 +124,23 @@ mod tests {
 
     #[test]
     fn test_counter_to_path_basic() {
-        let walker = ExtendedPublicKeyPathWalker::new(1000, 2000, 10000);
+        let walker = ExtendedPublicKeyPathWalker::new(1000, 2000, 10000, PathTemplate::default());
 
         let mut iter = walker.iter_from_counter(0, 1);
-        assert_eq!(iter.next(), Some([1000, 2000, 0, 0, 0, 0]));
+        assert_eq!(iter.next().unwrap(), [1000, 2000, 0, 0, 0, 0]);
 
         let mut iter = walker.iter_from_counter(5000, 1);
-        assert_eq!(iter.next(), Some([1000, 2000, 0, 0, 0, 5000]));
+        assert_eq!(iter.next().unwrap(), [1000, 2000, 0, 0, 0, 5000]);
 
         let mut iter = walker.iter_from_counter(10000, 1);
-        assert_eq!(iter.next(), Some([1000, 2000, 0, 1, 0, 0]));
+        assert_eq!(iter.next().unwrap(), [1000, 2000, 0, 1, 0, 0]);
     }
 
     #[test]
     fn test_chunk_iteration() {
-        let walker = ExtendedPublicKeyPathWalker::new(100, 200, 10);
+        let walker = ExtendedPublicKeyPathWalker::new(100, 200, 10, PathTemplate::default());
 
-        let paths: Vec<[u32; 6]> = walker.iter_from_counter(5, 3).collect();
+        let paths: Vec<KeyPath> = walker.iter_from_counter(5, 3).collect();
 
         assert_eq!(paths.len(), 3);
         assert_eq!(paths[0], [100, 200, 0, 0, 0, 5]);
@@ -111,18 +150,18 @@ mod tests {
 
     #[test]
     fn test_a_increments() {
-        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100);
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100, PathTemplate::default());
 
         let mut iter = walker.iter_from_counter(100, 1);
-        assert_eq!(iter.next(), Some([0, 0, 0, 1, 0, 0]));
+        assert_eq!(iter.next().unwrap(), [0, 0, 0, 1, 0, 0]);
 
         let mut iter = walker.iter_from_counter(200, 1);
-        assert_eq!(iter.next(), Some([0, 0, 0, 2, 0, 0]));
+        assert_eq!(iter.next().unwrap(), [0, 0, 0, 2, 0, 0]);
     }
 
     #[test]
     fn test_fixed_zero_at_position_4() {
-        let walker = ExtendedPublicKeyPathWalker::new(123, 456, 1000);
+        let walker = ExtendedPublicKeyPathWalker::new(123, 456, 1000, PathTemplate::default());
 
         for counter in [0, 100, 1000, 10000, 1_000_000] {
             let mut iter = walker.iter_from_counter(counter, 1);
@@ -136,27 +175,27 @@ mod tests {
 
     #[test]
     fn test_max_depth_boundary() {
-        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100);
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100, PathTemplate::default());
 
         // Just before max_depth
         let mut iter = walker.iter_from_counter(99, 1);
-        assert_eq!(iter.next(), Some([0, 0, 0, 0, 0, 99]));
+        assert_eq!(iter.next().unwrap(), [0, 0, 0, 0, 0, 99]);
 
         // At max_depth - should roll over to a=1, index=0
         let mut iter = walker.iter_from_counter(100, 1);
-        assert_eq!(iter.next(), Some([0, 0, 0, 1, 0, 0]));
+        assert_eq!(iter.next().unwrap(), [0, 0, 0, 1, 0, 0]);
 
         // One after max_depth
         let mut iter = walker.iter_from_counter(101, 1);
-        assert_eq!(iter.next(), Some([0, 0, 0, 1, 0, 1]));
+        assert_eq!(iter.next().unwrap(), [0, 0, 0, 1, 0, 1]);
     }
 
     #[test]
     fn test_continuous_iteration_across_boundary() {
-        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 10);
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 10, PathTemplate::default());
 
         // Start at 8, iterate across the boundary
-        let paths: Vec<[u32; 6]> = walker.iter_from_counter(8, 5).collect();
+        let paths: Vec<KeyPath> = walker.iter_from_counter(8, 5).collect();
 
         assert_eq!(paths.len(), 5);
         assert_eq!(paths[0], [0, 0, 0, 0, 0, 8]); // counter=8
@@ -168,11 +207,11 @@ mod tests {
 
     #[test]
     fn test_a_increments_correctly() {
-        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100);
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100, PathTemplate::default());
 
         // Start at a=1000, index=0
         let counter_start = 1000 * 100; // a=1000, index=0
-        let paths: Vec<[u32; 6]> = walker.iter_from_counter(counter_start, 150).collect();
+        let paths: Vec<KeyPath> = walker.iter_from_counter(counter_start, 150).collect();
 
         // First 100 should have a=1000, index 0-99
         for (i, path) in paths.iter().enumerate().take(100) {
@@ -189,7 +228,7 @@ mod tests {
 
     #[test]
     fn test_large_counter() {
-        let walker = ExtendedPublicKeyPathWalker::new(1000, 2000, 10000);
+        let walker = ExtendedPublicKeyPathWalker::new(1000, 2000, 10000, PathTemplate::default());
 
         // Large counter that should set both a and b
         let counter = 5_000_000_000u64;
@@ -204,8 +243,8 @@ mod tests {
 
         // Verify decomposition - NOW WITH CORRECT ORDER
         let expected_index = (counter % 10000) as u32;
-        let expected_a = ((counter / 10000) % 0x7FFFFFFF) as u32;
-        let expected_b = (counter / (10000 * 0x7FFFFFFF)) as u32;
+        let expected_a = ((counter / 10000) % 0x80000000) as u32;
+        let expected_b = (counter / (10000 * 0x80000000)) as u32;
 
         assert_eq!(path[5], expected_index);
         assert_eq!(path[3], expected_a);
@@ -214,7 +253,7 @@ mod tests {
 
     #[test]
     fn test_seeds_preserved() {
-        let walker = ExtendedPublicKeyPathWalker::new(12345, 67890, 100);
+        let walker = ExtendedPublicKeyPathWalker::new(12345, 67890, 100, PathTemplate::default());
 
         for counter in [0, 99, 100, 1000, 1_000_000] {
             let mut iter = walker.iter_from_counter(counter, 1);
@@ -226,9 +265,9 @@ mod tests {
 
     #[test]
     fn test_iterator_stops_at_chunk_size() {
-        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100);
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 100, PathTemplate::default());
 
-        let paths: Vec<[u32; 6]> = walker.iter_from_counter(0, 5).collect();
+        let paths: Vec<KeyPath> = walker.iter_from_counter(0, 5).collect();
 
         assert_eq!(paths.len(), 5, "Should stop exactly at chunk_size");
 
@@ -242,7 +281,7 @@ mod tests {
 
     #[test]
     fn test_path_always_6_levels() {
-        let walker = ExtendedPublicKeyPathWalker::new(100, 200, 1000);
+        let walker = ExtendedPublicKeyPathWalker::new(100, 200, 1000, PathTemplate::default());
 
         for counter in [0, 500, 999, 1000, 5000, 1_000_000, 5_000_000_000] {
             let mut iter = walker.iter_from_counter(counter, 1);
@@ -258,7 +297,7 @@ mod tests {
 
     #[test]
     fn test_bip32_limits_never_exceeded() {
-        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 10000);
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 10000, PathTemplate::default());
         let max_bip32 = 0x7FFFFFFF;
 
         // Test various large counters
@@ -284,4 +323,74 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_b_increments_after_every_a() {
+        let walker = ExtendedPublicKeyPathWalker::new(0, 0, 10, PathTemplate::default());
+
+        let mut iter = walker.iter_from_counter(10 * 0x80000000 - 1, 2);
+        assert_eq!(iter.next().unwrap(), [0, 0, 0, 0x7FFFFFFF, 0, 9]);
+        assert_eq!(iter.next().unwrap(), [0, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_parents_split_at_max_depth() {
+        let walker = ExtendedPublicKeyPathWalker::new(1, 2, 10, PathTemplate::default());
+
+        let parents: Vec<ChildRange> = walker.iter_parents_from_counter(8, 15).collect();
+        assert_eq!(
+            parents,
+            vec![
+                ChildRange {
+                    parent: KeyPath::from_slice(&[1, 2, 0, 0, 0]),
+                    indexes: 8..10,
+                },
+                ChildRange {
+                    parent: KeyPath::from_slice(&[1, 2, 0, 1, 0]),
+                    indexes: 0..10,
+                },
+                ChildRange {
+                    parent: KeyPath::from_slice(&[1, 2, 0, 2, 0]),
+                    indexes: 0..3,
+                },
+            ]
+        );
+        assert_eq!(walker.iter_parents_from_counter(8, 0).next(), None);
+    }
+
+    #[test]
+    fn test_counter_space_stays_non_hardened() {
+        let template = PathTemplate::default();
+        assert_eq!(counter_space(&template, 1), 1 << 62);
+        assert_eq!(counter_space(&template, 1000), u64::MAX);
+
+        for max_depth in [1, 3, 4, 1000] {
+            let walker = ExtendedPublicKeyPathWalker::new(0, 0, max_depth, template);
+            let path = walker
+                .iter_from_counter(counter_space(&template, max_depth) - 1, 1)
+                .next()
+                .unwrap();
+            assert!(path.iter().all(|&level| level <= 0x7FFFFFFF));
+        }
+    }
+
+    #[test]
+    fn test_shorter_template() {
+        let template = PathTemplate::parse("s0/a/1/i").unwrap();
+        assert_eq!(counter_space(&template, 10), 10 * 0x80000000);
+
+        let walker = ExtendedPublicKeyPathWalker::new(7, 8, 10, template);
+        let paths: Vec<KeyPath> = walker.iter_from_counter(9, 2).collect();
+        assert_eq!(paths[0], [7, 0, 1, 9]);
+        assert_eq!(paths[1], [7, 1, 1, 0]);
+
+        let last = walker
+            .iter_from_counter(counter_space(&template, 10) - 1, 1)
+            .next()
+            .unwrap();
+        assert_eq!(last, [7, 0x7FFFFFFF, 1, 9]);
+
+        let template = PathTemplate::parse("i").unwrap();
+        assert_eq!(counter_space(&template, 10), 10);
+    }
 }
@@ -0,0 +1,204 @@
+use crate::constants::DEFAULT_MAX_DEPTH;
+use crate::difficulty::Difficulty;
+use crate::path_template::PathTemplate;
+use crate::prefix::Prefix;
+use std::time::Duration;
+
+/// Expected time to a hit below the gap limit, with the searched layout and
+/// with the default one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GapEstimate {
+    pub gap_limit: u32,
+    /// Searching only the indexes below the gap limit, as this run does
+    pub bounded_secs: f64,
+    /// Searching up to the default max depth, where only the hits below the
+    /// gap limit count
+    pub default_layout_secs: f64,
+}
+
+/// Scores paths by their address index: a hit only counts below the gap
+/// limit, the number of empty addresses a wallet scans past. A small max
+/// depth wastes no hit but derives a new parent every few addresses, so the
+/// measured rate is converted to the rate of the default layout with the
+/// derivations each parent costs.
+#[derive(Clone, Debug)]
+pub struct GapEstimator {
+    match_probability: f64,
+    parent_derivations: u32,
+    gap_limit: u32,
+}
+
+impl GapEstimator {
+    pub fn new(prefixes: &[Prefix], template: &PathTemplate, gap_limit: u32) -> Self {
+        GapEstimator {
+            match_probability: Difficulty::new(prefixes).combined,
+            parent_derivations: template.parent_derivations(),
+            gap_limit,
+        }
+    }
+
+    /// `addresses_per_sec` is the rate measured with the max depth set to
+    /// the gap limit. None until a rate is known.
+    pub fn estimate(&self, addresses_per_sec: f64) -> Option<GapEstimate> {
+        if addresses_per_sec <= 0.0 || self.match_probability <= 0.0 {
+            return None;
+        }
+
+        // Addresses per second if no parent ever had to be derived
+        let address_rate = addresses_per_sec / self.useful_share_of_work(self.gap_limit);
+        let expected_secs = |max_depth: u32| -> f64 {
+            let rate = address_rate * self.useful_share_of_work(max_depth);
+            let below_gap_limit = (self.gap_limit.min(max_depth) as f64) / max_depth as f64;
+            1.0 / (self.match_probability * rate * below_gap_limit)
+        };
+
+        Some(GapEstimate {
+            gap_limit: self.gap_limit,
+            bounded_secs: expected_secs(self.gap_limit),
+            default_layout_secs: expected_secs(DEFAULT_MAX_DEPTH),
+        })
+    }
+
+    /// Share of the derivations that produce an address rather than a parent
+    fn useful_share_of_work(&self, max_depth: u32) -> f64 {
+        let max_depth = max_depth.max(1) as f64;
+        max_depth / (max_depth + self.parent_derivations as f64)
+    }
+}
+
+/// Years past which a duration is written in scientific notation
+const SCIENTIFIC_YEARS: f64 = 1e6;
+
+/// Rounds a duration to its largest unit: "45s", "12m", "3.5h", "2.1 days",
+/// "4.6e34 years"
+pub fn format_duration(secs: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = 60.0 * MINUTE;
+    const DAY: f64 = 24.0 * HOUR;
+    const YEAR: f64 = 365.25 * DAY;
+
+    if !secs.is_finite() {
+        "forever".to_string()
+    } else if secs < 1.0 {
+        "<1s".to_string()
+    } else if secs < MINUTE {
+        format!("{:.0}s", secs)
+    } else if secs < HOUR {
+        format!("{:.0}m", secs / MINUTE)
+    } else if secs < DAY {
+        format!("{:.1}h", secs / HOUR)
+    } else if secs < YEAR {
+        format!("{:.1} days", secs / DAY)
+    } else if secs < SCIENTIFIC_YEARS * YEAR {
+        format!("{:.1} years", secs / YEAR)
+    } else {
+        format!("{:.1e} years", secs / YEAR)
+    }
+}
+
+/// An expected duration: "~3.5h", but "<1s" and "forever" as they are
+pub fn format_estimate(secs: f64) -> String {
+    if secs.is_finite() && secs >= 1.0 {
+        format!("~{}", format_duration(secs))
+    } else {
+        format_duration(secs)
+    }
+}
+
+/// Reads a duration like the ones `format_duration` writes: "90s", "45m",
+/// "1.5h", "2d", or a bare number of seconds
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let scale = match unit.trim() {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        unit => {
+            return Err(format!(
+                "Unknown duration unit '{}', use s, m, h or d (e.g. 30m)",
+                unit
+            ))
+        }
+    };
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}', e.g. 90s, 30m or 1.5h", text))?;
+    if value <= 0.0 {
+        return Err("The duration must be positive".to_string());
+    }
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+
+    fn estimator(gap_limit: u32) -> GapEstimator {
+        // 1 chance in 1024
+        let prefixes = vec![Prefix::new("bc1qxy", Network::Mainnet).unwrap()];
+        GapEstimator::new(&prefixes, &PathTemplate::default(), gap_limit)
+    }
+
+    #[test]
+    fn test_bounded_gap_beats_the_default_layout_below_its_max_depth() {
+        let estimate = estimator(20).estimate(1024.0).unwrap();
+
+        // Every hit counts at the measured rate
+        assert!((estimate.bounded_secs - 1.0).abs() < 1e-9);
+        // 1 hit in 50 is below 20 with a max depth of 1000, at a rate
+        // 1000/1002 * 22/20 times the measured one
+        let default_rate = 1024.0 * 22.0 / 20.0 * 1000.0 / 1002.0;
+        assert!((estimate.default_layout_secs - 1024.0 * 50.0 / default_rate).abs() < 1e-9);
+        assert!(estimate.default_layout_secs > 40.0 * estimate.bounded_secs);
+    }
+
+    #[test]
+    fn test_large_gap_limits_only_cost_parents() {
+        let estimate = estimator(100_000).estimate(1024.0).unwrap();
+
+        // Every hit of the default layout is below the gap limit too
+        assert!(estimate.default_layout_secs > estimate.bounded_secs);
+        assert!(estimate.default_layout_secs < 1.01 * estimate.bounded_secs);
+    }
+
+    #[test]
+    fn test_no_estimate_without_a_rate() {
+        assert_eq!(estimator(20).estimate(0.0), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0.2), "<1s");
+        assert_eq!(format_duration(45.2), "45s");
+        assert_eq!(format_duration(720.0), "12m");
+        assert_eq!(format_duration(3.5 * 3600.0), "3.5h");
+        assert_eq!(format_duration(2.1 * 86400.0), "2.1 days");
+        assert_eq!(format_duration(12345.6 * 365.25 * 86400.0), "12345.6 years");
+        assert_eq!(format_duration(4.63e34 * 365.25 * 86400.0), "4.6e34 years");
+        assert_eq!(format_duration(f64::INFINITY), "forever");
+    }
+
+    #[test]
+    fn test_format_estimate() {
+        assert_eq!(format_estimate(720.0), "~12m");
+        assert_eq!(format_estimate(0.2), "<1s");
+        assert_eq!(format_estimate(f64::INFINITY), "forever");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("45m"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(2 * 86400)));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("10 weeks").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+}
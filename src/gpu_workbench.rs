@@ -2,7 +2,9 @@ use crate::events::EventSender;
 use crate::opencl::cache_preloader::CachePreloader;
 use crate::opencl::cache_range_analyzer::CacheRangeAnalyzer;
 use crate::opencl::g_tables;
-use crate::opencl::gpu_cache::{GpuCache, XPub};
+use crate::opencl::gpu_cache::{GpuCache, GpuPrefixTable, XPub};
+use crate::prefix_table::PrefixTables;
+use crate::seeds::Seeds;
 use crate::workbench::Workbench;
 use crate::workbench_config::WorkbenchConfig;
 use ocl::{Buffer, Context, Device, Kernel, Platform, Program, Queue};
@@ -17,6 +19,8 @@ use std::time::{Duration, Instant};
 /// time so the GPU never stalls waiting for them.
 struct PreloadedBatch {
     start_counter: u64,
+    /// `GPU_WORK_SIZE`, but for the last batch of the range
+    size: u64,
     cache_keys: Vec<[u32; 2]>,
     xpubs: Vec<XPub>,
 }
@@ -66,8 +70,6 @@ impl GpuWorkbench {
         device_index: usize,
         platform_index: usize,
     ) {
-        let start_time = Instant::now();
-
         // Initialize OpenCL context and queue
         let (device, context, queue) = match Self::init_opencl(device_index, platform_index) {
             Ok(result) => result,
@@ -98,15 +100,37 @@ impl GpuWorkbench {
 
         event_sender.started(Instant::now());
 
-        // Prepare GPU ranges from all prefixes
-        let gpu_ranges = Self::prepare_gpu_ranges(&config.prefixes);
-        let range_count = gpu_ranges.len() as u32;
+        // Sorted range tables of all prefixes, binary searched by the kernel
+        let table = GpuPrefixTable::from(&PrefixTables::new(&config.prefixes));
 
         // Create ALL GPU buffers ONCE before the loop
         let ranges_buffer = match Buffer::<crate::opencl::gpu_cache::Hash160RangeGpu>::builder()
             .queue(queue.clone())
-            .len(gpu_ranges.len())
-            .copy_host_slice(&gpu_ranges)
+            .len(table.ranges.len())
+            .copy_host_slice(&table.ranges)
+            .build()
+        {
+            Ok(buf) => buf,
+            Err(_) => return,
+        };
+
+        // Ids of the prefixes covering each range
+        let candidates_buffer = match Buffer::<u32>::builder()
+            .queue(queue.clone())
+            .len(table.candidates.len())
+            .copy_host_slice(&table.candidates)
+            .build()
+        {
+            Ok(buf) => buf,
+            Err(_) => return,
+        };
+
+        // One flag per prefix id, rewritten when the orchestrator drops one
+        let mut filter_generation = config.prefix_filter.generation();
+        let wanted_buffer = match Buffer::<u8>::builder()
+            .queue(queue.clone())
+            .len(config.prefixes.len())
+            .copy_host_slice(&config.prefix_filter.flags())
             .build()
         {
             Ok(buf) => buf,
@@ -115,6 +139,18 @@ impl GpuWorkbench {
 
         // Precomputed g_times_scalar tables: computed once on the CPU,
         // written once to GPU global memory, then read-only for the whole run
+        // One suffix check per prefix, indexed by prefix id
+        let gpu_suffixes = Self::prepare_gpu_suffixes(&config.prefixes);
+        let suffixes_buffer = match Buffer::<crate::opencl::gpu_cache::SuffixGpu>::builder()
+            .queue(queue.clone())
+            .len(gpu_suffixes.len())
+            .copy_host_slice(&gpu_suffixes)
+            .build()
+        {
+            Ok(buf) => buf,
+            Err(_) => return,
+        };
+
         let g_times_tables_buffer = match g_tables::create_g_tables_buffer(&queue) {
             Ok(buf) => buf,
             Err(e) => {
@@ -159,7 +195,7 @@ impl GpuWorkbench {
             Err(_) => return,
         };
 
-        let matches_prefix_id_buffer = match Buffer::<u8>::builder()
+        let matches_prefix_id_buffer = match Buffer::<u32>::builder()
             .queue(queue.clone())
             .len(MAX_MATCHES)
             .build()
@@ -211,7 +247,7 @@ impl GpuWorkbench {
             .arg(cache_keys_buffer) // arg 0 - fixed (same buffer object always)
             .arg(cache_values_buffer) // arg 1 - fixed (same buffer object always)
             .arg(&ranges_buffer) // arg 2 - fixed (Hash160RangeGpu struct buffer)
-            .arg(range_count) // arg 3 - fixed
+            .arg(table.pubkey_range_count) // arg 3 - fixed
             .arg(cache_size_buffer) // arg 4 - fixed buffer (GpuCache updates its content)
             .arg(0u64) // arg 5 - start_counter, will update in loop
             .arg(config.max_depth) // arg 6 - fixed
@@ -219,10 +255,14 @@ impl GpuWorkbench {
             .arg(&matches_b_buffer) // arg 8 - fixed
             .arg(&matches_a_buffer) // arg 9 - fixed
             .arg(&matches_index_buffer) // arg 10 - fixed
-            .arg(&matches_prefix_id_buffer) // arg 11 - fixed (NEW!)
+            .arg(&matches_prefix_id_buffer) // arg 11 - fixed
             .arg(&match_count_buffer) // arg 12 - fixed (reset separately)
             .arg(&cache_miss_error_buffer) // arg 13 - fixed (reset separately)
-            .arg(&g_times_tables_buffer); // arg 14 - fixed (precomputed tables)
+            .arg(&g_times_tables_buffer) // arg 14 - fixed (precomputed tables)
+            .arg(&suffixes_buffer) // arg 15 - fixed (SuffixGpu struct buffer)
+            .arg(table.script_range_count) // arg 16 - fixed
+            .arg(&candidates_buffer) // arg 17 - fixed
+            .arg(&wanted_buffer); // arg 18 - fixed (rewritten when a prefix is dropped)
 
         // ocl's arg type check parses the "Point*" type name as an int
         // pointer ("Point" contains "int"), rejecting the tables buffer.
@@ -247,16 +287,28 @@ impl GpuWorkbench {
         // (~9ms), making the GPU the bottleneck. Capping here avoids
         // oversubscribing the CPU workbenches in the default CPU+GPU mode and
         // avoids spawning dozens of threads per batch on many-core hosts.
-        let derive_threads = thread::available_parallelism()
-            .map(|n| n.get().min(8))
-            .unwrap_or(4);
+        // Cores reserved for the GPU set the count instead.
+        let derive_threads = match producer_config.placement.cores.len() {
+            0 => thread::available_parallelism()
+                .map(|n| n.get().min(8))
+                .unwrap_or(4),
+            reserved => reserved,
+        };
 
         let producer = thread::spawn(move || {
-            let mut counter = 0u64;
+            producer_config.placement.place_current_thread(0);
+            let mut counter = producer_config.counter_range.start;
             while !producer_stop.load(Ordering::Relaxed) {
+                // The last batch is cut at the end of the range rather than
+                // spilling into the next device's counters
+                let size = GPU_WORK_SIZE.min(producer_config.counter_range.end - counter);
+                if size == 0 {
+                    break;
+                }
+
                 let cache_keys = CacheRangeAnalyzer::analyze_counter_range(
                     counter,
-                    GPU_WORK_SIZE,
+                    size,
                     producer_config.max_depth,
                 );
 
@@ -265,7 +317,9 @@ impl GpuWorkbench {
                     &producer_config.xpub,
                     producer_config.seed0,
                     producer_config.seed1,
+                    &producer_config.template,
                     derive_threads,
+                    &producer_config.placement,
                 ) {
                     Ok(xpubs) => xpubs,
                     Err(e) => {
@@ -276,6 +330,7 @@ impl GpuWorkbench {
 
                 let batch = PreloadedBatch {
                     start_counter: counter,
+                    size,
                     cache_keys,
                     xpubs,
                 };
@@ -286,15 +341,18 @@ impl GpuWorkbench {
                     break;
                 }
 
-                counter += GPU_WORK_SIZE;
+                counter += size;
             }
         });
 
         let mut last_report = Instant::now();
         let mut generated_since_last_report = 0u64;
+        // Batches run in counter order: once one's matches are sent, every
+        // counter below its end is done
+        let mut processed_counter = config.counter_range.start;
         let mut needs_match_count_reset = false; // Track if we need to reset match count
 
-        while !stop_signal.load(Ordering::Relaxed) {
+        'batches: while !stop_signal.load(Ordering::Relaxed) {
             // Receive the next preloaded batch from the producer. An error
             // means the producer has stopped (shutdown or derivation error).
             let batch = match batch_rx.recv() {
@@ -311,116 +369,153 @@ impl GpuWorkbench {
                 break;
             }
 
-            // Only reset match counter if there were matches in the previous iteration
-            if needs_match_count_reset {
-                if let Err(e) = match_count_buffer.cmd().fill(0u32, None).enq() {
-                    eprintln!("Failed to reset match_count: {}", e);
+            // The previous kernel has finished, the flags are not in use
+            let generation = config.prefix_filter.generation();
+            if generation != filter_generation {
+                if let Err(e) = wanted_buffer.write(&config.prefix_filter.flags()).enq() {
+                    eprintln!("Failed to update the wanted prefixes: {}", e);
                     break;
                 }
+                filter_generation = generation;
+            }
 
-                // Ensure reset operation completed
-                if let Err(e) = queue.finish() {
-                    eprintln!("Failed to sync match count reset: {}", e);
-                    break;
-                }
+            // A batch whose matches overflow the buffers runs again in
+            // halves, with the same parents in the cache
+            let mut pieces = vec![(batch.start_counter, batch.size)];
+            while let Some((start, size)) = pieces.pop() {
+                // Only reset match counter if there were matches in the previous iteration
+                if needs_match_count_reset {
+                    if let Err(e) = match_count_buffer.cmd().fill(0u32, None).enq() {
+                        eprintln!("Failed to reset match_count: {}", e);
+                        break 'batches;
+                    }
 
-                needs_match_count_reset = false;
-            }
+                    // Ensure reset operation completed
+                    if let Err(e) = queue.finish() {
+                        eprintln!("Failed to sync match count reset: {}", e);
+                        break 'batches;
+                    }
 
-            // Only need to update the counter arg - cache size is now managed by GpuCache buffer
-            if let Err(e) = kernel.set_arg(5, batch.start_counter) {
-                eprintln!("Failed to set start_counter arg: {}", e);
-                break;
-            }
+                    needs_match_count_reset = false;
+                }
 
-            if let Err(e) = unsafe { kernel.enq() } {
-                eprintln!("Failed to execute kernel: {}", e);
-                break;
-            }
+                // Only need to update the counter arg - cache size is now managed by GpuCache buffer
+                if let Err(e) = kernel.set_arg(5, start) {
+                    eprintln!("Failed to set start_counter arg: {}", e);
+                    break 'batches;
+                }
+                // One work item per counter of the piece
+                if let Err(e) = unsafe { kernel.cmd().global_work_size(size as usize).enq() } {
+                    eprintln!("Failed to execute kernel: {}", e);
+                    break 'batches;
+                }
 
-            // Wait for kernel completion
-            if let Err(e) = queue.finish() {
-                eprintln!("Failed to finish queue: {}", e);
-                break;
-            }
+                // Wait for kernel completion
+                if let Err(e) = queue.finish() {
+                    eprintln!("Failed to finish queue: {}", e);
+                    break 'batches;
+                }
 
-            // Check for cache miss errors
-            let mut cache_miss_error = vec![0u32; 1];
-            if let Err(e) = cache_miss_error_buffer.read(&mut cache_miss_error).enq() {
-                eprintln!("Failed to read cache_miss_error: {}", e);
-                break;
-            }
+                // Check for cache miss errors
+                let mut cache_miss_error = vec![0u32; 1];
+                if let Err(e) = cache_miss_error_buffer.read(&mut cache_miss_error).enq() {
+                    eprintln!("Failed to read cache_miss_error: {}", e);
+                    break 'batches;
+                }
 
-            if cache_miss_error[0] != 0 {
-                panic!(
-                    "CACHE MISS ERROR: {} lookups failed! This should never happen - cache was not properly preloaded.",
-                    cache_miss_error[0]
-                );
-            }
+                if cache_miss_error[0] != 0 {
+                    panic!(
+                        "CACHE MISS ERROR: {} lookups failed! This should never happen - cache was not properly preloaded.",
+                        cache_miss_error[0]
+                    );
+                }
 
-            // Read match count
-            let mut match_count = vec![0u32; 1];
-            if let Err(e) = match_count_buffer.read(&mut match_count).enq() {
-                eprintln!("Failed to read match count: {}", e);
-                break;
-            }
+                // Read match count
+                let mut match_count = vec![0u32; 1];
+                if let Err(e) = match_count_buffer.read(&mut match_count).enq() {
+                    eprintln!("Failed to read match count: {}", e);
+                    break 'batches;
+                }
 
-            let num_matches = match_count[0].min(MAX_MATCHES as u32) as usize;
+                if match_count[0] as usize > MAX_MATCHES {
+                    needs_match_count_reset = true;
+                    if size > 1 {
+                        // Popped in counter order
+                        let half = size / 2;
+                        pieces.push((start + half, size - half));
+                        pieces.push((start, half));
+                        continue;
+                    }
+                    eprintln!(
+                        "Dropped {} of the {} matches of counter {}",
+                        match_count[0] as usize - MAX_MATCHES,
+                        match_count[0],
+                        start
+                    );
+                }
+                let num_matches = match_count[0].min(MAX_MATCHES as u32) as usize;
 
-            if num_matches > 0 {
-                // Mark that we need to reset match count in the next iteration
-                needs_match_count_reset = true;
+                if num_matches > 0 {
+                    // Mark that we need to reset match count in the next iteration
+                    needs_match_count_reset = true;
 
-                // Read matches
-                let mut matches_hash160_data = vec![0u8; num_matches * 20];
-                let mut matches_b_data = vec![0u32; num_matches];
-                let mut matches_a_data = vec![0u32; num_matches];
-                let mut matches_index_data = vec![0u32; num_matches];
+                    // Read matches
+                    let mut matches_hash160_data = vec![0u8; num_matches * 20];
+                    let mut matches_b_data = vec![0u32; num_matches];
+                    let mut matches_a_data = vec![0u32; num_matches];
+                    let mut matches_index_data = vec![0u32; num_matches];
 
-                if let Err(e) = matches_hash160_buffer.read(&mut matches_hash160_data).enq() {
-                    eprintln!("Failed to read hash160: {}", e);
-                    break;
-                }
-                if let Err(e) = matches_b_buffer.read(&mut matches_b_data).enq() {
-                    eprintln!("Failed to read b: {}", e);
-                    break;
-                }
-                if let Err(e) = matches_a_buffer.read(&mut matches_a_data).enq() {
-                    eprintln!("Failed to read a: {}", e);
-                    break;
-                }
-                if let Err(e) = matches_index_buffer.read(&mut matches_index_data).enq() {
-                    eprintln!("Failed to read index: {}", e);
-                    break;
-                }
+                    if let Err(e) = matches_hash160_buffer.read(&mut matches_hash160_data).enq() {
+                        eprintln!("Failed to read hash160: {}", e);
+                        break 'batches;
+                    }
+                    if let Err(e) = matches_b_buffer.read(&mut matches_b_data).enq() {
+                        eprintln!("Failed to read b: {}", e);
+                        break 'batches;
+                    }
+                    if let Err(e) = matches_a_buffer.read(&mut matches_a_data).enq() {
+                        eprintln!("Failed to read a: {}", e);
+                        break 'batches;
+                    }
+                    if let Err(e) = matches_index_buffer.read(&mut matches_index_data).enq() {
+                        eprintln!("Failed to read index: {}", e);
+                        break 'batches;
+                    }
 
-                let mut matches_prefix_id_data = vec![0u8; num_matches];
-                if let Err(e) = matches_prefix_id_buffer
-                    .read(&mut matches_prefix_id_data)
-                    .enq()
-                {
-                    eprintln!("Failed to read prefix_id: {}", e);
-                    break;
-                }
+                    let mut matches_prefix_id_data = vec![0u32; num_matches];
+                    if let Err(e) = matches_prefix_id_buffer
+                        .read(&mut matches_prefix_id_data)
+                        .enq()
+                    {
+                        eprintln!("Failed to read prefix_id: {}", e);
+                        break 'batches;
+                    }
 
-                // Process matches
-                for i in 0..num_matches {
-                    let b = matches_b_data[i];
-                    let a = matches_a_data[i];
-                    let index = matches_index_data[i];
-                    let prefix_id = matches_prefix_id_data[i];
-                    let path = [config.seed0, config.seed1, b, a, 0, index];
-                    event_sender.potential_match(path, prefix_id);
+                    // Process matches
+                    let seeds = Seeds {
+                        seed0: config.seed0,
+                        seed1: config.seed1,
+                    };
+                    for i in 0..num_matches {
+                        let b = matches_b_data[i];
+                        let a = matches_a_data[i];
+                        let index = matches_index_data[i];
+                        let prefix_id = matches_prefix_id_data[i];
+                        let path = config.template.path(&seeds, b, a, index);
+                        event_sender.potential_match(path, prefix_id);
+                    }
                 }
             }
 
             // Update counters
-            generated_since_last_report += GPU_WORK_SIZE;
-            global_generated.fetch_add(GPU_WORK_SIZE, Ordering::Relaxed);
+            generated_since_last_report += batch.size;
+            global_generated.fetch_add(batch.size, Ordering::Relaxed);
+            processed_counter = batch.start_counter + batch.size;
 
             // Report progress
             if last_report.elapsed() >= REPORT_INTERVAL {
                 event_sender.progress(generated_since_last_report);
+                event_sender.processed(processed_counter);
                 generated_since_last_report = 0;
                 last_report = Instant::now();
             }
@@ -430,11 +525,7 @@ impl GpuWorkbench {
         // (its send fails), then join it to avoid a detached thread.
         drop(batch_rx);
         let _ = producer.join();
-
-        // Send stopped event with final stats
-        let total_generated = global_generated.load(Ordering::Relaxed);
-        let elapsed = start_time.elapsed();
-        event_sender.stopped(total_generated, elapsed);
+        event_sender.processed(processed_counter);
     }
 
     fn init_opencl(
@@ -498,22 +589,13 @@ impl GpuWorkbench {
         result
     }
 
-    fn prepare_gpu_ranges(
+    fn prepare_gpu_suffixes(
         prefixes: &[crate::prefix::Prefix],
-    ) -> Vec<crate::opencl::gpu_cache::Hash160RangeGpu> {
-        let mut gpu_ranges = Vec::new();
-
-        for (prefix_id, prefix) in prefixes.iter().enumerate() {
-            for range in &prefix.ranges {
-                gpu_ranges.push(crate::opencl::gpu_cache::Hash160RangeGpu {
-                    low: range.low,
-                    high: range.high,
-                    prefix_id: prefix_id as u8,
-                });
-            }
-        }
-
-        gpu_ranges
+    ) -> Vec<crate::opencl::gpu_cache::SuffixGpu> {
+        prefixes
+            .iter()
+            .map(|prefix| crate::opencl::gpu_cache::SuffixGpu::from(prefix.suffix.as_ref()))
+            .collect()
     }
 }
 
@@ -533,6 +615,11 @@ impl Workbench for GpuWorkbench {
         // No-op: work is already done in start()
     }
 
+    fn is_done(&self) -> bool {
+        // start() returns once the producer ran out of batches, or on a stop
+        true
+    }
+
     fn total_generated(&self) -> u64 {
         self.global_generated.load(Ordering::Relaxed)
     }
@@ -1,71 +1,81 @@
+use crate::key_version;
+use crate::network::Network;
 use crate::prefix::{AddressType, Prefix};
 use bitcoin::bip32::{ChildNumber, DerivationPath, Xpub};
 use bitcoin::secp256k1::{self, Secp256k1};
-use bitcoin::{Address, CompressedPublicKey, Network, NetworkKind, PublicKey};
+use bitcoin::{Address, CompressedPublicKey, PublicKey};
 
 pub struct GroundTruthValidator {
     xpub: Xpub,
+    network: bitcoin::Network,
     secp: Secp256k1<secp256k1::All>,
 }
 
 //Bitcoin specialized library for validating addresses derived from xpubs with my own implementation
 impl GroundTruthValidator {
-    pub fn new(xpub_str: &str) -> Result<Self, String> {
-        let xpub = xpub_str
+    /// Also takes SLIP-132 keys (ypub, zpub, ...), read as the plain xpub
+    pub fn new(xpub_str: &str, network: Network) -> Result<Self, String> {
+        let xpub = key_version::to_xpub(xpub_str)?
             .parse::<Xpub>()
             .map_err(|e| format!("Failed to parse xpub: {}", e))?;
 
+        let bitcoin_network = bitcoin::Network::from(network);
+        if xpub.network != bitcoin_network.into() {
+            return Err(format!("The xpub is not a {} key", network.name()));
+        }
+
         Ok(Self {
             xpub,
+            network: bitcoin_network,
             secp: Secp256k1::new(),
         })
     }
 
     #[cfg(test)]
-    pub fn validate_address(&self, prefix: &Prefix, path: &[u32; 6]) -> Result<bool, String> {
+    pub fn validate_address(&self, prefix: &Prefix, path: &[u32]) -> Result<bool, String> {
         let derived_key = self.derive_key(path)?;
 
-        let address = match prefix.address_type {
-            AddressType::P2PKH => self.pubkey_to_p2pkh_address(&derived_key)?,
-            AddressType::P2WPKH => self.pubkey_to_p2wpkh_address(&derived_key)?,
-        };
+        let address = self.encode_address(&derived_key, &prefix.address_type)?;
 
-        Ok(address.starts_with(prefix.as_str()))
+        Ok(prefix.matches_address(&address))
     }
 
     #[cfg(test)]
-    pub fn get_address(
-        &self,
-        path: &[u32; 6],
-        address_type: AddressType,
-    ) -> Result<String, String> {
+    pub fn get_address(&self, path: &[u32], address_type: AddressType) -> Result<String, String> {
         let derived_key = self.derive_key(path)?;
-        match address_type {
-            AddressType::P2PKH => self.pubkey_to_p2pkh_address(&derived_key),
-            AddressType::P2WPKH => self.pubkey_to_p2wpkh_address(&derived_key),
-        }
+        self.encode_address(&derived_key, &address_type)
     }
 
     pub fn validate_and_get_address(
         &self,
         prefix: &Prefix,
-        path: &[u32; 6],
+        path: &[u32],
     ) -> Result<Option<String>, String> {
         let derived_key = self.derive_key(path)?;
 
-        let address = match prefix.address_type {
-            AddressType::P2PKH => self.pubkey_to_p2pkh_address(&derived_key)?,
-            AddressType::P2WPKH => self.pubkey_to_p2wpkh_address(&derived_key)?,
-        };
+        let address = self.encode_address(&derived_key, &prefix.address_type)?;
 
-        if address.starts_with(prefix.as_str()) {
+        if prefix.matches_address(&address) {
             Ok(Some(address))
         } else {
             Ok(None)
         }
     }
 
-    fn derive_key(&self, path: &[u32; 6]) -> Result<bitcoin::secp256k1::PublicKey, String> {
+    fn encode_address(
+        &self,
+        pubkey: &bitcoin::secp256k1::PublicKey,
+        address_type: &AddressType,
+    ) -> Result<String, String> {
+        match address_type {
+            AddressType::P2PKH => self.pubkey_to_p2pkh_address(pubkey),
+            AddressType::P2WPKH => self.pubkey_to_p2wpkh_address(pubkey),
+            AddressType::P2SHP2WPKH => self.pubkey_to_p2sh_p2wpkh_address(pubkey),
+            AddressType::P2TR => self.pubkey_to_p2tr_address(pubkey),
+        }
+    }
+
+    fn derive_key(&self, path: &[u32]) -> Result<bitcoin::secp256k1::PublicKey, String> {
         let child_numbers: Vec<ChildNumber> = path
             .iter()
             .map(|&index| ChildNumber::from_normal_idx(index).unwrap())
@@ -87,7 +97,7 @@ impl GroundTruthValidator {
     ) -> Result<String, String> {
         let public_key = PublicKey::new(*pubkey);
 
-        let address = Address::p2pkh(public_key, NetworkKind::Main);
+        let address = Address::p2pkh(public_key, self.network);
 
         Ok(address.to_string())
     }
@@ -98,7 +108,30 @@ impl GroundTruthValidator {
     ) -> Result<String, String> {
         let compressed_pubkey = CompressedPublicKey(*pubkey);
 
-        let address = Address::p2wpkh(&compressed_pubkey, Network::Bitcoin);
+        let address = Address::p2wpkh(&compressed_pubkey, self.network);
+
+        Ok(address.to_string())
+    }
+
+    fn pubkey_to_p2sh_p2wpkh_address(
+        &self,
+        pubkey: &bitcoin::secp256k1::PublicKey,
+    ) -> Result<String, String> {
+        let compressed_pubkey = CompressedPublicKey(*pubkey);
+
+        let address = Address::p2shwpkh(&compressed_pubkey, self.network);
+
+        Ok(address.to_string())
+    }
+
+    fn pubkey_to_p2tr_address(
+        &self,
+        pubkey: &bitcoin::secp256k1::PublicKey,
+    ) -> Result<String, String> {
+        // BIP86: key path only, no script tree
+        let (internal_key, _) = pubkey.x_only_public_key();
+
+        let address = Address::p2tr(&self.secp, internal_key, None, self.network);
 
         Ok(address.to_string())
     }
@@ -111,20 +144,20 @@ mod tests {
 
     #[test]
     fn test_ground_truth_validator_creation() {
-        let validator = GroundTruthValidator::new(TEST_XPUB);
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet);
         assert!(validator.is_ok());
     }
 
     #[test]
     fn test_ground_truth_validator_invalid_xpub() {
         let invalid_xpub = "invalid";
-        let validator = GroundTruthValidator::new(invalid_xpub);
+        let validator = GroundTruthValidator::new(invalid_xpub, Network::Mainnet);
         assert!(validator.is_err());
     }
 
     #[test]
     fn test_address_path_1000_2000_0_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "16EhLAUerc8rnmdHvBh1ABjEsddTom3FyZ");
@@ -132,7 +165,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_0_0_0_1() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 1];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "12x9m2JaDDWZ2Pf7t97hVjymA1uHRqEd7C");
@@ -140,7 +173,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_0_0_0_100() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 100];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "1K1g6s5LHneq2Km9rs8fGfGc2xpfsVRQ82");
@@ -148,7 +181,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_0_1_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 1, 0, 0];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "14hMRf1rnTgwwdEcPYUJMq5PYWh2owCo4x");
@@ -156,7 +189,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_1_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 1, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "1J57PqrPQSKP85Gd8eYRSwHS65FtorCZwB");
@@ -164,7 +197,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_0_0_0_9999() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 9999];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "1ND9xQjQWC7U2xmhapTWFSEsfsDozqkp4z");
@@ -172,7 +205,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_0_100_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 100, 0, 0];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "17zbeS1wPdtncwSZCtZRptPz9MRY7ZGt9H");
@@ -180,7 +213,7 @@ mod tests {
 
     #[test]
     fn test_address_path_1000_2000_0_1000_0_50() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 1000, 0, 50];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "17DojH5JeQtfFbyG4yuiCmuwQrhdR8UfN3");
@@ -188,7 +221,7 @@ mod tests {
 
     #[test]
     fn test_address_path_5000_6000_0_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [5000, 6000, 0, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "1LAVfqDqtFfjUSQUhZsE7TxWrQpgHRsGVF");
@@ -196,7 +229,7 @@ mod tests {
 
     #[test]
     fn test_address_path_9999_9999_0_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [9999, 9999, 0, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2PKH).unwrap();
         assert_eq!(address, "12Wq6aUM2jiJQWV3gSCGogWuAyYZR2otoH");
@@ -204,22 +237,22 @@ mod tests {
 
     #[test]
     fn test_validate_address_prefix_matching() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
 
         // Test path [1000, 2000, 0, 0, 0, 0] -> "16EhLAUerc8rnmdHvBh1ABjEsddTom3FyZ"
         let path = [1000, 2000, 0, 0, 0, 0];
 
         // Should match "1", "16", "16E", etc.
-        let prefix1 = Prefix::new("1").unwrap();
-        let prefix16 = Prefix::new("16").unwrap();
-        let prefix16e = Prefix::new("16E").unwrap();
+        let prefix1 = Prefix::new("1", Network::Mainnet).unwrap();
+        let prefix16 = Prefix::new("16", Network::Mainnet).unwrap();
+        let prefix16e = Prefix::new("16E", Network::Mainnet).unwrap();
         assert!(validator.validate_address(&prefix1, &path).unwrap());
         assert!(validator.validate_address(&prefix16, &path).unwrap());
         assert!(validator.validate_address(&prefix16e, &path).unwrap());
 
         // Should NOT match other prefixes
-        let prefix17 = Prefix::new("17").unwrap();
-        let prefix12 = Prefix::new("12").unwrap();
+        let prefix17 = Prefix::new("17", Network::Mainnet).unwrap();
+        let prefix12 = Prefix::new("12", Network::Mainnet).unwrap();
         assert!(!validator.validate_address(&prefix17, &path).unwrap());
         assert!(!validator.validate_address(&prefix12, &path).unwrap());
     }
@@ -227,7 +260,7 @@ mod tests {
     // P2WPKH address generation tests
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1q89hm8k39a388dju9fysuk6dsm6eerzj860sxx4");
@@ -235,7 +268,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_0_0_1() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 1];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qz4n9pwdcwzc9kczc7e6vk3cpxr2mfn8r9m6jzu");
@@ -243,7 +276,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_0_0_100() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 100];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qckfwwupesemaulllr9jrmllhxyymku2tajj6g7");
@@ -251,7 +284,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_1_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 1, 0, 0];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1q9z9ppkny85mndyc968hcudsmgjzqlaern0mq2d");
@@ -259,7 +292,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_1_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 1, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qhdqj8dyvwe3t2lt2qha073cgkyuseretjv6sjx");
@@ -267,7 +300,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_0_0_9999() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 9999];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qazn3hj8rscl7lxteumr65vjwnezr670j42l93a");
@@ -275,7 +308,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_100_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 100, 0, 0];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qfj6k9y9utp96yh4wt9ggqnhs7wxhukc4pr3304");
@@ -283,7 +316,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_1000_2000_0_1000_0_50() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 1000, 0, 50];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qgs7vp65du9cpeu5p242kuntaakuqsptghdzv02");
@@ -291,7 +324,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_5000_6000_0_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [5000, 6000, 0, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1q6gmppn3l84jlfetqvpe8crnf9jzdt5tmekrspf");
@@ -299,7 +332,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_address_path_9999_9999_0_0_0_0() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [9999, 9999, 0, 0, 0, 0];
         let address = validator.get_address(&path, AddressType::P2WPKH).unwrap();
         assert_eq!(address, "bc1qzzw9vkhkn5p3da5vpfat7c0e70wvxvrx79ehqw");
@@ -308,12 +341,12 @@ mod tests {
     // Test validate_and_get_address with P2WPKH
     #[test]
     fn test_validate_and_get_address_p2wpkh_matching() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 0];
         // bc1q89hm8k39a388dju9fysuk6dsm6eerzj860sxx4
 
-        let prefix_bc1q = Prefix::new("bc1q").unwrap();
-        let prefix_bc1q89 = Prefix::new("bc1q89").unwrap();
+        let prefix_bc1q = Prefix::new("bc1q", Network::Mainnet).unwrap();
+        let prefix_bc1q89 = Prefix::new("bc1q89", Network::Mainnet).unwrap();
 
         // Should match
         let result = validator
@@ -335,17 +368,130 @@ mod tests {
         );
 
         // Should NOT match
-        let prefix_bc1qzz = Prefix::new("bc1qzz").unwrap();
+        let prefix_bc1qzz = Prefix::new("bc1qzz", Network::Mainnet).unwrap();
         let result3 = validator
             .validate_and_get_address(&prefix_bc1qzz, &path)
             .unwrap();
         assert!(result3.is_none());
     }
 
+    #[test]
+    fn test_validate_address_suffix_matching() {
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
+
+        // Path [1000, 2000, 0, 0, 0, 0] -> "16EhLAUerc8rnmdHvBh1ABjEsddTom3FyZ"
+        // and "bc1q89hm8k39a388dju9fysuk6dsm6eerzj860sxx4"
+        let path = [1000, 2000, 0, 0, 0, 0];
+
+        for pattern in ["1...FyZ", "16E...3FyZ", "bc1q...sxx4", "bc1q89...j860sxx4"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            assert!(
+                validator.validate_address(&prefix, &path).unwrap(),
+                "{} should match",
+                pattern
+            );
+        }
+
+        for pattern in ["1...FyY", "17...FyZ", "bc1q...sxx5", "bc1qzz...sxx4"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            assert!(
+                !validator.validate_address(&prefix, &path).unwrap(),
+                "{} should not match",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_p2tr_address_matches_deriver_output_key() {
+        use crate::extended_public_key::ExtendedPubKey;
+        use crate::extended_public_key_deriver::{ExtendedPublicKeyDeriver, KeyDeriver};
+
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
+        let mut deriver =
+            ExtendedPublicKeyDeriver::new(&ExtendedPubKey::from_str(TEST_XPUB).unwrap());
+
+        for path in [[1000, 2000, 0, 0, 0, 0], [5000, 6000, 0, 7, 0, 42]] {
+            let address = validator.get_address(&path, AddressType::P2TR).unwrap();
+            assert!(address.starts_with("bc1p"));
+
+            // The deriver's output key must encode to the same address
+            let output_key = deriver.get_taproot_output_key(&path).unwrap();
+            let program =
+                bitcoin::WitnessProgram::new(bitcoin::WitnessVersion::V1, &output_key).unwrap();
+            let expected = Address::from_witness_program(program, bitcoin::KnownHrp::Mainnet);
+            assert_eq!(address, expected.to_string());
+
+            let prefix = Prefix::new(&address[..7], Network::Mainnet).unwrap();
+            assert!(prefix.matches_output_key(&output_key));
+            assert_eq!(
+                validator.validate_and_get_address(&prefix, &path).unwrap(),
+                Some(address)
+            );
+        }
+    }
+
     // Test that P2PKH and P2WPKH addresses are different for same path
+    const TEST_TPUB: &str = "tpubDCthgcrNduJHHyErmegKUF38GTioPpkwG7yiANbqpWmxuSPGVi1jj54VVTQ3TzPT77eGkaEk9XGkSkjpBHjM8gm2V4gQrBhnBAihiFHJuRe";
+
+    #[test]
+    fn test_test_network_addresses() {
+        let path = [1000, 2000, 0, 0, 0, 0];
+
+        let validator = GroundTruthValidator::new(TEST_TPUB, Network::Testnet).unwrap();
+        let p2pkh = validator.get_address(&path, AddressType::P2PKH).unwrap();
+        assert!(p2pkh.starts_with(['m', 'n']));
+        let p2sh = validator
+            .get_address(&path, AddressType::P2SHP2WPKH)
+            .unwrap();
+        assert!(p2sh.starts_with('2'));
+        let p2wpkh = validator.get_address(&path, AddressType::P2WPKH).unwrap();
+        assert!(p2wpkh.starts_with("tb1q"));
+        let p2tr = validator.get_address(&path, AddressType::P2TR).unwrap();
+        assert!(p2tr.starts_with("tb1p"));
+
+        let prefix = Prefix::new(&p2wpkh[..7], Network::Testnet).unwrap();
+        assert_eq!(
+            validator.validate_and_get_address(&prefix, &path).unwrap(),
+            Some(p2wpkh)
+        );
+
+        let validator = GroundTruthValidator::new(TEST_TPUB, Network::Regtest).unwrap();
+        let p2wpkh = validator.get_address(&path, AddressType::P2WPKH).unwrap();
+        assert!(p2wpkh.starts_with("bcrt1q"));
+    }
+
+    #[test]
+    fn test_slip132_keys_derive_like_xpub() {
+        let zpub = "zpub6rFq6u6y1V6FaudwXoagq7Gtm3brGbcYbneJGjuFQGPWVTjY8qMJckD7S3B8H9mrQbkwtm5dWxNUG5zn1QW5HAMS1Tc289Gnr4xVFyENzte";
+        let path = [1000, 2000, 0, 0, 0, 0];
+
+        let xpub_validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
+        let zpub_validator = GroundTruthValidator::new(zpub, Network::Mainnet).unwrap();
+        assert_eq!(
+            zpub_validator
+                .get_address(&path, AddressType::P2WPKH)
+                .unwrap(),
+            xpub_validator
+                .get_address(&path, AddressType::P2WPKH)
+                .unwrap()
+        );
+
+        let vpub = "vpub5YrYZ3grdGFsKafmek7yr9WkbQLFXCGstGLyF2kvibGK3x66vAqXLW5PD1Q5wg8NVvske3Xkh6jbz125M345emwXgnYFzqQa3m11B5ypuor";
+        assert!(GroundTruthValidator::new(vpub, Network::Testnet).is_ok());
+        assert!(GroundTruthValidator::new(vpub, Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_network_must_match_xpub() {
+        let error = GroundTruthValidator::new(TEST_TPUB, Network::Mainnet).err();
+        assert!(error.is_some_and(|error| error.contains("not a mainnet key")));
+        assert!(GroundTruthValidator::new(TEST_XPUB, Network::Signet).is_err());
+    }
+
     #[test]
     fn test_p2pkh_vs_p2wpkh_different_addresses() {
-        let validator = GroundTruthValidator::new(TEST_XPUB).unwrap();
+        let validator = GroundTruthValidator::new(TEST_XPUB, Network::Mainnet).unwrap();
         let path = [1000, 2000, 0, 0, 0, 0];
 
         let p2pkh = validator.get_address(&path, AddressType::P2PKH).unwrap();
@@ -0,0 +1,498 @@
+use crate::budget::EndReason;
+use crate::constants::DEFAULT_MAX_DEPTH;
+use crate::descriptor::KeyOrigin;
+use crate::difficulty::{Difficulty, LISTED_PREFIXES};
+use crate::display_backend::{format_derivation_path, BenchStats, UiBackend};
+use crate::gap_estimate::{format_duration, GapEstimate};
+use crate::longest_match::Leaderboard;
+use crate::path_template::{KeyPath, PathTemplate};
+use crate::prefix::Prefix;
+use crate::seeds::Seeds;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::time::Duration;
+
+/// Headless backend: one JSON object per line on stdout for every event,
+/// short human-readable logs on stderr. Needs no terminal, so it works in
+/// cron jobs, CI and pipelines.
+pub struct JsonBackend {
+    out: Box<dyn Write + Send>,
+    prefixes: Vec<Prefix>,
+    key_origin: Option<KeyOrigin>,
+    template: PathTemplate,
+    difficulty: Difficulty,
+}
+
+impl JsonBackend {
+    pub fn new() -> Self {
+        Self::with_writer(Box::new(io::stdout()))
+    }
+
+    fn with_writer(out: Box<dyn Write + Send>) -> Self {
+        JsonBackend {
+            out,
+            prefixes: Vec::new(),
+            key_origin: None,
+            template: PathTemplate::default(),
+            difficulty: Difficulty::new(&[]),
+        }
+    }
+
+    /// Writes `{"event":"<event>",<fields>}`. Flushes every line so readers
+    /// of a pipe see the events as they happen.
+    fn emit(&mut self, event: &str, fields: &[(&str, String)]) {
+        let mut line = format!("{{\"event\":{}", json_string(event));
+        for (key, value) in fields {
+            line.push_str(&format!(",{}:{}", json_string(key), value));
+        }
+        line.push('}');
+
+        // A closed stdout must not stop the search, the results are also on stderr
+        let _ = writeln!(self.out, "{}", line);
+        let _ = self.out.flush();
+    }
+}
+
+impl Default for JsonBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UiBackend for JsonBackend {
+    fn start(
+        &mut self,
+        prefixes: &[Prefix],
+        key_origin: Option<&KeyOrigin>,
+        seeds: Seeds,
+        template: &PathTemplate,
+        max_depth: u32,
+        cpu_threads: u32,
+    ) {
+        self.prefixes = prefixes.to_vec();
+        self.key_origin = key_origin.cloned();
+        self.template = *template;
+        self.difficulty = Difficulty::new(prefixes);
+
+        let prefix_strs: Vec<&str> = prefixes.iter().map(|prefix| prefix.as_str()).collect();
+        let mut listed = prefix_strs[..prefix_strs.len().min(LISTED_PREFIXES)].join(", ");
+        if prefix_strs.len() > LISTED_PREFIXES {
+            listed.push_str(&format!(
+                " and {} more",
+                prefix_strs.len() - LISTED_PREFIXES
+            ));
+        }
+        eprintln!(
+            "Searching for {} (seeds {},{}, path {}, max depth {}, {} CPU threads)",
+            listed, seeds.seed0, seeds.seed1, template, max_depth, cpu_threads
+        );
+        for line in self.difficulty.summary_lines(prefixes, LISTED_PREFIXES) {
+            eprintln!("{}", line);
+        }
+        self.emit(
+            "start",
+            &[
+                (
+                    "prefixes",
+                    json_array(prefix_strs.iter().map(|prefix| json_string(prefix))),
+                ),
+                (
+                    "probabilities",
+                    json_array(
+                        self.difficulty
+                            .probabilities
+                            .iter()
+                            .map(|probability| format!("{:e}", probability)),
+                    ),
+                ),
+                (
+                    "expected_attempts",
+                    json_number(self.difficulty.expected_attempts()),
+                ),
+                ("seed0", seeds.seed0.to_string()),
+                ("seed1", seeds.seed1.to_string()),
+                ("path_template", json_string(&template.to_string())),
+                ("max_depth", max_depth.to_string()),
+                ("cpu_threads", cpu_threads.to_string()),
+            ],
+        );
+    }
+
+    fn workbench_starting(&mut self, bench_id: &str, counter_range: &Range<u64>) {
+        self.emit(
+            "workbench_starting",
+            &[
+                ("bench_id", json_string(bench_id)),
+                ("counter_start", counter_range.start.to_string()),
+                ("counter_end", counter_range.end.to_string()),
+            ],
+        );
+    }
+
+    fn workbench_started(&mut self, bench_id: &str) {
+        eprintln!("{} started", bench_id);
+        self.emit("workbench_started", &[("bench_id", json_string(bench_id))]);
+    }
+
+    fn log_status(&mut self, bench_stats: &HashMap<String, BenchStats>) {
+        let mut bench_ids: Vec<&String> = bench_stats.keys().collect();
+        bench_ids.sort();
+
+        let total_rate: u64 = bench_stats
+            .values()
+            .map(|stats| stats.addresses_per_sec())
+            .sum();
+        let total_generated: u64 = bench_stats
+            .values()
+            .map(|stats| stats.total_generated)
+            .sum();
+        let benches = json_array(bench_ids.into_iter().map(|bench_id| {
+            let stats = &bench_stats[bench_id];
+            format!(
+                "{{\"bench_id\":{},\"addresses_per_sec\":{},\"total_generated\":{},\"runtime_secs\":{}}}",
+                json_string(bench_id),
+                stats.addresses_per_sec(),
+                stats.total_generated,
+                stats.runtime_secs()
+            )
+        }));
+
+        eprintln!("{} addr/s, {} generated", total_rate, total_generated);
+        self.emit(
+            "status",
+            &[
+                ("addresses_per_sec", total_rate.to_string()),
+                ("total_generated", total_generated.to_string()),
+                (
+                    "eta_secs",
+                    self.difficulty
+                        .eta_secs(total_rate as f64)
+                        .map_or("null".to_string(), json_number),
+                ),
+                ("benches", benches),
+            ],
+        );
+    }
+
+    fn log_gap_estimate(&mut self, estimate: &GapEstimate) {
+        eprintln!(
+            "Hit below index {} expected in {} (with max depth {}: {})",
+            estimate.gap_limit,
+            format_duration(estimate.bounded_secs),
+            DEFAULT_MAX_DEPTH,
+            format_duration(estimate.default_layout_secs)
+        );
+        self.emit(
+            "gap_estimate",
+            &[
+                ("gap_limit", estimate.gap_limit.to_string()),
+                ("expected_secs", json_number(estimate.bounded_secs)),
+                ("default_max_depth", DEFAULT_MAX_DEPTH.to_string()),
+                (
+                    "default_layout_expected_secs",
+                    json_number(estimate.default_layout_secs),
+                ),
+            ],
+        );
+    }
+
+    fn log_found_address(
+        &mut self,
+        bench_id: &str,
+        address: &str,
+        path: &KeyPath,
+        prefix_id: u32,
+        descriptor: &str,
+    ) {
+        let prefix = &self.prefixes[prefix_id as usize];
+        let prefix_str = prefix.as_str().to_string();
+        let address_type = prefix.address_type.name();
+        let derivation_path =
+            format_derivation_path(path, &self.template, self.key_origin.as_ref());
+
+        eprintln!(
+            "Found {} ({}) at {}, index {}",
+            address,
+            prefix_str,
+            derivation_path,
+            path.index()
+        );
+        self.emit(
+            "found",
+            &[
+                ("bench_id", json_string(bench_id)),
+                ("address", json_string(address)),
+                ("type", json_string(address_type)),
+                ("prefix", json_string(&prefix_str)),
+                ("derivation_path", json_string(&derivation_path)),
+                ("path", json_array(path.iter().map(|i| i.to_string()))),
+                ("index", path.index().to_string()),
+                ("descriptor", json_string(descriptor)),
+            ],
+        );
+    }
+
+    fn log_leaderboard(&mut self, leaderboard: &Leaderboard) {
+        if let Some(best) = leaderboard.entries().first() {
+            eprintln!(
+                "Longest match: {} ({} of {} characters), candidates now need {}",
+                best.address,
+                best.matched_chars,
+                leaderboard.target().len(),
+                leaderboard.required_chars()
+            );
+        }
+        let entries = json_array(leaderboard.entries().iter().map(|entry| {
+            format!(
+                "{{\"address\":{},\"matched_chars\":{},\"bench_id\":{},\"path\":{}}}",
+                json_string(&entry.address),
+                entry.matched_chars,
+                json_string(&entry.bench_id),
+                json_array(entry.path.iter().map(|i| i.to_string()))
+            )
+        }));
+        self.emit(
+            "leaderboard",
+            &[
+                ("target", json_string(leaderboard.target())),
+                ("required_chars", leaderboard.required_chars().to_string()),
+                ("entries", entries),
+            ],
+        );
+    }
+
+    fn log_derivation_error(&mut self) {
+        eprintln!("Failed to derive a matched address");
+        self.emit("derivation_error", &[]);
+    }
+
+    fn log_false_positive(&mut self, bench_id: &str, path: &KeyPath) {
+        self.emit(
+            "false_positive",
+            &[
+                ("bench_id", json_string(bench_id)),
+                ("path", json_array(path.iter().map(|i| i.to_string()))),
+            ],
+        );
+    }
+
+    fn log_error(&mut self, message: &str) {
+        eprintln!("Error: {}", message);
+        self.emit("error", &[("message", json_string(message))]);
+    }
+
+    fn stop_requested(&mut self) {
+        eprintln!("Stopping...");
+        self.emit("stop_requested", &[]);
+    }
+
+    fn workbench_stopping(&mut self, bench_id: &str) {
+        self.emit("workbench_stopping", &[("bench_id", json_string(bench_id))]);
+    }
+
+    fn workbench_stopped(&mut self, bench_id: &str, total_generated: u64, elapsed: Duration) {
+        eprintln!(
+            "{} stopped: {} generated in {}s",
+            bench_id,
+            total_generated,
+            elapsed.as_secs()
+        );
+        self.emit(
+            "workbench_stopped",
+            &[
+                ("bench_id", json_string(bench_id)),
+                ("total_generated", total_generated.to_string()),
+                ("elapsed_secs", elapsed.as_secs_f64().to_string()),
+            ],
+        );
+    }
+
+    fn final_status(&mut self, reason: EndReason) {
+        eprintln!("Search ended: {}", reason);
+        self.emit("finished", &[("reason", json_string(reason.name()))]);
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// JSON has no infinity, an endless search is `null`
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:.0}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_array(values: impl Iterator<Item = String>) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects what the backend writes
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        assert_eq!(json_string("1abc"), "\"1abc\"");
+        assert_eq!(json_string("a\"b\\c\n\t"), "\"a\\\"b\\\\c\\n\\u0009\"");
+    }
+
+    #[test]
+    fn test_emits_one_line_per_event() {
+        let buffer = SharedBuffer::default();
+        let mut backend = JsonBackend::with_writer(Box::new(buffer.clone()));
+        let prefixes = vec![
+            Prefix::new("1A", Network::Mainnet).unwrap(),
+            Prefix::new("bc1qxy", Network::Mainnet).unwrap(),
+        ];
+
+        backend.start(
+            &prefixes,
+            None,
+            Seeds { seed0: 1, seed1: 2 },
+            &PathTemplate::default(),
+            1000,
+            4,
+        );
+        backend.workbench_starting("CPU", &(0..500));
+        backend.workbench_started("CPU");
+        backend.log_found_address(
+            "CPU",
+            "bc1qxyexample",
+            &KeyPath::from_slice(&[1, 2, 3, 4, 0, 5]),
+            1,
+            "wpkh(xpub/1/2/3/4/0/*)#checksum",
+        );
+        backend.log_false_positive("CPU", &KeyPath::from_slice(&[1, 2, 3, 4, 0, 6]));
+        backend.workbench_stopped("CPU", 100, Duration::from_millis(1500));
+
+        let lines = buffer.lines();
+        assert_eq!(
+            lines,
+            vec![
+                "{\"event\":\"start\",\"prefixes\":[\"1A\",\"bc1qxy\"],\"probabilities\":[4.358736794383069e-2,9.765625e-4],\"expected_attempts\":22,\"seed0\":1,\"seed1\":2,\"path_template\":\"s0/s1/b/a/0/i\",\"max_depth\":1000,\"cpu_threads\":4}",
+                "{\"event\":\"workbench_starting\",\"bench_id\":\"CPU\",\"counter_start\":0,\"counter_end\":500}",
+                "{\"event\":\"workbench_started\",\"bench_id\":\"CPU\"}",
+                "{\"event\":\"found\",\"bench_id\":\"CPU\",\"address\":\"bc1qxyexample\",\"type\":\"P2WPKH\",\"prefix\":\"bc1qxy\",\"derivation_path\":\"xpub'/1/2/3/4\",\"path\":[1,2,3,4,0,5],\"index\":5,\"descriptor\":\"wpkh(xpub/1/2/3/4/0/*)#checksum\"}",
+                "{\"event\":\"false_positive\",\"bench_id\":\"CPU\",\"path\":[1,2,3,4,0,6]}",
+                "{\"event\":\"workbench_stopped\",\"bench_id\":\"CPU\",\"total_generated\":100,\"elapsed_secs\":1.5}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gap_estimate() {
+        let buffer = SharedBuffer::default();
+        let mut backend = JsonBackend::with_writer(Box::new(buffer.clone()));
+
+        backend.log_gap_estimate(&GapEstimate {
+            gap_limit: 20,
+            bounded_secs: 125.4,
+            default_layout_secs: f64::INFINITY,
+        });
+
+        assert_eq!(
+            buffer.lines(),
+            vec!["{\"event\":\"gap_estimate\",\"gap_limit\":20,\"expected_secs\":125,\"default_max_depth\":1000,\"default_layout_expected_secs\":null}"]
+        );
+    }
+
+    #[test]
+    fn test_finished_names_the_end_reason() {
+        let buffer = SharedBuffer::default();
+        let mut backend = JsonBackend::with_writer(Box::new(buffer.clone()));
+
+        backend.final_status(EndReason::KeyBudget(1000));
+
+        assert_eq!(
+            buffer.lines(),
+            vec!["{\"event\":\"finished\",\"reason\":\"key_budget\"}"]
+        );
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        use crate::longest_match::LeaderboardEntry;
+
+        let buffer = SharedBuffer::default();
+        let mut backend = JsonBackend::with_writer(Box::new(buffer.clone()));
+        let mut leaderboard = Leaderboard::new("1BitcoinEater", 4);
+        leaderboard.offer(LeaderboardEntry {
+            address: "1Bitcoxyz".to_string(),
+            matched_chars: 6,
+            path: KeyPath::from_slice(&[1, 2, 0, 7]),
+            bench_id: "CPU".to_string(),
+        });
+
+        backend.log_leaderboard(&leaderboard);
+
+        assert_eq!(
+            buffer.lines(),
+            vec!["{\"event\":\"leaderboard\",\"target\":\"1BitcoinEater\",\"required_chars\":4,\"entries\":[{\"address\":\"1Bitcoxyz\",\"matched_chars\":6,\"bench_id\":\"CPU\",\"path\":[1,2,0,7]}]}"]
+        );
+    }
+
+    #[test]
+    fn test_status_lists_benches_in_order() {
+        let buffer = SharedBuffer::default();
+        let mut backend = JsonBackend::with_writer(Box::new(buffer.clone()));
+        let mut bench_stats = HashMap::new();
+        for bench_id in ["GPU", "CPU"] {
+            let mut stats = BenchStats::new(std::time::Instant::now());
+            stats.total_generated = 10;
+            bench_stats.insert(bench_id.to_string(), stats);
+        }
+
+        backend.log_status(&bench_stats);
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with(
+            "{\"event\":\"status\",\"addresses_per_sec\":0,\"total_generated\":20,\"eta_secs\":null,\"benches\":[{\"bench_id\":\"CPU\""
+        ));
+        assert!(lines[0].contains("{\"bench_id\":\"GPU\""));
+    }
+}
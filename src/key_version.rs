@@ -0,0 +1,260 @@
+use crate::prefix::AddressType;
+use sha2::{Digest, Sha256};
+
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// Scripts a wallet builds from a key, told by its SLIP-132 version
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptType {
+    /// xpub/tpub: BIP44 P2PKH, but wallets also export them for every other
+    /// single-key script (BIP86 Taproot, descriptors)
+    Any,
+    P2SHP2WPKH,
+    P2WPKH,
+    /// Multisig keys: their addresses also need the cosigners' keys
+    MultisigP2SHP2WSH,
+    MultisigP2WSH,
+}
+
+impl ScriptType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptType::Any => "any",
+            ScriptType::P2SHP2WPKH => "P2SH-P2WPKH",
+            ScriptType::P2WPKH => "P2WPKH",
+            ScriptType::MultisigP2SHP2WSH => "multisig P2SH-P2WSH",
+            ScriptType::MultisigP2WSH => "multisig P2WSH",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeyVersion {
+    pub name: &'static str,
+    pub bytes: [u8; 4],
+    pub testnet: bool,
+    pub script_type: ScriptType,
+}
+
+const KEY_VERSIONS: [KeyVersion; 10] = [
+    KeyVersion {
+        name: "xpub",
+        bytes: XPUB_VERSION,
+        testnet: false,
+        script_type: ScriptType::Any,
+    },
+    KeyVersion {
+        name: "ypub",
+        bytes: [0x04, 0x9d, 0x7c, 0xb2],
+        testnet: false,
+        script_type: ScriptType::P2SHP2WPKH,
+    },
+    KeyVersion {
+        name: "zpub",
+        bytes: [0x04, 0xb2, 0x47, 0x46],
+        testnet: false,
+        script_type: ScriptType::P2WPKH,
+    },
+    KeyVersion {
+        name: "Ypub",
+        bytes: [0x02, 0x95, 0xb4, 0x3f],
+        testnet: false,
+        script_type: ScriptType::MultisigP2SHP2WSH,
+    },
+    KeyVersion {
+        name: "Zpub",
+        bytes: [0x02, 0xaa, 0x7e, 0xd3],
+        testnet: false,
+        script_type: ScriptType::MultisigP2WSH,
+    },
+    KeyVersion {
+        name: "tpub",
+        bytes: TPUB_VERSION,
+        testnet: true,
+        script_type: ScriptType::Any,
+    },
+    KeyVersion {
+        name: "upub",
+        bytes: [0x04, 0x4a, 0x52, 0x62],
+        testnet: true,
+        script_type: ScriptType::P2SHP2WPKH,
+    },
+    KeyVersion {
+        name: "vpub",
+        bytes: [0x04, 0x5f, 0x1c, 0xf6],
+        testnet: true,
+        script_type: ScriptType::P2WPKH,
+    },
+    KeyVersion {
+        name: "Upub",
+        bytes: [0x02, 0x42, 0x89, 0xef],
+        testnet: true,
+        script_type: ScriptType::MultisigP2SHP2WSH,
+    },
+    KeyVersion {
+        name: "Vpub",
+        bytes: [0x02, 0x57, 0x54, 0x83],
+        testnet: true,
+        script_type: ScriptType::MultisigP2WSH,
+    },
+];
+
+impl KeyVersion {
+    /// Version of a base58check encoded extended public key
+    pub fn of(key: &str) -> Result<&'static KeyVersion, String> {
+        let payload = decode_check(key)?;
+        KEY_VERSIONS
+            .iter()
+            .find(|version| payload[..4] == version.bytes)
+            .ok_or_else(|| format!("Unknown extended public key version {:02x?}", &payload[..4]))
+    }
+
+    /// Whether the key starts like one of the known versions, without
+    /// decoding it
+    pub fn has_known_name(key: &str) -> bool {
+        KEY_VERSIONS
+            .iter()
+            .any(|version| key.starts_with(version.name))
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        KEY_VERSIONS.iter().map(|version| version.name).collect()
+    }
+
+    /// Whether a wallet holding this key shows addresses of this type
+    pub fn supports(&self, address_type: &AddressType) -> bool {
+        match self.script_type {
+            ScriptType::Any => true,
+            ScriptType::P2SHP2WPKH => *address_type == AddressType::P2SHP2WPKH,
+            ScriptType::P2WPKH => *address_type == AddressType::P2WPKH,
+            ScriptType::MultisigP2SHP2WSH | ScriptType::MultisigP2WSH => false,
+        }
+    }
+
+    pub fn is_multisig(&self) -> bool {
+        matches!(
+            self.script_type,
+            ScriptType::MultisigP2SHP2WSH | ScriptType::MultisigP2WSH
+        )
+    }
+}
+
+/// The same key with the plain xpub/tpub version, the only ones BIP32
+/// libraries parse
+pub fn to_xpub(key: &str) -> Result<String, String> {
+    let version = KeyVersion::of(key)?;
+    let mut payload = decode_check(key)?;
+    payload[..4].copy_from_slice(if version.testnet {
+        &TPUB_VERSION
+    } else {
+        &XPUB_VERSION
+    });
+    Ok(encode_check(&payload))
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(payload));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn decode_check(key: &str) -> Result<Vec<u8>, String> {
+    let mut data = bs58::decode(key)
+        .into_vec()
+        .map_err(|e| format!("Failed to decode base58: {}", e))?;
+
+    if data.len() != 82 {
+        return Err(format!(
+            "Invalid extended public key length: {}",
+            data.len()
+        ));
+    }
+
+    let payload_checksum = data.split_off(78);
+    if payload_checksum != checksum(&data) {
+        return Err("Invalid extended public key checksum".to_string());
+    }
+
+    Ok(data)
+}
+
+fn encode_check(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&checksum(payload));
+    bs58::encode(data).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_XPUB: &str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+    const TEST_YPUB: &str = "ypub6XRZoES3roYmjcSphSo4d2BPb5TQKyd3gg85VM1N2G1dSMvJtBBjzgYyQqDYHF7vzxe99HV54J1vNoPDHi64Uvfq97ubYETJaLtqsQ44fGS";
+    const TEST_ZPUB: &str = "zpub6rFq6u6y1V6FaudwXoagq7Gtm3brGbcYbneJGjuFQGPWVTjY8qMJckD7S3B8H9mrQbkwtm5dWxNUG5zn1QW5HAMS1Tc289Gnr4xVFyENzte";
+    const TEST_CAPITAL_ZPUB: &str = "Zpub739vE8qQaSed1UoKUU3ffBchUqe7UxJ8v4HyC1Ann3Dv7eJSuEjahs53Ek8bqazke4pvmM5kQAkyPFcYJdf2ReU6qviRXYjnjoEMqu843VX";
+    const TEST_TPUB: &str = "tpubDCthgcrNduJHHyErmegKUF38GTioPpkwG7yiANbqpWmxuSPGVi1jj54VVTQ3TzPT77eGkaEk9XGkSkjpBHjM8gm2V4gQrBhnBAihiFHJuRe";
+    const TEST_VPUB: &str = "vpub5YrYZ3grdGFsKafmek7yr9WkbQLFXCGstGLyF2kvibGK3x66vAqXLW5PD1Q5wg8NVvske3Xkh6jbz125M345emwXgnYFzqQa3m11B5ypuor";
+
+    #[test]
+    fn test_of_detects_versions() {
+        for (key, name, script_type) in [
+            (TEST_XPUB, "xpub", ScriptType::Any),
+            (TEST_YPUB, "ypub", ScriptType::P2SHP2WPKH),
+            (TEST_ZPUB, "zpub", ScriptType::P2WPKH),
+            (TEST_CAPITAL_ZPUB, "Zpub", ScriptType::MultisigP2WSH),
+            (TEST_TPUB, "tpub", ScriptType::Any),
+            (TEST_VPUB, "vpub", ScriptType::P2WPKH),
+        ] {
+            let version = KeyVersion::of(key).unwrap();
+            assert_eq!(version.name, name);
+            assert_eq!(version.script_type, script_type);
+            assert_eq!(version.testnet, name == "tpub" || name == "vpub");
+            assert!(KeyVersion::has_known_name(key));
+        }
+    }
+
+    #[test]
+    fn test_to_xpub() {
+        for key in [TEST_XPUB, TEST_YPUB, TEST_ZPUB, TEST_CAPITAL_ZPUB] {
+            assert_eq!(to_xpub(key).unwrap(), TEST_XPUB);
+        }
+        for key in [TEST_TPUB, TEST_VPUB] {
+            assert_eq!(to_xpub(key).unwrap(), TEST_TPUB);
+        }
+    }
+
+    #[test]
+    fn test_supports() {
+        let zpub = KeyVersion::of(TEST_ZPUB).unwrap();
+        assert!(zpub.supports(&AddressType::P2WPKH));
+        assert!(!zpub.supports(&AddressType::P2PKH));
+        assert!(!zpub.supports(&AddressType::P2TR));
+
+        let ypub = KeyVersion::of(TEST_YPUB).unwrap();
+        assert!(ypub.supports(&AddressType::P2SHP2WPKH));
+        assert!(!ypub.supports(&AddressType::P2WPKH));
+
+        let xpub = KeyVersion::of(TEST_XPUB).unwrap();
+        assert!(xpub.supports(&AddressType::P2PKH));
+        assert!(xpub.supports(&AddressType::P2TR));
+
+        let multisig = KeyVersion::of(TEST_CAPITAL_ZPUB).unwrap();
+        assert!(multisig.is_multisig());
+        assert!(!multisig.supports(&AddressType::P2WPKH));
+    }
+
+    #[test]
+    fn test_of_invalid() {
+        // the last character was changed to 'm'
+        let bad_checksum = TEST_XPUB.replace("rNn", "rNm");
+        assert!(KeyVersion::of(&bad_checksum)
+            .unwrap_err()
+            .contains("checksum"));
+
+        let mut payload = decode_check(TEST_XPUB).unwrap();
+        payload[..4].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+        assert!(KeyVersion::of(&encode_check(&payload))
+            .unwrap_err()
+            .contains("Unknown extended public key version"));
+    }
+}
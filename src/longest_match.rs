@@ -0,0 +1,208 @@
+use crate::network::Network;
+use crate::path_template::KeyPath;
+use crate::prefix::Prefix;
+
+/// Candidates kept on the leaderboard
+pub const LEADERBOARD_SIZE: usize = 10;
+/// Without --min-match, candidates start at the first length rarer than
+/// this, so the first minutes do not flood the orchestrator
+const DEFAULT_CANDIDATE_ODDS: f64 = 100_000.0;
+
+/// One prefix per length of the target, from `min_chars` up: prefix id `i`
+/// is the target's first `min_chars + i` characters. Without `min_chars`,
+/// the first length rarer than 1 in 100,000 addresses is used. Returns the
+/// resolved minimum with the prefixes.
+pub fn target_prefixes(
+    target: &str,
+    min_chars: Option<usize>,
+    network: Network,
+) -> Result<(usize, Vec<Prefix>), String> {
+    if target.contains("...") {
+        return Err("The longest-match target cannot have a suffix".to_string());
+    }
+    // Validates the characters: every shorter prefix is then ASCII too
+    Prefix::new(target, network)?;
+
+    let min_chars = match min_chars {
+        Some(0) => return Err("--min-match must be at least 1".to_string()),
+        Some(min_chars) if min_chars > target.len() => {
+            return Err(format!(
+                "--min-match {} is longer than the target '{}'",
+                min_chars, target
+            ))
+        }
+        Some(min_chars) => min_chars,
+        None => (1..=target.len())
+            .find(|&len| {
+                Prefix::new(&target[..len], network)
+                    .is_ok_and(|prefix| prefix.match_probability() * DEFAULT_CANDIDATE_ODDS <= 1.0)
+            })
+            .unwrap_or(target.len()),
+    };
+
+    let prefixes = (min_chars..=target.len())
+        .map(|len| {
+            Prefix::new(&target[..len], network).map_err(|e| {
+                format!(
+                    "--min-match {}: '{}' is not a prefix on its own ({}), ask for more characters",
+                    min_chars,
+                    &target[..len],
+                    e
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((min_chars, prefixes))
+}
+
+/// Number of leading characters `address` shares with `target`
+pub fn matched_chars(address: &str, target: &str) -> usize {
+    address
+        .bytes()
+        .zip(target.bytes())
+        .take_while(|(a, t)| a == t)
+        .count()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeaderboardEntry {
+    pub address: String,
+    pub matched_chars: usize,
+    pub path: KeyPath,
+    pub bench_id: String,
+}
+
+/// The best candidates of a longest-match search, longest first, the
+/// earlier one first on ties. Once full, only a candidate beating the last
+/// entry gets in: the characters asked of the workbenches rise with it.
+#[derive(Clone, Debug)]
+pub struct Leaderboard {
+    target: String,
+    min_chars: usize,
+    entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    pub fn new(target: &str, min_chars: usize) -> Self {
+        Leaderboard {
+            target: target.to_string(),
+            min_chars,
+            entries: Vec::with_capacity(LEADERBOARD_SIZE + 1),
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn entries(&self) -> &[LeaderboardEntry] {
+        &self.entries
+    }
+
+    /// Adds the candidate if it makes the board, false otherwise. A
+    /// workbench can report an address once per length it matches.
+    pub fn offer(&mut self, entry: LeaderboardEntry) -> bool {
+        if entry.matched_chars < self.required_chars()
+            || self
+                .entries
+                .iter()
+                .any(|known| known.address == entry.address)
+        {
+            return false;
+        }
+
+        let position = self
+            .entries
+            .partition_point(|known| known.matched_chars >= entry.matched_chars);
+        self.entries.insert(position, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        true
+    }
+
+    /// Leading characters a new candidate needs to make the board: one more
+    /// than the last entry once the board is full
+    pub fn required_chars(&self) -> usize {
+        match self.entries.get(LEADERBOARD_SIZE - 1) {
+            Some(last) => (last.matched_chars + 1).min(self.target.len()),
+            None => self.min_chars,
+        }
+    }
+
+    /// First prefix id the workbenches still report, see `target_prefixes`
+    pub fn required_prefix_id(&self) -> u32 {
+        (self.required_chars() - self.min_chars) as u32
+    }
+
+    /// Prefix id of `matched_chars` characters of the target
+    pub fn prefix_id(&self, matched_chars: usize) -> u32 {
+        (matched_chars - self.min_chars) as u32
+    }
+
+    /// The whole target was found, nothing can beat it
+    pub fn is_complete(&self) -> bool {
+        self.entries
+            .first()
+            .is_some_and(|best| best.matched_chars == self.target.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(address: &str, target: &str) -> LeaderboardEntry {
+        LeaderboardEntry {
+            address: address.to_string(),
+            matched_chars: matched_chars(address, target),
+            path: KeyPath::from_slice(&[0, 0, 0]),
+            bench_id: "cpu".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_target_prefixes() {
+        let (min_chars, prefixes) =
+            target_prefixes("1BitcoinEater", Some(4), Network::Mainnet).unwrap();
+        assert_eq!(min_chars, 4);
+        let patterns: Vec<&str> = prefixes.iter().map(Prefix::as_str).collect();
+        assert_eq!(patterns.first(), Some(&"1Bit"));
+        assert_eq!(patterns.last(), Some(&"1BitcoinEater"));
+        assert_eq!(patterns.len(), 10);
+
+        // 1Bitc is the first prefix rarer than 1 in 100,000
+        let (min_chars, prefixes) =
+            target_prefixes("1BitcoinEater", None, Network::Mainnet).unwrap();
+        assert_eq!(min_chars, 5);
+        assert!(prefixes[0].match_probability() <= 1e-5);
+
+        assert!(target_prefixes("1Bit", Some(5), Network::Mainnet).is_err());
+        assert!(target_prefixes("1Bob...Eater", None, Network::Mainnet).is_err());
+        assert!(target_prefixes("bc1qxyz", Some(2), Network::Mainnet)
+            .unwrap_err()
+            .contains("'bc' is not a prefix on its own"));
+    }
+
+    #[test]
+    fn test_leaderboard_keeps_the_longest_and_raises_the_bar() {
+        let target = "1BitcoinEater";
+        let mut board = Leaderboard::new(target, 4);
+
+        for i in 0..LEADERBOARD_SIZE {
+            assert!(board.offer(entry(&format!("1Bitx{}", i), target)));
+        }
+        assert_eq!(board.required_chars(), 5);
+        assert_eq!(board.required_prefix_id(), 1);
+
+        // Too short or already known
+        assert!(!board.offer(entry("1Bitz", target)));
+        assert!(board.offer(entry("1Bitcoz", target)));
+        assert!(!board.offer(entry("1Bitcoz", target)));
+        assert_eq!(board.entries()[0].matched_chars, 6);
+        assert_eq!(board.entries().len(), LEADERBOARD_SIZE);
+        assert!(!board.is_complete());
+
+        assert!(board.offer(entry("1BitcoinEater9", target)));
+        assert!(board.is_complete());
+        assert_eq!(board.prefix_id(13), 9);
+    }
+}
@@ -1,32 +1,61 @@
+mod budget;
+mod checkpoint;
 mod cli;
+mod comb_multiplier;
 mod constants;
+mod cpu_throttle;
+mod cpu_topology;
 mod cpu_workbench;
+mod descriptor;
 mod device_info;
 mod device_manager;
 mod device_selector;
+mod difficulty;
 mod display_backend;
 mod events;
 mod extended_public_key;
 mod extended_public_key_deriver;
 mod extended_public_key_path_walker;
+mod gap_estimate;
 mod gpu_workbench;
 mod ground_truth_validator;
+mod json_backend;
+mod key_version;
+mod longest_match;
+mod multi_hash160;
+mod network;
 #[cfg(test)]
 mod null_backend;
 mod opencl;
 mod orchestrator;
+mod path_template;
 mod prefix;
+mod prefix_filter;
+mod prefix_table;
+mod quota;
+mod results_file;
+mod seeds;
+mod shard;
 mod tui_backend;
 mod workbench;
 mod workbench_config;
 mod workbench_factory;
 
-use cli::Cli;
+use checkpoint::Checkpoint;
+use cli::{Cli, Command};
+use cpu_topology::{CorePlan, CpuTopology};
+use descriptor::DescriptorKey;
+use device_info::DeviceInfo;
 use device_selector::{DeviceConfig, DeviceSelector};
 use display_backend::UiBackend;
 use extended_public_key::ExtendedPubKey;
+use gap_estimate::GapEstimator;
 use ground_truth_validator::GroundTruthValidator;
+use json_backend::JsonBackend;
+use longest_match::Leaderboard;
 use orchestrator::Orchestrator;
+use results_file::ResultsFile;
+use std::io::{stdout, IsTerminal};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tui_backend::TuiBackend;
@@ -34,12 +63,59 @@ use tui_backend::TuiBackend;
 fn main() {
     let cli = Cli::parse_args();
 
+    if let Some(Command::Merge { output, inputs }) = &cli.command {
+        match results_file::merge(inputs, output) {
+            Ok(summary) => println!(
+                "Merged {} results into '{}', skipped {} duplicates",
+                summary.merged,
+                output.display(),
+                summary.duplicates
+            ),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let prefixes = cli.prefixes.clone();
+    let gap_estimator = cli
+        .gap_limit
+        .map(|gap_limit| GapEstimator::new(&prefixes, &cli.path_template, gap_limit));
+    if cli.dry_run {
+        difficulty::print_dry_run(&prefixes, gap_estimator.as_ref());
+        return;
+    }
+
+    let leaderboard = cli
+        .longest
+        .as_ref()
+        .map(|target| Leaderboard::new(target, cli.min_match.expect("Resolved with the prefixes")));
+
     let xpub = ExtendedPubKey::from_str(&cli.xpub).unwrap();
-    let ground_truth_validator =
-        GroundTruthValidator::new(&cli.xpub).expect("Failed to create ground truth validator");
+    let ground_truth_validator = GroundTruthValidator::new(&cli.xpub, cli.network)
+        .expect("Failed to create ground truth validator");
+    let key_origin = cli
+        .descriptor
+        .as_ref()
+        .and_then(|descriptor| descriptor.origin.clone());
+    let descriptor_key = DescriptorKey {
+        origin: key_origin.clone(),
+        xpub: key_version::to_xpub(&cli.xpub).expect("Failed to convert the key to an xpub"),
+    };
+
+    // Opened before the search so a file of another xpub is refused upfront
+    let results_file = cli.output.as_ref().map(|path| {
+        ResultsFile::open(path, xpub.fingerprint()).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        })
+    });
 
     let stop_signal = Arc::new(AtomicBool::new(false));
+    // Shared by the CPU workbench and the TUI, whose keys adjust the limit
+    let cpu_throttle = cli.cpu_throttle();
 
     let stop_signal_clone = Arc::clone(&stop_signal);
     ctrlc::set_handler(move || {
@@ -57,24 +133,111 @@ fn main() {
         }
     };
 
+    // A resumed search keeps the seeds and the counters of its checkpoint
+    let bench_ids: Vec<String> = selected_devices.iter().map(DeviceInfo::bench_id).collect();
+    let checkpoint = match (&cli.checkpoint, cli.resume) {
+        (Some(path), true) => Checkpoint::load(path).and_then(|checkpoint| {
+            checkpoint
+                .check_run(
+                    xpub.fingerprint(),
+                    &prefixes,
+                    &cli.path_template,
+                    cli.max_depth,
+                    &bench_ids,
+                )
+                .map_err(|e| format!("Checkpoint '{}' {}", path.display(), e))?;
+            Ok(checkpoint)
+        }),
+        (Some(path), false) if path.exists() => Err(format!(
+            "Checkpoint '{}' already exists, pass --resume to continue it",
+            path.display()
+        )),
+        _ => {
+            let checkpoint = Checkpoint::new(
+                xpub.fingerprint(),
+                &prefixes,
+                cli.path_template,
+                cli.max_depth,
+                cli.run_seeds(),
+                &cli.search_range(),
+                &bench_ids,
+            );
+            // Saved upfront so an unwritable path is refused before the search
+            match &cli.checkpoint {
+                Some(path) => checkpoint
+                    .save(path)
+                    .map(|_| checkpoint)
+                    .map_err(|e| format!("Failed to write '{}': {}", path.display(), e)),
+                None => Ok(checkpoint),
+            }
+        }
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    let seeds = checkpoint.seeds().expect("At least one device is selected");
+
+    // Cores of the workbenches' threads, from the topology when pinned or
+    // when cores are set aside for the GPU producers
+    let searches_on_gpu = selected_devices
+        .iter()
+        .any(|device| matches!(device, DeviceInfo::Gpu { .. }));
+    let reserved_gpu_cores = if searches_on_gpu {
+        cli.reserve_gpu_cores
+    } else {
+        0
+    };
+    let core_plan = if cli.pin_threads || reserved_gpu_cores > 0 {
+        CpuTopology::detect().plan(reserved_gpu_cores, cli.pin_threads)
+    } else {
+        CorePlan::default()
+    };
+
     // Calculate total threads (for logging purposes)
     let total_cpu_threads: u32 = selected_devices.iter().filter_map(|d| d.threads()).sum();
 
-    // Create TUI backend
-    let mut backend: Box<dyn UiBackend> =
-        Box::new(TuiBackend::new(Arc::clone(&stop_signal)).expect("Failed to initialize TUI"));
+    // The TUI needs a terminal, pipes and cron jobs get JSON Lines
+    let mut backend: Box<dyn UiBackend> = if cli.json || !stdout().is_terminal() {
+        Box::new(JsonBackend::new())
+    } else {
+        Box::new(
+            TuiBackend::new(Arc::clone(&stop_signal), cpu_throttle.clone())
+                .expect("Failed to initialize TUI"),
+        )
+    };
 
-    backend.start(&prefixes, cli.max_depth, total_cpu_threads);
+    backend.start(
+        &prefixes,
+        key_origin.as_ref(),
+        seeds,
+        &checkpoint.template(),
+        cli.max_depth,
+        total_cpu_threads,
+    );
 
     let mut orchestrator = Orchestrator::new(
         xpub,
         prefixes,
+        checkpoint,
+        cli.checkpoint.clone(),
         cli.max_depth,
-        cli.num_addresses,
+        cli.quota(),
+        gap_estimator,
+        leaderboard,
+        cli.budget(),
+        core_plan,
+        cpu_throttle,
         stop_signal,
         ground_truth_validator,
+        descriptor_key,
+        results_file,
         backend,
     );
 
-    orchestrator.run(selected_devices);
+    let end_reason = orchestrator.run(selected_devices);
+    let exit_code = end_reason.exit_code(orchestrator.found_any());
+    // Dropped first: the TUI restores the terminal and prints its summary
+    drop(orchestrator);
+    std::process::exit(exit_code);
 }
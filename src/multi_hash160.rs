@@ -0,0 +1,660 @@
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// Most lanes of any implementation, AVX-512's 16
+const MAX_LANES: usize = 16;
+
+/// Longest message hashed in a single SHA-256 block, with its padding
+const MAX_MESSAGE_LEN: usize = 55;
+
+const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const RIPEMD160_INITIAL_STATE: [u32; 5] =
+    [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// Message word of each step, left and right line
+const RIPEMD160_WORDS: [[usize; 80]; 2] = [
+    [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, //
+        7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5, 2, 14, 11, 8, //
+        3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12, //
+        1, 9, 11, 10, 0, 8, 12, 4, 13, 3, 7, 15, 14, 5, 6, 2, //
+        4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13,
+    ],
+    [
+        5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12, //
+        6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12, 4, 9, 1, 2, //
+        15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13, //
+        8, 6, 4, 1, 3, 11, 15, 0, 5, 12, 2, 13, 9, 7, 10, 14, //
+        12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11,
+    ],
+];
+
+/// Left rotation of each step, left and right line
+const RIPEMD160_ROTATIONS: [[u32; 80]; 2] = [
+    [
+        11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8, //
+        7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15, 9, 11, 7, 13, 12, //
+        11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5, //
+        11, 12, 14, 15, 14, 15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12, //
+        9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6,
+    ],
+    [
+        8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6, //
+        9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12, 7, 6, 15, 13, 11, //
+        9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5, //
+        15, 5, 8, 11, 14, 14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8, //
+        8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11,
+    ],
+];
+
+/// Constant of each round of 16 steps, left and right line
+const RIPEMD160_ROUND_CONSTANTS: [[u32; 5]; 2] = [
+    [0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e],
+    [0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9, 0x00000000],
+];
+
+/// The implementation picked for the CPU it runs on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Implementation {
+    /// One message at a time through the `sha2` and `ripemd` hashers
+    Scalar,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "x86_64")]
+    Avx512,
+}
+
+/// Hash160 (RIPEMD-160 of SHA-256) of many short messages at once, one per
+/// SIMD lane: 4 with NEON, 8 with AVX2 and 16 with AVX-512, picked at run
+/// time. Messages fit a single SHA-256 block, as compressed public keys and
+/// redeem scripts do.
+#[derive(Clone, Copy, Debug)]
+pub struct MultiHash160 {
+    implementation: Implementation,
+}
+
+impl MultiHash160 {
+    /// The widest implementation the CPU supports
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx512f") {
+                return MultiHash160 {
+                    implementation: Implementation::Avx512,
+                };
+            }
+            if std::arch::is_x86_feature_detected!("avx2") {
+                return MultiHash160 {
+                    implementation: Implementation::Avx2,
+                };
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return MultiHash160 {
+                    implementation: Implementation::Neon,
+                };
+            }
+        }
+        Self::scalar()
+    }
+
+    pub fn scalar() -> Self {
+        MultiHash160 {
+            implementation: Implementation::Scalar,
+        }
+    }
+
+    /// Messages hashed together
+    #[cfg(test)]
+    pub fn lanes(&self) -> usize {
+        match self.implementation {
+            Implementation::Scalar => 1,
+            #[cfg(target_arch = "aarch64")]
+            Implementation::Neon => 4,
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx2 => 8,
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx512 => 16,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn name(&self) -> &'static str {
+        match self.implementation {
+            Implementation::Scalar => "scalar",
+            #[cfg(target_arch = "aarch64")]
+            Implementation::Neon => "NEON",
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx2 => "AVX2",
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx512 => "AVX-512",
+        }
+    }
+
+    /// The hash160 of each of `messages` into `hashes`, of the same length
+    pub fn hash_160<const N: usize>(&self, messages: &[[u8; N]], hashes: &mut [[u8; 20]]) {
+        const { assert!(N <= MAX_MESSAGE_LEN) };
+        assert_eq!(messages.len(), hashes.len());
+
+        match self.implementation {
+            Implementation::Scalar => {
+                for (message, hash) in messages.iter().zip(hashes.iter_mut()) {
+                    hash.copy_from_slice(&Ripemd160::digest(Sha256::digest(message)));
+                }
+            }
+            // SAFETY: detect() only picks implementations the CPU supports
+            #[cfg(target_arch = "aarch64")]
+            Implementation::Neon => unsafe { neon::hash_160(messages, hashes) },
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx2 => unsafe { avx2::hash_160(messages, hashes) },
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx512 => unsafe { avx512::hash_160(messages, hashes) },
+        }
+    }
+}
+
+/// A vector of `COUNT` u32 lanes, one message per lane. Every method is
+/// inlined into the `target_feature` function that hashes, so the
+/// intrinsics are compiled with the feature.
+trait Lanes: Copy {
+    const COUNT: usize;
+
+    fn splat(value: u32) -> Self;
+    /// From the first `COUNT` words
+    fn load(words: &[u32; MAX_LANES]) -> Self;
+    /// Into the first `COUNT` words
+    fn store(self, words: &mut [u32; MAX_LANES]);
+    fn add(self, other: Self) -> Self;
+    fn and(self, other: Self) -> Self;
+    fn or(self, other: Self) -> Self;
+    fn xor(self, other: Self) -> Self;
+    fn shl(self, bits: u32) -> Self;
+    fn shr(self, bits: u32) -> Self;
+
+    #[inline(always)]
+    fn not(self) -> Self {
+        self.xor(Self::splat(u32::MAX))
+    }
+
+    #[inline(always)]
+    fn rotl(self, bits: u32) -> Self {
+        self.shl(bits).or(self.shr(32 - bits))
+    }
+
+    #[inline(always)]
+    fn rotr(self, bits: u32) -> Self {
+        self.rotl(32 - bits)
+    }
+
+    #[inline(always)]
+    fn swap_bytes(self) -> Self {
+        let middle = Self::splat(0x0000_ff00);
+        self.shl(24)
+            .or(self.and(middle).shl(8))
+            .or(self.shr(8).and(middle))
+            .or(self.shr(24))
+    }
+}
+
+/// Hashes `messages` `L::COUNT` at a time, the unused lanes of the last
+/// group hash zeros
+#[inline(always)]
+fn hash_160_lanes<L: Lanes, const N: usize>(messages: &[[u8; N]], hashes: &mut [[u8; 20]]) {
+    for (messages, hashes) in messages.chunks(L::COUNT).zip(hashes.chunks_mut(L::COUNT)) {
+        // The single padded SHA-256 block of every lane, big-endian words
+        let mut block = [[0u32; MAX_LANES]; 16];
+        for (lane, message) in messages.iter().enumerate() {
+            let mut bytes = [0u8; 64];
+            bytes[..N].copy_from_slice(message);
+            bytes[N] = 0x80;
+            bytes[56..].copy_from_slice(&(N as u64 * 8).to_be_bytes());
+            for (word, chunk) in block.iter_mut().zip(bytes.chunks_exact(4)) {
+                word[lane] = u32::from_be_bytes(chunk.try_into().unwrap());
+            }
+        }
+
+        let sha256 = sha256_block(std::array::from_fn(|i| L::load(&block[i])));
+        let ripemd160 = ripemd160_digest(&sha256);
+
+        for (i, word) in ripemd160.iter().enumerate() {
+            let mut words = [0u32; MAX_LANES];
+            word.store(&mut words);
+            for (hash, word) in hashes.iter_mut().zip(words) {
+                hash[4 * i..4 * i + 4].copy_from_slice(&word.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// SHA-256 of a single padded block
+#[inline(always)]
+fn sha256_block<L: Lanes>(mut w: [L; 16]) -> [L; 8] {
+    let mut state = SHA256_INITIAL_STATE.map(L::splat);
+
+    for (i, &round_constant) in SHA256_ROUND_CONSTANTS.iter().enumerate() {
+        // The message schedule, kept to a rolling window of 16 words
+        if i >= 16 {
+            let w15 = w[(i + 1) % 16];
+            let w2 = w[(i + 14) % 16];
+            let s0 = w15.rotr(7).xor(w15.rotr(18)).xor(w15.shr(3));
+            let s1 = w2.rotr(17).xor(w2.rotr(19)).xor(w2.shr(10));
+            w[i % 16] = w[i % 16].add(s0).add(w[(i + 9) % 16]).add(s1);
+        }
+
+        let [a, b, c, d, e, f, g, h] = state;
+        let s1 = e.rotr(6).xor(e.rotr(11)).xor(e.rotr(25));
+        let choice = e.and(f).xor(e.not().and(g));
+        let t1 = h
+            .add(s1)
+            .add(choice)
+            .add(L::splat(round_constant))
+            .add(w[i % 16]);
+        let s0 = a.rotr(2).xor(a.rotr(13)).xor(a.rotr(22));
+        let majority = a.and(b).xor(a.and(c)).xor(b.and(c));
+        let t2 = s0.add(majority);
+
+        state = [t1.add(t2), a, b, c, d.add(t1), e, f, g];
+    }
+
+    let mut digest = state;
+    for (word, initial) in digest.iter_mut().zip(SHA256_INITIAL_STATE) {
+        *word = word.add(L::splat(initial));
+    }
+    digest
+}
+
+/// RIPEMD-160 of a 32-byte SHA-256 digest, a single padded block
+#[inline(always)]
+fn ripemd160_digest<L: Lanes>(sha256: &[L; 8]) -> [L; 5] {
+    // Little-endian words: the digest bytes, 0x80 and the 256-bit length
+    let mut x = [L::splat(0); 16];
+    for (word, sha256_word) in x.iter_mut().zip(sha256) {
+        *word = sha256_word.swap_bytes();
+    }
+    x[8] = L::splat(0x80);
+    x[14] = L::splat(256);
+
+    let initial = RIPEMD160_INITIAL_STATE.map(L::splat);
+    let mut lines = [initial, initial];
+    for (line, state) in lines.iter_mut().enumerate() {
+        for step in 0..80 {
+            let round = step / 16;
+            // The right line runs the functions in reverse order
+            let function = if line == 0 { round } else { 4 - round };
+            let [a, b, c, d, e] = *state;
+            let f = match function {
+                0 => b.xor(c).xor(d),
+                1 => b.and(c).or(b.not().and(d)),
+                2 => b.or(c.not()).xor(d),
+                3 => b.and(d).or(c.and(d.not())),
+                _ => b.xor(c.or(d.not())),
+            };
+            let t = a
+                .add(f)
+                .add(x[RIPEMD160_WORDS[line][step]])
+                .add(L::splat(RIPEMD160_ROUND_CONSTANTS[line][round]))
+                .rotl(RIPEMD160_ROTATIONS[line][step])
+                .add(e);
+            *state = [e, t, b, c.rotl(10), d];
+        }
+    }
+
+    let [left, right] = lines;
+    [
+        initial[1].add(left[2]).add(right[3]),
+        initial[2].add(left[3]).add(right[4]),
+        initial[3].add(left[4]).add(right[0]),
+        initial[4].add(left[0]).add(right[1]),
+        initial[0].add(left[1]).add(right[2]),
+    ]
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{hash_160_lanes, Lanes, MAX_LANES};
+    use std::arch::x86_64::*;
+
+    impl Lanes for __m256i {
+        const COUNT: usize = 8;
+
+        #[inline(always)]
+        fn splat(value: u32) -> Self {
+            unsafe { _mm256_set1_epi32(value as i32) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32; MAX_LANES]) -> Self {
+            unsafe { _mm256_loadu_si256(words.as_ptr() as *const __m256i) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32; MAX_LANES]) {
+            unsafe { _mm256_storeu_si256(words.as_mut_ptr() as *mut __m256i, self) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { _mm256_add_epi32(self, other) }
+        }
+
+        #[inline(always)]
+        fn and(self, other: Self) -> Self {
+            unsafe { _mm256_and_si256(self, other) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { _mm256_or_si256(self, other) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { _mm256_xor_si256(self, other) }
+        }
+
+        #[inline(always)]
+        fn shl(self, bits: u32) -> Self {
+            unsafe { _mm256_sll_epi32(self, _mm_cvtsi32_si128(bits as i32)) }
+        }
+
+        #[inline(always)]
+        fn shr(self, bits: u32) -> Self {
+            unsafe { _mm256_srl_epi32(self, _mm_cvtsi32_si128(bits as i32)) }
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn hash_160<const N: usize>(messages: &[[u8; N]], hashes: &mut [[u8; 20]]) {
+        hash_160_lanes::<__m256i, N>(messages, hashes)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx512 {
+    use super::{hash_160_lanes, Lanes, MAX_LANES};
+    use std::arch::x86_64::*;
+
+    impl Lanes for __m512i {
+        const COUNT: usize = 16;
+
+        #[inline(always)]
+        fn splat(value: u32) -> Self {
+            unsafe { _mm512_set1_epi32(value as i32) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32; MAX_LANES]) -> Self {
+            unsafe { _mm512_loadu_si512(words.as_ptr() as *const __m512i) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32; MAX_LANES]) {
+            unsafe { _mm512_storeu_si512(words.as_mut_ptr() as *mut __m512i, self) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { _mm512_add_epi32(self, other) }
+        }
+
+        #[inline(always)]
+        fn and(self, other: Self) -> Self {
+            unsafe { _mm512_and_si512(self, other) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { _mm512_or_si512(self, other) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { _mm512_xor_si512(self, other) }
+        }
+
+        #[inline(always)]
+        fn shl(self, bits: u32) -> Self {
+            unsafe { _mm512_sll_epi32(self, _mm_cvtsi32_si128(bits as i32)) }
+        }
+
+        #[inline(always)]
+        fn shr(self, bits: u32) -> Self {
+            unsafe { _mm512_srl_epi32(self, _mm_cvtsi32_si128(bits as i32)) }
+        }
+
+        #[inline(always)]
+        fn rotl(self, bits: u32) -> Self {
+            unsafe { _mm512_rolv_epi32(self, _mm512_set1_epi32(bits as i32)) }
+        }
+    }
+
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn hash_160<const N: usize>(messages: &[[u8; N]], hashes: &mut [[u8; 20]]) {
+        hash_160_lanes::<__m512i, N>(messages, hashes)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::{hash_160_lanes, Lanes, MAX_LANES};
+    use std::arch::aarch64::*;
+
+    impl Lanes for uint32x4_t {
+        const COUNT: usize = 4;
+
+        #[inline(always)]
+        fn splat(value: u32) -> Self {
+            unsafe { vdupq_n_u32(value) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32; MAX_LANES]) -> Self {
+            unsafe { vld1q_u32(words.as_ptr()) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32; MAX_LANES]) {
+            unsafe { vst1q_u32(words.as_mut_ptr(), self) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { vaddq_u32(self, other) }
+        }
+
+        #[inline(always)]
+        fn and(self, other: Self) -> Self {
+            unsafe { vandq_u32(self, other) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { vorrq_u32(self, other) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { veorq_u32(self, other) }
+        }
+
+        // A negative shift count shifts right
+        #[inline(always)]
+        fn shl(self, bits: u32) -> Self {
+            unsafe { vshlq_u32(self, vdupq_n_s32(bits as i32)) }
+        }
+
+        #[inline(always)]
+        fn shr(self, bits: u32) -> Self {
+            unsafe { vshlq_u32(self, vdupq_n_s32(-(bits as i32))) }
+        }
+
+        #[inline(always)]
+        fn not(self) -> Self {
+            unsafe { vmvnq_u32(self) }
+        }
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn hash_160<const N: usize>(messages: &[[u8; N]], hashes: &mut [[u8; 20]]) {
+        hash_160_lanes::<uint32x4_t, N>(messages, hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use std::time::Instant;
+
+    /// Plain u32 lanes, to check the lane code on any CPU
+    impl Lanes for u32 {
+        const COUNT: usize = 1;
+
+        fn splat(value: u32) -> Self {
+            value
+        }
+
+        fn load(words: &[u32; MAX_LANES]) -> Self {
+            words[0]
+        }
+
+        fn store(self, words: &mut [u32; MAX_LANES]) {
+            words[0] = self;
+        }
+
+        fn add(self, other: Self) -> Self {
+            self.wrapping_add(other)
+        }
+
+        fn and(self, other: Self) -> Self {
+            self & other
+        }
+
+        fn or(self, other: Self) -> Self {
+            self | other
+        }
+
+        fn xor(self, other: Self) -> Self {
+            self ^ other
+        }
+
+        fn shl(self, bits: u32) -> Self {
+            self << bits
+        }
+
+        fn shr(self, bits: u32) -> Self {
+            self >> bits
+        }
+    }
+
+    fn random_messages<const N: usize>(count: usize) -> Vec<[u8; N]> {
+        let mut rng = rand::rng();
+        (0..count)
+            .map(|_| {
+                let mut message = [0u8; N];
+                rng.fill(&mut message[..]);
+                message
+            })
+            .collect()
+    }
+
+    fn scalar_hashes<const N: usize>(messages: &[[u8; N]]) -> Vec<[u8; 20]> {
+        let mut hashes = vec![[0u8; 20]; messages.len()];
+        MultiHash160::scalar().hash_160(messages, &mut hashes);
+        hashes
+    }
+
+    #[test]
+    fn test_scalar_matches_bitcoin_hash160() {
+        use bitcoin::hashes::{hash160, Hash};
+
+        let messages = random_messages::<33>(10);
+        for (message, hash) in messages.iter().zip(scalar_hashes(&messages)) {
+            assert_eq!(hash, hash160::Hash::hash(message).to_byte_array());
+        }
+    }
+
+    #[test]
+    fn test_lane_code_matches_scalar() {
+        let messages = random_messages::<33>(50);
+        let mut hashes = vec![[0u8; 20]; messages.len()];
+        hash_160_lanes::<u32, 33>(&messages, &mut hashes);
+        assert_eq!(hashes, scalar_hashes(&messages));
+
+        let scripts = random_messages::<22>(50);
+        let mut hashes = vec![[0u8; 20]; scripts.len()];
+        hash_160_lanes::<u32, 22>(&scripts, &mut hashes);
+        assert_eq!(hashes, scalar_hashes(&scripts));
+    }
+
+    #[test]
+    fn test_detected_lanes_match_scalar() {
+        let hasher = MultiHash160::detect();
+
+        // Whole lane groups and a partial last one
+        for count in [0, 1, hasher.lanes(), 3 * hasher.lanes() + 5, 1000] {
+            let messages = random_messages::<33>(count);
+            let mut hashes = vec![[0u8; 20]; count];
+            hasher.hash_160(&messages, &mut hashes);
+            assert_eq!(hashes, scalar_hashes(&messages), "{}", hasher.name());
+        }
+
+        let scripts = random_messages::<22>(100);
+        let mut hashes = vec![[0u8; 20]; scripts.len()];
+        hasher.hash_160(&scripts, &mut hashes);
+        assert_eq!(hashes, scalar_hashes(&scripts), "{}", hasher.name());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_matches_scalar() {
+        if !std::arch::is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let messages = random_messages::<33>(100);
+        let mut hashes = vec![[0u8; 20]; messages.len()];
+        unsafe { avx2::hash_160(&messages, &mut hashes) };
+        assert_eq!(hashes, scalar_hashes(&messages));
+    }
+
+    /// Compressed public keys per second of each implementation the CPU
+    /// supports. Run with
+    /// `cargo test --release bench_multi_hash160 -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_multi_hash160() {
+        const KEYS: usize = 1 << 20;
+        let messages = random_messages::<33>(KEYS);
+        let mut hashes = vec![[0u8; 20]; KEYS];
+
+        for hasher in [MultiHash160::scalar(), MultiHash160::detect()] {
+            let start = Instant::now();
+            hasher.hash_160(&messages, &mut hashes);
+            let elapsed = start.elapsed();
+            println!(
+                "{} ({} lanes): {:.0} hashes/s",
+                hasher.name(),
+                hasher.lanes(),
+                KEYS as f64 / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
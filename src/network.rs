@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+
+/// Bitcoin network of the xpub and of the searched addresses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Network {
+    #[default]
+    #[value(alias = "bitcoin")]
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// Base58check version byte of P2PKH addresses
+    pub fn p2pkh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            _ => 0x6f,
+        }
+    }
+
+    /// Base58check version byte of P2SH addresses
+    pub fn p2sh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            _ => 0xc4,
+        }
+    }
+
+    /// First character of every P2PKH address of this network
+    pub fn p2pkh_leading_chars(&self) -> &'static [char] {
+        match self {
+            Network::Mainnet => &['1'],
+            _ => &['m', 'n'],
+        }
+    }
+
+    /// First character of every P2SH address of this network
+    pub fn p2sh_leading_chars(&self) -> &'static [char] {
+        match self {
+            Network::Mainnet => &['3'],
+            _ => &['2'],
+        }
+    }
+
+    /// Human readable part of the segwit addresses
+    pub fn bech32_hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+
+    /// Whether `pattern` starts like an address of this network, in any
+    /// casing
+    pub fn has_leading_chars(&self, pattern: &str) -> bool {
+        let base58_start = pattern.chars().next().is_some_and(|first| {
+            self.p2pkh_leading_chars().contains(&first)
+                || self.p2sh_leading_chars().contains(&first)
+        });
+        base58_start
+            || pattern
+                .to_lowercase()
+                .starts_with(&format!("{}1", self.bech32_hrp()))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+}
+
+impl From<Network> for bitcoin::Network {
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Mainnet => bitcoin::Network::Bitcoin,
+            Network::Testnet => bitcoin::Network::Testnet,
+            Network::Signet => bitcoin::Network::Signet,
+            Network::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bech32_hrp_matches_bitcoin() {
+        for network in Network::value_variants() {
+            let program =
+                bitcoin::WitnessProgram::new(bitcoin::WitnessVersion::V0, &[0u8; 20]).unwrap();
+            let address =
+                bitcoin::Address::from_witness_program(program, bitcoin::Network::from(*network));
+            assert!(address
+                .to_string()
+                .starts_with(&format!("{}1q", network.bech32_hrp())));
+        }
+    }
+
+    #[test]
+    fn test_has_leading_chars() {
+        assert!(Network::Mainnet.has_leading_chars("1A"));
+        assert!(Network::Mainnet.has_leading_chars("BC1Qxy"));
+        assert!(!Network::Mainnet.has_leading_chars("mA"));
+        assert!(!Network::Mainnet.has_leading_chars("bcrt1q"));
+        assert!(Network::Testnet.has_leading_chars("mA"));
+        assert!(Network::Testnet.has_leading_chars("2N"));
+        assert!(Network::Regtest.has_leading_chars("bcrt1q"));
+        assert!(!Network::Regtest.has_leading_chars("tb1q"));
+        assert!(!Network::Testnet.has_leading_chars(""));
+    }
+
+    #[test]
+    fn test_leading_chars_match_versions() {
+        use bitcoin::hashes::Hash;
+
+        for network in Network::value_variants() {
+            let kind = bitcoin::NetworkKind::from(bitcoin::Network::from(*network));
+            for hash in [[0u8; 20], [0xff; 20]] {
+                let p2pkh =
+                    bitcoin::Address::p2pkh(bitcoin::PubkeyHash::from_byte_array(hash), kind);
+                let p2sh = bitcoin::Address::p2sh_from_hash(
+                    bitcoin::ScriptHash::from_byte_array(hash),
+                    kind,
+                );
+
+                assert!(p2pkh.to_string().starts_with(network.p2pkh_leading_chars()));
+                assert!(p2sh.to_string().starts_with(network.p2sh_leading_chars()));
+            }
+        }
+    }
+}
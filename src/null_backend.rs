@@ -1,6 +1,13 @@
+use crate::budget::EndReason;
+use crate::descriptor::KeyOrigin;
 use crate::display_backend::{BenchStats, UiBackend};
+use crate::gap_estimate::GapEstimate;
+use crate::longest_match::Leaderboard;
+use crate::path_template::{KeyPath, PathTemplate};
 use crate::prefix::Prefix;
+use crate::seeds::Seeds;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
@@ -15,22 +22,35 @@ impl NullBackend {
 }
 
 impl UiBackend for NullBackend {
-    fn start(&mut self, _prefixes: &[Prefix], _max_depth: u32, _cpu_threads: u32) {}
-    fn workbench_starting(&mut self, _bench_id: &str) {}
+    fn start(
+        &mut self,
+        _prefixes: &[Prefix],
+        _key_origin: Option<&KeyOrigin>,
+        _seeds: Seeds,
+        _template: &PathTemplate,
+        _max_depth: u32,
+        _cpu_threads: u32,
+    ) {
+    }
+    fn workbench_starting(&mut self, _bench_id: &str, _counter_range: &Range<u64>) {}
     fn workbench_started(&mut self, _bench_id: &str) {}
     fn log_status(&mut self, _bench_stats: &HashMap<String, BenchStats>) {}
+    fn log_gap_estimate(&mut self, _estimate: &GapEstimate) {}
     fn log_found_address(
         &mut self,
         _bench_id: &str,
         _address: &str,
-        _path: &[u32; 6],
-        _prefix_id: u8,
+        _path: &KeyPath,
+        _prefix_id: u32,
+        _descriptor: &str,
     ) {
     }
+    fn log_leaderboard(&mut self, _leaderboard: &Leaderboard) {}
     fn log_derivation_error(&mut self) {}
-    fn log_false_positive(&mut self, _bench_id: &str, _path: &[u32; 6]) {}
+    fn log_false_positive(&mut self, _bench_id: &str, _path: &KeyPath) {}
+    fn log_error(&mut self, _message: &str) {}
     fn stop_requested(&mut self) {}
     fn workbench_stopping(&mut self, _bench_id: &str) {}
     fn workbench_stopped(&mut self, _bench_id: &str, _total_generated: u64, _elapsed: Duration) {}
-    fn final_status(&mut self) {}
+    fn final_status(&mut self, _reason: EndReason) {}
 }
@@ -1,8 +1,11 @@
+use crate::cpu_topology::ThreadPlacement;
 use crate::extended_public_key::ExtendedPubKey;
 use crate::extended_public_key_deriver::ExtendedPublicKeyDeriver;
 #[cfg(test)]
 use crate::opencl::gpu_cache::GpuCache;
 use crate::opencl::gpu_cache::{PointGpu, Uint256, XPub};
+use crate::path_template::PathTemplate;
+use crate::seeds::Seeds;
 use std::thread;
 
 pub struct CachePreloader;
@@ -19,12 +22,13 @@ impl CachePreloader {
         deriver: &mut ExtendedPublicKeyDeriver,
         seed0: u32,
         seed1: u32,
+        template: &PathTemplate,
     ) -> Result<bool, String> {
         if cache_keys.is_empty() {
             return Ok(false);
         }
 
-        let xpubs = Self::derive_xpubs(cache_keys, deriver, seed0, seed1)?;
+        let xpubs = Self::derive_xpubs(cache_keys, deriver, seed0, seed1, template)?;
 
         // Replace cache data (GpuCache will only write to GPU if keys changed)
         cache.replace_data(cache_keys, &xpubs)
@@ -37,12 +41,14 @@ impl CachePreloader {
         deriver: &mut ExtendedPublicKeyDeriver,
         seed0: u32,
         seed1: u32,
+        template: &PathTemplate,
     ) -> Result<Vec<XPub>, String> {
         let mut xpubs = Vec::with_capacity(cache_keys.len());
+        let seeds = Seeds { seed0, seed1 };
 
         for &[b, a] in cache_keys {
-            // Build path: [seed0, seed1, b, a, 0]
-            let path = [seed0, seed1, b, a, 0];
+            // The template's path without its address index, e.g. [seed0, seed1, b, a, 0]
+            let path = template.parent_path(&seeds, b, a);
 
             // Derive using CPU deriver - returns (chain_code, x, y)
             let (chain_code, x_bytes, y_bytes) = deriver
@@ -65,12 +71,16 @@ impl CachePreloader {
     /// fan-out with no shared state.
     ///
     /// `num_threads` is clamped to at least 1 and at most the key count.
+    /// Thread `i` runs on the `i`-th core of `placement` when it pins, on
+    /// any of its cores otherwise.
     pub fn derive_xpubs_parallel(
         cache_keys: &[[u32; 2]],
         base_xpub: &ExtendedPubKey,
         seed0: u32,
         seed1: u32,
+        template: &PathTemplate,
         num_threads: usize,
+        placement: &ThreadPlacement,
     ) -> Result<Vec<XPub>, String> {
         if cache_keys.is_empty() {
             return Ok(Vec::new());
@@ -79,7 +89,7 @@ impl CachePreloader {
         let num_threads = num_threads.clamp(1, cache_keys.len());
         if num_threads == 1 {
             let mut deriver = ExtendedPublicKeyDeriver::new(base_xpub);
-            return Self::derive_xpubs(cache_keys, &mut deriver, seed0, seed1);
+            return Self::derive_xpubs(cache_keys, &mut deriver, seed0, seed1, template);
         }
 
         // Ceil division so every key lands in exactly one contiguous chunk.
@@ -88,10 +98,12 @@ impl CachePreloader {
         let chunk_results: Vec<Result<Vec<XPub>, String>> = thread::scope(|scope| {
             let handles: Vec<_> = cache_keys
                 .chunks(chunk_size)
-                .map(|chunk| {
+                .enumerate()
+                .map(|(thread_index, chunk)| {
                     scope.spawn(move || {
+                        placement.place_current_thread(thread_index);
                         let mut deriver = ExtendedPublicKeyDeriver::new(base_xpub);
-                        Self::derive_xpubs(chunk, &mut deriver, seed0, seed1)
+                        Self::derive_xpubs(chunk, &mut deriver, seed0, seed1, template)
                     })
                 })
                 .collect();
@@ -156,7 +168,14 @@ mod tests {
     fn serial_xpubs(cache_keys: &[[u32; 2]], seed0: u32, seed1: u32) -> Vec<XPub> {
         let xpub = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
         let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
-        CachePreloader::derive_xpubs(cache_keys, &mut deriver, seed0, seed1).unwrap()
+        CachePreloader::derive_xpubs(
+            cache_keys,
+            &mut deriver,
+            seed0,
+            seed1,
+            &PathTemplate::default(),
+        )
+        .unwrap()
     }
 
     fn assert_parallel_matches_serial(cache_keys: &[[u32; 2]], seed0: u32, seed1: u32) {
@@ -166,9 +185,16 @@ mod tests {
         // Parallel result must be identical (same values, same order) for
         // any thread count - including more threads than keys.
         for num_threads in [1, 2, 3, 4, 8, 16, 64] {
-            let got =
-                CachePreloader::derive_xpubs_parallel(cache_keys, &xpub, seed0, seed1, num_threads)
-                    .unwrap();
+            let got = CachePreloader::derive_xpubs_parallel(
+                cache_keys,
+                &xpub,
+                seed0,
+                seed1,
+                &PathTemplate::default(),
+                num_threads,
+                &ThreadPlacement::default(),
+            )
+            .unwrap();
             assert_eq!(
                 got.len(),
                 cache_keys.len(),
@@ -186,7 +212,16 @@ mod tests {
     #[test]
     fn test_parallel_derivation_empty() {
         let xpub = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
-        let got = CachePreloader::derive_xpubs_parallel(&[], &xpub, 0, 0, 8).unwrap();
+        let got = CachePreloader::derive_xpubs_parallel(
+            &[],
+            &xpub,
+            0,
+            0,
+            &PathTemplate::default(),
+            8,
+            &ThreadPlacement::default(),
+        )
+        .unwrap();
         assert!(got.is_empty());
     }
 
@@ -253,7 +288,15 @@ mod tests {
 
         let cache_keys = vec![[0, 0]];
 
-        CachePreloader::preload(&mut cache, &cache_keys, &mut deriver, 0, 0).unwrap();
+        CachePreloader::preload(
+            &mut cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         assert_eq!(cache.size(), 1);
         assert!(cache.contains_key(&[0, 0]).unwrap());
@@ -269,7 +312,15 @@ mod tests {
 
         let cache_keys = vec![[0, 0], [0, 1], [0, 2]];
 
-        CachePreloader::preload(&mut cache, &cache_keys, &mut deriver, 0, 0).unwrap();
+        CachePreloader::preload(
+            &mut cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         assert_eq!(cache.size(), 3);
         assert!(cache.contains_key(&[0, 0]).unwrap());
@@ -287,7 +338,15 @@ mod tests {
 
         let cache_keys: Vec<[u32; 2]> = vec![];
 
-        CachePreloader::preload(&mut cache, &cache_keys, &mut deriver, 0, 0).unwrap();
+        CachePreloader::preload(
+            &mut cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         assert_eq!(cache.size(), 0);
     }
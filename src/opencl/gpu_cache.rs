@@ -1,3 +1,5 @@
+use crate::prefix::{Suffix, SuffixCheck};
+use crate::prefix_table::{PrefixTables, TableRange};
 use ocl::{Buffer, Context, Device, Queue};
 
 #[repr(C)]
@@ -37,11 +39,117 @@ unsafe impl ocl::OclPrm for XPub {}
 pub struct Hash160RangeGpu {
     pub low: [u8; 20],
     pub high: [u8; 20],
-    pub prefix_id: u8,
+    pub first_candidate: u32,
+    pub candidate_count: u32,
 }
 
 unsafe impl ocl::OclPrm for Hash160RangeGpu {}
 
+impl From<&TableRange<20>> for Hash160RangeGpu {
+    fn from(range: &TableRange<20>) -> Self {
+        Self {
+            low: range.low,
+            high: range.high,
+            first_candidate: range.first_candidate,
+            candidate_count: range.candidate_count,
+        }
+    }
+}
+
+/// The pubkey hash160 table followed by the redeem script one in a single
+/// range buffer, their candidates in another
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GpuPrefixTable {
+    pub ranges: Vec<Hash160RangeGpu>,
+    pub pubkey_range_count: u32,
+    pub script_range_count: u32,
+    pub candidates: Vec<u32>,
+}
+
+impl From<&PrefixTables> for GpuPrefixTable {
+    fn from(tables: &PrefixTables) -> Self {
+        let pubkey_hash = &tables.pubkey_hash;
+        let script_hash = &tables.script_hash;
+
+        // The script ranges point past the pubkey candidates
+        let offset = pubkey_hash.candidates().len() as u32;
+        let ranges = pubkey_hash
+            .ranges()
+            .iter()
+            .map(Hash160RangeGpu::from)
+            .chain(script_hash.ranges().iter().map(|range| Hash160RangeGpu {
+                first_candidate: range.first_candidate + offset,
+                ..Hash160RangeGpu::from(range)
+            }))
+            .collect();
+
+        Self {
+            ranges,
+            pubkey_range_count: pubkey_hash.ranges().len() as u32,
+            script_range_count: script_hash.ranges().len() as u32,
+            candidates: [pubkey_hash.candidates(), script_hash.candidates()].concat(),
+        }
+    }
+}
+
+pub const SUFFIX_KIND_NONE: u32 = 0;
+pub const SUFFIX_KIND_BASE58: u32 = 1;
+pub const SUFFIX_KIND_BECH32: u32 = 2;
+
+/// Suffix check of one prefix, indexed by prefix_id in the kernel
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SuffixGpu {
+    pub modulus: u64,
+    pub remainder: u64,
+    pub checksum_mask: u32,
+    pub checksum_value: u32,
+    pub polymod_start: u32,
+    pub kind: u32,
+    pub version: u32,
+    pub data_mask: [u8; 20],
+    pub data_value: [u8; 20],
+}
+
+unsafe impl ocl::OclPrm for SuffixGpu {}
+
+impl From<Option<&Suffix>> for SuffixGpu {
+    fn from(suffix: Option<&Suffix>) -> Self {
+        match suffix.map(|suffix| &suffix.check) {
+            None => Self {
+                kind: SUFFIX_KIND_NONE,
+                ..Self::default()
+            },
+            Some(SuffixCheck::Base58 {
+                version,
+                modulus,
+                remainder,
+            }) => Self {
+                kind: SUFFIX_KIND_BASE58,
+                version: *version as u32,
+                modulus: *modulus,
+                remainder: *remainder,
+                ..Self::default()
+            },
+            Some(SuffixCheck::Bech32 {
+                data_mask,
+                data_value,
+                checksum_mask,
+                checksum_value,
+                polymod_start,
+            }) => Self {
+                kind: SUFFIX_KIND_BECH32,
+                checksum_mask: *checksum_mask,
+                checksum_value: *checksum_value,
+                polymod_start: *polymod_start,
+                data_mask: *data_mask,
+                data_value: *data_value,
+                ..Self::default()
+            },
+        }
+    }
+}
+
 pub struct GpuCache {
     _device: Device,
     _context: Context,
@@ -0,0 +1,10 @@
+#include "src/opencl/structs/structs.cl.h"
+#include "src/opencl/headers/hash/sha256.cl.h"
+
+#define SUFFIX_KIND_NONE 0
+#define SUFFIX_KIND_BASE58 1
+#define SUFFIX_KIND_BECH32 2
+
+#define BECH32_CHECKSUM_LEN 6
+
+int suffix_matches(const uchar hash160[20], __global const SuffixGpu *suffix);
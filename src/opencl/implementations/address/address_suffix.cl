@@ -0,0 +1,95 @@
+#include "src/opencl/headers/address/address_suffix.cl.h"
+#include "src/opencl/headers/hash/sha256.cl.h"
+
+// Base58check address (version byte, hash160 and checksum) read as a
+// base58 number, modulo `modulus`. Reduced one nibble at a time so the
+// shifted remainder never overflows a ulong: `modulus` must stay below 2^60.
+inline ulong base58_address_remainder(const uchar version, const uchar hash160[20], const ulong modulus)
+{
+    uchar payload[21];
+    uchar digest[32];
+    uchar checksum[32];
+
+    payload[0] = version;
+    for (uint i = 0; i < 20; i++)
+    {
+        payload[i + 1] = hash160[i];
+    }
+
+    sha256_single_block(payload, 21, digest);
+    sha256_single_block(digest, 32, checksum);
+
+    ulong remainder = 0;
+    for (uint i = 0; i < 25; i++)
+    {
+        uchar byte = i < 21 ? payload[i] : checksum[i - 21];
+        remainder = ((remainder << 4) | (byte >> 4)) % modulus;
+        remainder = ((remainder << 4) | (byte & 0x0f)) % modulus;
+    }
+
+    return remainder;
+}
+
+inline uint bech32_polymod_step(const uint checksum, const uchar value)
+{
+    uint top = checksum >> 25;
+    uint result = ((checksum & 0x1ffffff) << 5) ^ value;
+
+    result ^= (-((top >> 0) & 1)) & 0x3b6a57b2;
+    result ^= (-((top >> 1) & 1)) & 0x26508e6d;
+    result ^= (-((top >> 2) & 1)) & 0x1ea119fa;
+    result ^= (-((top >> 3) & 1)) & 0x3d4233dd;
+    result ^= (-((top >> 4) & 1)) & 0x2a1462b3;
+
+    return result;
+}
+
+// 30-bit bech32 (witness v0) checksum of a P2WPKH address, continuing from
+// the polymod state after the HRP and witness version
+inline uint bech32_checksum(const uchar hash160[20], const uint polymod_start)
+{
+    uint checksum = polymod_start;
+    uint accumulator = 0;
+    uint bits = 0;
+
+    for (uint i = 0; i < 20; i++)
+    {
+        accumulator = (accumulator << 8) | hash160[i];
+        bits += 8;
+        while (bits >= 5)
+        {
+            bits -= 5;
+            checksum = bech32_polymod_step(checksum, (accumulator >> bits) & 31);
+        }
+    }
+
+    for (uint i = 0; i < BECH32_CHECKSUM_LEN; i++)
+    {
+        checksum = bech32_polymod_step(checksum, 0);
+    }
+
+    return checksum ^ 1;
+}
+
+inline int suffix_matches(const uchar hash160[20], __global const SuffixGpu *suffix)
+{
+    if (suffix->kind == SUFFIX_KIND_BASE58)
+    {
+        return base58_address_remainder((uchar)suffix->version, hash160, suffix->modulus) == suffix->remainder;
+    }
+
+    if (suffix->kind == SUFFIX_KIND_BECH32)
+    {
+        for (uint i = 0; i < 20; i++)
+        {
+            if ((hash160[i] & suffix->data_mask[i]) != suffix->data_value[i])
+            {
+                return 0;
+            }
+        }
+
+        return (bech32_checksum(hash160, suffix->polymod_start) & suffix->checksum_mask) == suffix->checksum_value;
+    }
+
+    return 1;
+}
@@ -1,13 +1,8 @@
 #include "src/opencl/headers/hash/hash160.cl.h"
-#include "src/opencl/headers/hash/hash_common.cl.h"
+#include "src/opencl/headers/hash/sha256.cl.h"
 #include "src/opencl/headers/big_uint/big_uint_from_bytes.cl.h"
 #include "src/opencl/headers/big_uint/big_uint_to_bytes.cl.h"
 
-#define HASH160_BSIG0(x) (ROTR(x, 2) ^ ROTR(x, 13) ^ ROTR(x, 22))
-#define HASH160_BSIG1(x) (ROTR(x, 6) ^ ROTR(x, 11) ^ ROTR(x, 25))
-#define HASH160_SSIG0(x) (ROTR(x, 7) ^ ROTR(x, 18) ^ SHR(x, 3))
-#define HASH160_SSIG1(x) (ROTR(x, 17) ^ ROTR(x, 19) ^ SHR(x, 10))
-
 #define HASH160_F(x, y, z) ((x) ^ (y) ^ (z))
 #define HASH160_G(x, y, z) (((x) & (y)) | ((~(x)) & (z)))
 #define HASH160_H(x, y, z) (((x) | (~(y))) ^ (z))
@@ -15,87 +10,14 @@
 #define HASH160_J(x, y, z) ((x) ^ ((y) | (~(z))))
 #define HASH160_ROL(x, n) (((x) << (n)) | ((x) >> (32 - (n))))
 
-inline void hash160_33(const uchar *restrict input, uchar *restrict output)
+// Hash160 of a message short enough for a single SHA256 block (len <= 55).
+// Inlined with a constant length, so the padding folds away.
+inline void hash160_short(const uchar *restrict input, const uint len, uchar *restrict output)
 {
     uchar padded[64];
+    uint W[16];
 
-    uint W[64];
-
-    uint H_sha[8] = {
-        0x6a09e667, 0xbb67ae85,
-        0x3c6ef372, 0xa54ff53a,
-        0x510e527f, 0x9b05688c,
-        0x1f83d9ab, 0x5be0cd19};
-
-#pragma unroll
-    for (uint i = 0; i < 33; i++)
-    {
-        padded[i] = input[i];
-    }
-
-    padded[33] = 0x80;
-
-#pragma unroll
-    for (uint i = 34; i < 62; i++)
-    {
-        padded[i] = 0x00;
-    }
-
-    padded[62] = 0x01; // 264 bits = 0x0108
-    padded[63] = 0x08;
-
-    uint a, b, c, d, e, f, g, h;
-    uint T1, T2;
-    uint t;
-
-#pragma unroll
-    for (t = 0; t < 16; t++)
-    {
-        W[t] = UINT_FROM_BYTES_BE(padded + (t << 2));
-    }
-
-#pragma unroll
-    for (t = 16; t < 64; t++)
-    {
-        W[t] = HASH160_SSIG1(W[t - 2]) + W[t - 7] + HASH160_SSIG0(W[t - 15]) + W[t - 16];
-    }
-
-    a = H_sha[0];
-    b = H_sha[1];
-    c = H_sha[2];
-    d = H_sha[3];
-    e = H_sha[4];
-    f = H_sha[5];
-    g = H_sha[6];
-    h = H_sha[7];
-
-    for (t = 0; t < 64; t++)
-    {
-        T1 = h + HASH160_BSIG1(e) + CH(e, f, g) + K_SHA256[t] + W[t];
-        T2 = HASH160_BSIG0(a) + MAJ(a, b, c);
-        h = g;
-        g = f;
-        f = e;
-        e = d + T1;
-        d = c;
-        c = b;
-        b = a;
-        a = T1 + T2;
-    }
-
-    H_sha[0] += a;
-    H_sha[1] += b;
-    H_sha[2] += c;
-    H_sha[3] += d;
-    H_sha[4] += e;
-    H_sha[5] += f;
-    H_sha[6] += g;
-    H_sha[7] += h;
-
-    for (uint i = 0; i < 8; i++)
-    {
-        UINT_TO_BYTES_BE(H_sha[i], padded + (i << 2));
-    }
+    sha256_single_block(input, len, padded);
 
     // ====== RIPEMD160 ======
     uint H_ripemd[5] = {
@@ -192,4 +114,26 @@ inline void hash160_33(const uchar *restrict input, uchar *restrict output)
     {
         UINT_TO_BYTES_LE(H_ripemd[i], output + (i << 2));
     }
+}
+
+inline void hash160_33(const uchar *restrict input, uchar *restrict output)
+{
+    hash160_short(input, 33, output);
+}
+
+// Hash160 of the P2SH-P2WPKH redeem script OP_0 <pubkey_hash>
+inline void hash160_redeem_script(const uchar *restrict pubkey_hash, uchar *restrict output)
+{
+    uchar redeem_script[22];
+
+    redeem_script[0] = 0x00; // OP_0
+    redeem_script[1] = 0x14; // push 20 bytes
+
+#pragma unroll
+    for (uint i = 0; i < 20; i++)
+    {
+        redeem_script[i + 2] = pubkey_hash[i];
+    }
+
+    hash160_short(redeem_script, 22, output);
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+#include "src/opencl/headers/hash/sha256.cl.h"
+#include "src/opencl/headers/hash/hash_common.cl.h"
+#include "src/opencl/headers/big_uint/big_uint_from_bytes.cl.h"
+#include "src/opencl/headers/big_uint/big_uint_to_bytes.cl.h"
+
+// SHA256-specific sigma functions, prefixed apart from the SHA512 ones
+#define SHA256_BSIG0(x) (ROTR(x, 2) ^ ROTR(x, 13) ^ ROTR(x, 22))
+#define SHA256_BSIG1(x) (ROTR(x, 6) ^ ROTR(x, 11) ^ ROTR(x, 25))
+#define SHA256_SSIG0(x) (ROTR(x, 7) ^ ROTR(x, 18) ^ SHR(x, 3))
+#define SHA256_SSIG1(x) (ROTR(x, 17) ^ ROTR(x, 19) ^ SHR(x, 10))
+
+// SHA256 of a message short enough for a single block (len <= 55).
+// Inlined with a constant length, so the padding folds away.
+inline void sha256_single_block(const uchar *restrict input, const uint len, uchar *restrict output)
+{
+    uchar padded[64];
+
+    uint W[64];
+
+    uint H[8] = {
+        0x6a09e667, 0xbb67ae85,
+        0x3c6ef372, 0xa54ff53a,
+        0x510e527f, 0x9b05688c,
+        0x1f83d9ab, 0x5be0cd19};
+
+#pragma unroll
+    for (uint i = 0; i < len; i++)
+    {
+        padded[i] = input[i];
+    }
+
+    padded[len] = 0x80;
+
+#pragma unroll
+    for (uint i = len + 1; i < 62; i++)
+    {
+        padded[i] = 0x00;
+    }
+
+    uint bit_len = len << 3;
+    padded[62] = (uchar)(bit_len >> 8);
+    padded[63] = (uchar)(bit_len);
+
+    uint a, b, c, d, e, f, g, h;
+    uint T1, T2;
+    uint t;
+
+#pragma unroll
+    for (t = 0; t < 16; t++)
+    {
+        W[t] = UINT_FROM_BYTES_BE(padded + (t << 2));
+    }
+
+#pragma unroll
+    for (t = 16; t < 64; t++)
+    {
+        W[t] = SHA256_SSIG1(W[t - 2]) + W[t - 7] + SHA256_SSIG0(W[t - 15]) + W[t - 16];
+    }
+
+    a = H[0];
+    b = H[1];
+    c = H[2];
+    d = H[3];
+    e = H[4];
+    f = H[5];
+    g = H[6];
+    h = H[7];
+
+    for (t = 0; t < 64; t++)
+    {
+        T1 = h + SHA256_BSIG1(e) + CH(e, f, g) + K_SHA256[t] + W[t];
+        T2 = SHA256_BSIG0(a) + MAJ(a, b, c);
+        h = g;
+        g = f;
+        f = e;
+        e = d + T1;
+        d = c;
+        c = b;
+        b = a;
+        a = T1 + T2;
+    }
+
+    H[0] += a;
+    H[1] += b;
+    H[2] += c;
+    H[3] += d;
+    H[4] += e;
+    H[5] += f;
+    H[6] += g;
+    H[7] += h;
+
+    for (uint i = 0; i < 8; i++)
+    {
+        UINT_TO_BYTES_BE(H[i], output + (i << 2));
+    }
+}
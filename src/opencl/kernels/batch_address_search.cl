@@ -1,6 +1,7 @@
 #include "src/opencl/headers/cache/cache_index.cl.h"
 #include "src/opencl/headers/secp256k1/ckdpub.cl.h"
 #include "src/opencl/headers/hash/hash160.cl.h"
+#include "src/opencl/headers/address/address_suffix.cl.h"
 
 #define NON_HARDENED_MAX_INDEX 0x7FFFFFFF
 #define NON_HARDENED_COUNT ((ulong)(NON_HARDENED_MAX_INDEX) + 1)
@@ -57,11 +58,93 @@ inline int hash160_lte(const uchar a[20], __global const uchar *b)
     return lt | eq; // a <= b if (a < b) OR (a == b)
 }
 
+// Binary search of the sorted, disjoint ranges: the index of the range
+// holding the key, or -1
+inline long find_range(const uchar key[20],
+                       __global const Hash160RangeGpu *ranges,
+                       uint range_count)
+{
+    // Ranges before `lo` start at or below the key, ranges from `hi` above it
+    uint lo = 0;
+    uint hi = range_count;
+    while (lo < hi)
+    {
+        uint mid = (lo + hi) / 2;
+        if (hash160_gte(key, ranges[mid].low))
+        {
+            lo = mid + 1;
+        }
+        else
+        {
+            hi = mid;
+        }
+    }
+
+    if (lo == 0 || !hash160_lte(key, ranges[lo - 1].high))
+    {
+        return -1;
+    }
+    return lo - 1;
+}
+
+// Saves a match for every wanted candidate of the range holding the key
+// whose suffix matches, like the CPU: nested prefixes, listed in any order,
+// each count toward their own quota
+inline void save_matches(const uchar key[20],
+                         __global const Hash160RangeGpu *ranges,
+                         uint range_count,
+                         __global const uint *candidates,
+                         __global const SuffixGpu *suffixes,
+                         __global const uchar *wanted,
+                         uint b,
+                         uint a,
+                         uint index,
+                         __global uchar *matches_hash160,
+                         __global uint *matches_b,
+                         __global uint *matches_a,
+                         __global uint *matches_index,
+                         __global uint *matches_prefix_id,
+                         __global uint *match_count)
+{
+    long range_index = find_range(key, ranges, range_count);
+    if (range_index < 0)
+    {
+        return;
+    }
+
+    __global const Hash160RangeGpu *range = &ranges[range_index];
+    for (uint c = 0; c < range->candidate_count; c++)
+    {
+        uint prefix_id = candidates[range->first_candidate + c];
+        if (!wanted[prefix_id] || !suffix_matches(key, &suffixes[prefix_id]))
+        {
+            continue;
+        }
+
+        // MATCH! Save atomically
+        uint slot = atomic_inc(match_count);
+        if (slot < MAX_MATCHES)
+        {
+            for (int i = 0; i < 20; i++)
+            {
+                matches_hash160[slot * 20 + i] = key[i];
+            }
+
+            // Save path [b, a, index]
+            matches_b[slot] = b;
+            matches_a[slot] = a;
+            matches_index[slot] = index;
+
+            matches_prefix_id[slot] = prefix_id;
+        }
+    }
+}
+
 __kernel void batch_address_search(
     __global const CacheKey *cache_keys,
     __global const XPub *cache_values,
     __global const Hash160RangeGpu *ranges,
-    const uint range_count,
+    const uint pubkey_range_count,
     __global const uint *cache_size_buffer,  // Now a buffer instead of scalar
     const ulong start_counter,
     const uint max_depth,
@@ -69,10 +152,14 @@ __kernel void batch_address_search(
     __global uint *matches_b,
     __global uint *matches_a,
     __global uint *matches_index,
-    __global uchar *matches_prefix_id,
+    __global uint *matches_prefix_id,
     __global uint *match_count,
     __global uint *cache_miss_error,
-    __global const Point *g_times_tables)
+    __global const Point *g_times_tables,
+    __global const SuffixGpu *suffixes,
+    const uint script_range_count,
+    __global const uint *candidates,
+    __global const uchar *wanted)
 {
     uint gid = get_global_id(0);
     ulong counter = start_counter + gid;
@@ -111,36 +198,19 @@ __kernel void batch_address_search(
     uchar hash160[20];
     hash160_33(compressed_key, hash160);
 
-    // Check all ranges
-    for (uint r = 0; r < range_count; r++)
-    {
-        __global const Hash160RangeGpu *range = &ranges[r];
+    // The pubkey ranges come first, then the P2SH-P2WPKH ones, compared
+    // against the redeem script hash. Every thread sees the same ranges, so
+    // the branch does not diverge.
+    save_matches(hash160, ranges, pubkey_range_count, candidates, suffixes, wanted, b, a, index,
+                 matches_hash160, matches_b, matches_a, matches_index, matches_prefix_id,
+                 match_count);
 
-        // Check if low <= hash160 <= high
-        // this if is ok because matches are expected to be rare
-        if (hash160_gte(hash160, range->low) && hash160_lte(hash160, range->high))
-        {
-            // MATCH! Save atomically
-            uint slot = atomic_inc(match_count);
-
-            if (slot < MAX_MATCHES)
-            {
-                // Save hash160
-                for (int i = 0; i < 20; i++)
-                {
-                    matches_hash160[slot * 20 + i] = hash160[i];
-                }
-
-                // Save path [b, a, index]
-                matches_b[slot] = b;
-                matches_a[slot] = a;
-                matches_index[slot] = index;
-
-                // Save prefix_id
-                matches_prefix_id[slot] = range->prefix_id;
-            }
-
-            return; // Found match, no need to check other ranges
-        }
+    if (script_range_count > 0)
+    {
+        uchar script_hash[20];
+        hash160_redeem_script(hash160, script_hash);
+        save_matches(script_hash, ranges + pubkey_range_count, script_range_count, candidates,
+                     suffixes, wanted, b, a, index, matches_hash160, matches_b, matches_a,
+                     matches_index, matches_prefix_id, match_count);
     }
 }
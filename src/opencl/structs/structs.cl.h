@@ -52,7 +52,21 @@ typedef struct
 {
   uchar low[20];
   uchar high[20];
-  uchar prefix_id;
+  uint first_candidate;
+  uint candidate_count;
 } Hash160RangeGpu;
 
+typedef struct
+{
+  ulong modulus;
+  ulong remainder;
+  uint checksum_mask;
+  uint checksum_value;
+  uint polymod_start;
+  uint kind;
+  uint version;
+  uchar data_mask[20];
+  uchar data_value[20];
+} SuffixGpu;
+
 #endif // STRUCTS_H
\ No newline at end of file
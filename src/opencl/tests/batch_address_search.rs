@@ -2,12 +2,20 @@
 mod tests {
     use crate::extended_public_key::ExtendedPubKey;
     use crate::extended_public_key_deriver::{ExtendedPublicKeyDeriver, KeyDeriver};
+    use crate::network::Network;
     use crate::opencl::cache_preloader::CachePreloader;
     use crate::opencl::g_tables;
-    use crate::opencl::gpu_cache::{CacheKey, GpuCache, Hash160RangeGpu, PointGpu, XPub};
+    use crate::opencl::gpu_cache::{
+        CacheKey, GpuCache, GpuPrefixTable, Hash160RangeGpu, PointGpu, SuffixGpu, XPub,
+    };
+    use crate::path_template::PathTemplate;
     use crate::prefix::Prefix;
+    use crate::prefix_table::PrefixTables;
     use ocl::{Buffer, Context, Device, Kernel, Platform, Program, Queue};
 
+    /// Prefixes the test kernel has room for
+    const MAX_PREFIXES: usize = 10;
+
     fn create_test_opencl_context() -> (Device, Context, Queue) {
         let platform = Platform::first().expect("No OpenCL platform found");
         let device = Device::first(platform).expect("No OpenCL device found");
@@ -29,11 +37,14 @@ mod tests {
         matches_hash160_buffer: Buffer<u8>,
         _matches_b_buffer: Buffer<u32>, // Needed for kernel args but not read in tests
         _matches_a_buffer: Buffer<u32>, // Needed for kernel args but not read in tests
-        _matches_index_buffer: Buffer<u32>, // Needed for kernel args but not read in tests
-        _matches_prefix_id_buffer: Buffer<u8>, // Needed for kernel args but not read in tests
+        matches_index_buffer: Buffer<u32>,
+        matches_prefix_id_buffer: Buffer<u32>,
         match_count_buffer: Buffer<u32>,
         cache_miss_error_buffer: Buffer<u32>,
         _g_times_tables_buffer: Buffer<PointGpu>, // Needed for kernel args but not read in tests
+        suffixes_buffer: Buffer<SuffixGpu>,
+        candidates_buffer: Buffer<u32>,
+        _wanted_buffer: Buffer<u8>, // Needed for kernel args but not read in tests
     }
 
     impl BatchAddressSearch {
@@ -43,15 +54,24 @@ mod tests {
             let cache_keys_buffer = Self::new_buffer::<CacheKey>(&queue, 1000)?;
             let cache_values_buffer = Self::new_buffer::<XPub>(&queue, 1000)?;
             let cache_size_buffer = Self::new_buffer::<u32>(&queue, 1)?; // Create cache size buffer
-            let ranges_buffer = Self::new_buffer::<Hash160RangeGpu>(&queue, 10)?;
+            let ranges_buffer = Self::new_buffer::<Hash160RangeGpu>(&queue, 100)?;
             let matches_hash160_buffer = Self::new_buffer::<u8>(&queue, 1000 * 20)?;
             let matches_b_buffer = Self::new_buffer::<u32>(&queue, 1000)?;
             let matches_a_buffer = Self::new_buffer::<u32>(&queue, 1000)?;
             let matches_index_buffer = Self::new_buffer::<u32>(&queue, 1000)?;
-            let matches_prefix_id_buffer = Self::new_buffer::<u8>(&queue, 1000)?;
+            let matches_prefix_id_buffer = Self::new_buffer::<u32>(&queue, 1000)?;
             let match_count_buffer = Self::new_buffer::<u32>(&queue, 1)?;
             let cache_miss_error_buffer = Self::new_buffer::<u32>(&queue, 1)?;
             let g_times_tables_buffer = g_tables::create_g_tables_buffer(&queue)?;
+            let suffixes_buffer = Self::new_buffer::<SuffixGpu>(&queue, MAX_PREFIXES)?;
+            let candidates_buffer = Self::new_buffer::<u32>(&queue, 100)?;
+            // The prefixes of the tests are always wanted
+            let wanted_buffer = Buffer::<u8>::builder()
+                .queue(queue.clone())
+                .len(MAX_PREFIXES)
+                .copy_host_slice(&[1u8; MAX_PREFIXES])
+                .build()
+                .map_err(|e| format!("Error creating buffer: {}", e))?;
 
             let program = Self::build_program(device, context.clone())?;
 
@@ -64,7 +84,7 @@ mod tests {
                 .arg(&cache_keys_buffer)
                 .arg(&cache_values_buffer)
                 .arg(&ranges_buffer)
-                .arg(0u32) // range_count
+                .arg(0u32) // pubkey_range_count
                 .arg(&cache_size_buffer) // Now using buffer instead of scalar
                 .arg(0u64) // start_counter
                 .arg(0u32) // max_depth
@@ -75,7 +95,11 @@ mod tests {
                 .arg(&matches_prefix_id_buffer)
                 .arg(&match_count_buffer)
                 .arg(&cache_miss_error_buffer)
-                .arg(&g_times_tables_buffer);
+                .arg(&g_times_tables_buffer)
+                .arg(&suffixes_buffer)
+                .arg(0u32) // script_range_count
+                .arg(&candidates_buffer)
+                .arg(&wanted_buffer);
 
             // ocl's arg type check parses "Point*" as an int pointer
             // ("Point" contains "int"), rejecting the tables buffer.
@@ -96,11 +120,14 @@ mod tests {
                 matches_hash160_buffer,
                 _matches_b_buffer: matches_b_buffer,
                 _matches_a_buffer: matches_a_buffer,
-                _matches_index_buffer: matches_index_buffer,
-                _matches_prefix_id_buffer: matches_prefix_id_buffer,
+                matches_index_buffer,
+                matches_prefix_id_buffer,
                 match_count_buffer,
                 cache_miss_error_buffer,
                 _g_times_tables_buffer: g_times_tables_buffer,
+                suffixes_buffer,
+                candidates_buffer,
+                _wanted_buffer: wanted_buffer,
             })
         }
 
@@ -145,27 +172,45 @@ mod tests {
         }
 
         fn load_ranges(&mut self, prefix: &Prefix) -> Result<(), String> {
-            let mut gpu_ranges = Vec::new();
-
-            for range in &prefix.ranges {
-                gpu_ranges.push(Hash160RangeGpu {
-                    low: range.low,
-                    high: range.high,
-                    prefix_id: 0, // Single prefix in tests, always use id 0
-                });
-            }
+            // Single prefix, id 0
+            self.load_prefixes(std::slice::from_ref(prefix))
+        }
+
+        fn load_prefixes(&mut self, prefixes: &[Prefix]) -> Result<(), String> {
+            assert!(prefixes.len() <= MAX_PREFIXES);
+            let table = GpuPrefixTable::from(&PrefixTables::new(prefixes));
 
             self.ranges_buffer
-                .write(&gpu_ranges)
+                .write(&table.ranges)
                 .enq()
                 .map_err(|e| format!("Error writing ranges: {}", e))?;
 
+            self.candidates_buffer
+                .write(&table.candidates)
+                .enq()
+                .map_err(|e| format!("Error writing candidates: {}", e))?;
+
+            self.kernel
+                .set_arg(3, table.pubkey_range_count)
+                .map_err(|e| format!("Error setting pubkey_range_count: {}", e))?;
+            self.kernel
+                .set_arg(16, table.script_range_count)
+                .map_err(|e| format!("Error setting script_range_count: {}", e))?;
+
+            let suffixes: Vec<SuffixGpu> = prefixes
+                .iter()
+                .map(|prefix| SuffixGpu::from(prefix.suffix.as_ref()))
+                .collect();
+            self.suffixes_buffer
+                .write(&suffixes)
+                .enq()
+                .map_err(|e| format!("Error writing suffixes: {}", e))?;
+
             Ok(())
         }
 
         fn execute(
             &mut self,
-            range_count: u32,
             cache_size: u32,
             start_counter: u64,
             work_size: usize,
@@ -182,10 +227,6 @@ mod tests {
                 .enq()
                 .map_err(|e| format!("Error resetting cache miss counter: {}", e))?;
 
-            self.kernel
-                .set_arg(3, range_count)
-                .map_err(|e| format!("Error setting range_count: {}", e))?;
-
             // Update cache size in buffer instead of setting kernel arg
             let cache_size_data = vec![cache_size];
             self.cache_size_buffer
@@ -248,6 +289,33 @@ mod tests {
             Ok((matches, match_count[0]))
         }
 
+        /// The (index, prefix id) of every reported match
+        fn read_match_ids(&self) -> Result<Vec<(u32, u32)>, String> {
+            let mut match_count = vec![0u32; 1];
+            self.match_count_buffer
+                .read(&mut match_count)
+                .enq()
+                .map_err(|e| format!("Error reading match count: {}", e))?;
+
+            let count = (match_count[0] as usize).min(1000);
+            if count == 0 {
+                return Ok(vec![]);
+            }
+
+            let mut indexes = vec![0u32; count];
+            self.matches_index_buffer
+                .read(&mut indexes)
+                .enq()
+                .map_err(|e| format!("Error reading match indexes: {}", e))?;
+            let mut prefix_ids = vec![0u32; count];
+            self.matches_prefix_id_buffer
+                .read(&mut prefix_ids)
+                .enq()
+                .map_err(|e| format!("Error reading match prefix ids: {}", e))?;
+
+            Ok(indexes.into_iter().zip(prefix_ids).collect())
+        }
+
         fn new_buffer<T: ocl::OclPrm>(queue: &Queue, len: usize) -> Result<Buffer<T>, String> {
             Buffer::<T>::builder()
                 .queue(queue.clone())
@@ -295,9 +363,17 @@ mod tests {
         let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
 
         let cache_keys = vec![[0, 0]];
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, 0, 0).unwrap();
-
-        let prefix = Prefix::new("1").unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
+
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         assert!(!prefix.ranges.is_empty());
 
         // For now just verify setup works
@@ -309,16 +385,14 @@ mod tests {
     fn test_batch_address_search_kernel_execution() {
         let mut search = BatchAddressSearch::new().unwrap();
 
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         // Execute with minimal params (no cache, should find nothing)
         search
             .execute(
-                prefix.ranges.len() as u32,
                 0, // cache_size = 0
-                0,
-                100, // work_size
+                0, 100, // work_size
                 10000,
             )
             .unwrap();
@@ -332,12 +406,10 @@ mod tests {
     fn test_batch_address_search_impossible_prefix() {
         let mut search = BatchAddressSearch::new().unwrap();
 
-        let prefix = Prefix::new("1ZZZZZZZZZ").unwrap();
+        let prefix = Prefix::new("1ZZZZZZZZZ", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
-        search
-            .execute(prefix.ranges.len() as u32, 0, 0, 1000, 10000)
-            .unwrap();
+        search.execute(0, 0, 1000, 10000).unwrap();
 
         let (matches, count) = search.read_matches().unwrap();
         assert_eq!(count, 0);
@@ -355,7 +427,15 @@ mod tests {
 
         // Preload cache with [0, 0], [0, 1]
         let cache_keys = vec![[0, 0], [0, 1]];
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, 0, 0).unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         assert_eq!(gpu_cache.size(), 2);
 
@@ -364,14 +444,13 @@ mod tests {
         search.load_cache(&gpu_cache).unwrap();
 
         // Use broad prefix "1" (matches most addresses)
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         // Search in first 1000 addresses (covers indices 0-999 for [0,0])
         let max_depth = 10000;
         search
             .execute(
-                prefix.ranges.len() as u32,
                 gpu_cache.size() as u32,
                 0,    // start_counter
                 1000, // work_size
@@ -406,12 +485,20 @@ mod tests {
 
         // Preload cache with [b, a]
         let cache_keys = vec![[b, a]];
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, seed0, seed1).unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            seed0,
+            seed1,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         let mut search = BatchAddressSearch::new().unwrap();
         search.load_cache(&gpu_cache).unwrap();
 
-        let prefix = Prefix::new("1abc").unwrap();
+        let prefix = Prefix::new("1abc", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         let non_hardened_count = 0x7FFFFFFFu64 + 1;
@@ -420,13 +507,7 @@ mod tests {
             + (index as u64);
 
         search
-            .execute(
-                prefix.ranges.len() as u32,
-                gpu_cache.size() as u32,
-                counter,
-                1,
-                max_depth,
-            )
+            .execute(gpu_cache.size() as u32, counter, 1, max_depth)
             .unwrap();
 
         let (matches, count) = search.read_matches().unwrap();
@@ -438,6 +519,63 @@ mod tests {
         assert_eq!(matches.len(), 1);
     }
 
+    #[test]
+    fn test_batch_search_reports_every_nested_prefix_like_the_cpu() {
+        use std::collections::HashSet;
+
+        // The longer prefix listed first: an address starting with "1AB"
+        // matches both, which the CPU reports once per prefix
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let max_depth = 10000u32;
+        let prefixes = vec![
+            Prefix::new("1AB", Network::Mainnet).unwrap(),
+            Prefix::new("1A", Network::Mainnet).unwrap(),
+        ];
+
+        let (device, context, queue) = create_test_opencl_context();
+        let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &[[0, 0]],
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
+
+        let mut search = BatchAddressSearch::new().unwrap();
+        search.load_cache(&gpu_cache).unwrap();
+        search.load_prefixes(&prefixes).unwrap();
+        search
+            .execute(gpu_cache.size() as u32, 0, max_depth as usize, max_depth)
+            .unwrap();
+        assert_eq!(search.read_cache_miss_errors().unwrap(), 0);
+
+        // Ground truth: the CPU matcher over the same keys
+        let tables = PrefixTables::new(&prefixes);
+        let mut expected: HashSet<(u32, u32)> = HashSet::new();
+        for index in 0..max_depth {
+            let hash = deriver
+                .get_pubkey_hash_160(&[0, 0, 0, 0, 0, index])
+                .unwrap();
+            tables.for_each_match(&prefixes, Some(&hash), None, None, |prefix_id| {
+                expected.insert((index, prefix_id));
+            });
+        }
+        assert!(
+            expected.iter().any(|&(_, prefix_id)| prefix_id == 0),
+            "the keys must include an address matching both prefixes"
+        );
+
+        let got = search.read_match_ids().unwrap();
+        assert_eq!(got.len(), expected.len(), "a match was reported twice");
+        let got: HashSet<(u32, u32)> = got.into_iter().collect();
+        assert_eq!(got, expected);
+    }
+
     /// Ordinal of a cache key: the position of (b, a) in the global key
     /// sequence that CacheRangeAnalyzer walks.
     fn key_ordinal(b: u32, a: u32) -> u64 {
@@ -461,19 +599,26 @@ mod tests {
         let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
         let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
         let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, seed0, seed1).unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            seed0,
+            seed1,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         let mut search = BatchAddressSearch::new().unwrap();
         search.load_cache(&gpu_cache).unwrap();
 
         // Prefix "1" matches every P2PKH address: all 4 threads must report
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         let start_counter = key_ordinal(0, 0x7FFFFFFE) * max_depth as u64;
         search
             .execute(
-                prefix.ranges.len() as u32,
                 gpu_cache.size() as u32,
                 start_counter,
                 cache_keys.len(),
@@ -517,24 +662,26 @@ mod tests {
         let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
         let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
         let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, seed0, seed1).unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            seed0,
+            seed1,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         let mut search = BatchAddressSearch::new().unwrap();
         search.load_cache(&gpu_cache).unwrap();
 
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         let work_size = cache_keys.len() * max_depth as usize;
         let start_counter = key_ordinal(7, 100) * max_depth as u64;
         search
-            .execute(
-                prefix.ranges.len() as u32,
-                gpu_cache.size() as u32,
-                start_counter,
-                work_size,
-                max_depth,
-            )
+            .execute(gpu_cache.size() as u32, start_counter, work_size, max_depth)
             .unwrap();
 
         assert_eq!(search.read_cache_miss_errors().unwrap(), 0);
@@ -568,23 +715,25 @@ mod tests {
         let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
         let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
         let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, 0, 0).unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         let mut search = BatchAddressSearch::new().unwrap();
         search.load_cache(&gpu_cache).unwrap();
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         // Counter points to (3, 999): the key immediately BEFORE the cache
         let start_counter = key_ordinal(3, 999) * max_depth as u64;
         search
-            .execute(
-                prefix.ranges.len() as u32,
-                gpu_cache.size() as u32,
-                start_counter,
-                1,
-                max_depth,
-            )
+            .execute(gpu_cache.size() as u32, start_counter, 1, max_depth)
             .unwrap();
 
         let (_, count) = search.read_matches().unwrap();
@@ -602,23 +751,25 @@ mod tests {
         let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
         let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
         let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
-        CachePreloader::preload(&mut gpu_cache, &cache_keys, &mut deriver, 0, 0).unwrap();
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            0,
+            0,
+            &PathTemplate::default(),
+        )
+        .unwrap();
 
         let mut search = BatchAddressSearch::new().unwrap();
         search.load_cache(&gpu_cache).unwrap();
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         search.load_ranges(&prefix).unwrap();
 
         // 10 counters cover the cache; work_size 13 overshoots by 3 threads
         let start_counter = key_ordinal(3, 1000) * max_depth as u64;
         search
-            .execute(
-                prefix.ranges.len() as u32,
-                gpu_cache.size() as u32,
-                start_counter,
-                13,
-                max_depth,
-            )
+            .execute(gpu_cache.size() as u32, start_counter, 13, max_depth)
             .unwrap();
 
         let (_, count) = search.read_matches().unwrap();
@@ -629,4 +780,125 @@ mod tests {
             "the 3 overshooting threads must report a miss"
         );
     }
+
+    #[test]
+    fn test_batch_search_suffix_matches_cpu() {
+        use std::collections::HashSet;
+
+        // 50 addresses of one parent: the GPU must report exactly the ones
+        // whose address ends with the suffix, for both address types
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let seed0 = 42u32;
+        let seed1 = 4242u32;
+        let max_depth = 50u32;
+        let cache_keys = vec![[7u32, 100]];
+
+        let (device, context, queue) = create_test_opencl_context();
+        let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            seed0,
+            seed1,
+            &PathTemplate::default(),
+        )
+        .unwrap();
+
+        let mut search = BatchAddressSearch::new().unwrap();
+        search.load_cache(&gpu_cache).unwrap();
+
+        for pattern in ["1...a", "1...z", "bc1q...q", "bc1q...l"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            search.load_ranges(&prefix).unwrap();
+
+            let start_counter = key_ordinal(7, 100) * max_depth as u64;
+            search
+                .execute(
+                    gpu_cache.size() as u32,
+                    start_counter,
+                    max_depth as usize,
+                    max_depth,
+                )
+                .unwrap();
+
+            assert_eq!(search.read_cache_miss_errors().unwrap(), 0);
+
+            let expected: HashSet<[u8; 20]> = (0..max_depth)
+                .map(|index| {
+                    deriver
+                        .get_pubkey_hash_160(&[seed0, seed1, 7, 100, 0, index])
+                        .unwrap()
+                })
+                .filter(|hash| prefix.matches_pattern(hash))
+                .collect();
+
+            let (matches, _) = search.read_matches().unwrap();
+            let got: HashSet<[u8; 20]> = matches.into_iter().collect();
+            assert_eq!(got, expected, "GPU and CPU disagree on {}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_batch_search_p2sh_p2wpkh_matches_cpu() {
+        use std::collections::HashSet;
+
+        // P2SH-P2WPKH ranges are compared against the redeem script hash
+        let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
+        let seed0 = 42u32;
+        let seed1 = 4242u32;
+        let max_depth = 200u32;
+        let cache_keys = vec![[7u32, 100]];
+
+        let (device, context, queue) = create_test_opencl_context();
+        let mut gpu_cache = GpuCache::new(device, context, queue, 100).unwrap();
+        let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
+        let mut deriver = ExtendedPublicKeyDeriver::new(&xpub);
+        CachePreloader::preload(
+            &mut gpu_cache,
+            &cache_keys,
+            &mut deriver,
+            seed0,
+            seed1,
+            &PathTemplate::default(),
+        )
+        .unwrap();
+
+        let mut search = BatchAddressSearch::new().unwrap();
+        search.load_cache(&gpu_cache).unwrap();
+
+        for pattern in ["3", "3A", "3...a"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            search.load_ranges(&prefix).unwrap();
+
+            let start_counter = key_ordinal(7, 100) * max_depth as u64;
+            search
+                .execute(
+                    gpu_cache.size() as u32,
+                    start_counter,
+                    max_depth as usize,
+                    max_depth,
+                )
+                .unwrap();
+
+            assert_eq!(search.read_cache_miss_errors().unwrap(), 0);
+
+            let expected: HashSet<[u8; 20]> = (0..max_depth)
+                .map(|index| {
+                    let pubkey_hash = deriver
+                        .get_pubkey_hash_160(&[seed0, seed1, 7, 100, 0, index])
+                        .unwrap();
+                    deriver.get_script_hash_160(&pubkey_hash)
+                })
+                .filter(|hash| prefix.matches_pattern(hash))
+                .collect();
+            assert!(!expected.is_empty());
+
+            let (matches, _) = search.read_matches().unwrap();
+            let got: HashSet<[u8; 20]> = matches.into_iter().collect();
+            assert_eq!(got, expected, "GPU and CPU disagree on {}", pattern);
+        }
+    }
 }
@@ -1,15 +1,29 @@
+use crate::budget::{Budget, EndReason};
+use crate::checkpoint::Checkpoint;
+use crate::cpu_throttle::CpuThrottle;
+use crate::cpu_topology::CorePlan;
+use crate::descriptor::DescriptorKey;
 use crate::device_info::DeviceInfo;
-use crate::display_backend::{BenchStats, UiBackend};
+use crate::display_backend::{format_derivation_path, BenchStats, UiBackend};
 use crate::events::{EventSender, WorkbenchEvent};
 use crate::extended_public_key::ExtendedPubKey;
+use crate::gap_estimate::GapEstimator;
 use crate::ground_truth_validator::GroundTruthValidator;
+use crate::longest_match::{matched_chars, Leaderboard, LeaderboardEntry};
+use crate::path_template::KeyPath;
 use crate::prefix::Prefix;
+use crate::prefix_filter::PrefixFilter;
+use crate::quota::Quota;
+use crate::results_file::{FoundRecord, ResultsFile};
+use crate::seeds::Seeds;
 use crate::workbench::Workbench;
 use crate::workbench_config::WorkbenchConfig;
 use crate::workbench_factory::WorkbenchFactory;
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -17,13 +31,34 @@ use std::time::{Duration, Instant};
 // Orchestrator Constants
 const GRACEFUL_SHUTDOWN_TIMEOUT_SECS: u64 = 5;
 const STATUS_LOG_INTERVAL_SECS: u64 = 2;
+const CHECKPOINT_INTERVAL_SECS: u64 = 30;
 
 pub struct Orchestrator {
     xpub: ExtendedPubKey,
     prefixes: Vec<Prefix>,
+    checkpoint: Checkpoint,
+    checkpoint_path: Option<PathBuf>,
     max_depth: u32,
-    num_addresses: u32,
+    quota: Quota,
     found_addresses: u32,
+    /// Confirmed addresses of each prefix, by prefix id
+    found_by_prefix: Vec<u32>,
+    gap_estimator: Option<GapEstimator>,
+    /// Longest-match mode: the best candidates so far
+    leaderboard: Option<Leaderboard>,
+    /// Time and keys the search may spend
+    budget: Budget,
+    /// Cores of the CPU and GPU workbenches' threads
+    core_plan: CorePlan,
+    /// CPU limit of the CPU workbench, adjusted from the TUI
+    cpu_throttle: CpuThrottle,
+    /// Keys derived by all the workbenches so far
+    keys_tried: u64,
+    /// Set by the first stop condition met
+    end_reason: Option<EndReason>,
+    /// Shared with the workbenches: prefixes whose quota is met, or too
+    /// short for the leaderboard, are dropped
+    prefix_filter: Arc<PrefixFilter>,
 
     stop_signal: Arc<AtomicBool>,
 
@@ -31,55 +66,90 @@ pub struct Orchestrator {
     event_rx: Receiver<WorkbenchEvent>,
 
     ground_truth_validator: GroundTruthValidator,
+    descriptor_key: DescriptorKey,
+    results_file: Option<ResultsFile>,
+    /// Set once a found address could not be saved: the search stops
+    results_file_failed: bool,
 
     backend: Box<dyn UiBackend>,
 }
 
 impl Orchestrator {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         xpub: ExtendedPubKey,
         prefixes: Vec<Prefix>,
+        checkpoint: Checkpoint,
+        checkpoint_path: Option<PathBuf>,
         max_depth: u32,
-        num_addresses: u32,
+        quota: Quota,
+        gap_estimator: Option<GapEstimator>,
+        leaderboard: Option<Leaderboard>,
+        budget: Budget,
+        core_plan: CorePlan,
+        cpu_throttle: CpuThrottle,
         stop_signal: Arc<AtomicBool>,
         ground_truth_validator: GroundTruthValidator,
+        descriptor_key: DescriptorKey,
+        results_file: Option<ResultsFile>,
         backend: Box<dyn UiBackend>,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::channel();
+        let prefix_count = prefixes.len();
 
         Self {
             xpub,
             prefixes,
+            checkpoint,
+            checkpoint_path,
             max_depth,
-            num_addresses,
+            quota,
             found_addresses: 0,
+            found_by_prefix: vec![0; prefix_count],
+            gap_estimator,
+            leaderboard,
+            budget,
+            core_plan,
+            cpu_throttle,
+            keys_tried: 0,
+            end_reason: None,
+            prefix_filter: Arc::new(PrefixFilter::new(prefix_count)),
             stop_signal,
             event_tx,
             event_rx,
             ground_truth_validator,
+            descriptor_key,
+            results_file,
+            results_file_failed: false,
             backend,
         }
     }
 
-    pub fn run(&mut self, devices: Vec<DeviceInfo>) {
-        for device in devices.iter() {
-            self.spawn_workbench(device.clone());
+    /// Searches until a stop condition is met, which is returned
+    pub fn run(&mut self, devices: Vec<DeviceInfo>) -> EndReason {
+        // Every device searches its own part of the counter space, from
+        // where the checkpoint left it
+        for device in &devices {
+            let bench = self
+                .checkpoint
+                .bench(&device.bench_id())
+                .expect("Every device has a bench in the checkpoint");
+            let (seeds, counter_range) = (bench.seeds, bench.remaining_range());
+            self.spawn_workbench(device.clone(), seeds, counter_range);
         }
 
         let mut bench_stats: HashMap<String, BenchStats> = HashMap::new();
         let mut bench_ids: Vec<String> = Vec::new();
         let mut running_benches = devices.len();
         let mut last_log_time = Instant::now();
+        let mut last_checkpoint_time = Instant::now();
         let mut stop_time: Option<Instant> = None;
+        let deadline = self.budget.time.map(|time| Instant::now() + time);
 
         loop {
             // Check if stop was requested externally (e.g., Ctrl+C)
             if self.stop_signal.load(Ordering::Relaxed) && stop_time.is_none() {
-                self.backend.stop_requested();
-                for id in &bench_ids {
-                    self.backend.workbench_stopping(id);
-                }
-                stop_time = Some(Instant::now());
+                stop_time = Some(self.stop_workbenches(&bench_ids, EndReason::Interrupted));
             }
 
             // Use timeout after stop is requested
@@ -92,19 +162,33 @@ impl Orchestrator {
                 }
                 let remaining = timeout - elapsed;
                 self.event_rx.recv_timeout(remaining)
+            } else if let Some(deadline) = deadline {
+                match self
+                    .event_rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    Err(RecvTimeoutError::Timeout) => {
+                        // Time budget spent - stop like on Ctrl+C
+                        let time = self.budget.time.expect("The deadline comes from it");
+                        stop_time =
+                            Some(self.stop_workbenches(&bench_ids, EndReason::TimeBudget(time)));
+                        continue;
+                    }
+                    event => event,
+                }
             } else {
                 self.event_rx
                     .recv()
-                    .map_err(|_| std::sync::mpsc::RecvTimeoutError::Disconnected)
+                    .map_err(|_| RecvTimeoutError::Disconnected)
             };
 
             let event = match event {
                 Ok(e) => e,
-                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                Err(RecvTimeoutError::Timeout) => {
                     // Timeout reached - all workbenches should have stopped by now
                     break;
                 }
-                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                Err(RecvTimeoutError::Disconnected) => {
                     // Channel disconnected
                     break;
                 }
@@ -127,6 +211,10 @@ impl Orchestrator {
                         &mut bench_stats,
                         &mut last_log_time,
                     );
+
+                    if let Some(reason) = self.key_budget_spent().filter(|_| stop_time.is_none()) {
+                        stop_time = Some(self.stop_workbenches(&bench_ids, reason));
+                    }
                 }
 
                 WorkbenchEvent::PotentialMatch {
@@ -134,21 +222,33 @@ impl Orchestrator {
                     path,
                     prefix_id,
                 } => {
-                    if !self.should_stop() {
+                    if !self.is_ending() {
                         self.handle_potential_match(bench_id, path, prefix_id);
 
-                        if self.should_stop() {
-                            self.backend.stop_requested();
-                            // Notify all workbenches that they are stopping
-                            for id in &bench_ids {
-                                self.backend.workbench_stopping(id);
-                            }
-                            self.stop_signal.store(true, Ordering::Relaxed);
-                            stop_time = Some(Instant::now()); // Start 5 second timeout
+                        if self.results_file_failed && stop_time.is_none() {
+                            stop_time =
+                                Some(self.stop_workbenches(&bench_ids, EndReason::OutputFailed));
+                        } else if self.should_stop() {
+                            // Start 5 second timeout
+                            stop_time = Some(self.stop_workbenches(&bench_ids, EndReason::Found));
                         }
                     }
                 }
 
+                WorkbenchEvent::Processed {
+                    bench_id,
+                    processed_counter,
+                } => {
+                    self.handle_processed(bench_id, processed_counter);
+
+                    if last_checkpoint_time.elapsed()
+                        >= Duration::from_secs(CHECKPOINT_INTERVAL_SECS)
+                    {
+                        self.save_checkpoint();
+                        last_checkpoint_time = Instant::now();
+                    }
+                }
+
                 WorkbenchEvent::Stopped {
                     bench_id,
                     total_generated,
@@ -164,34 +264,69 @@ impl Orchestrator {
             }
         }
 
-        self.backend.final_status();
+        // Workbenches stopping on their own searched their whole range
+        let end_reason = self.end_reason.unwrap_or(EndReason::Exhausted);
+        // The previous checkpoint stays, so a resumed run finds the unsaved
+        // address again
+        if end_reason != EndReason::OutputFailed {
+            self.save_checkpoint();
+        }
+        self.backend.final_status(end_reason);
+        end_reason
+    }
+
+    /// Whether the search confirmed any address, or leaderboard candidate
+    pub fn found_any(&self) -> bool {
+        self.found_addresses > 0
     }
 
-    fn spawn_workbench(&mut self, device: DeviceInfo) {
+    /// Asks every workbench to stop, returns when it was asked
+    fn stop_workbenches(&mut self, bench_ids: &[String], reason: EndReason) -> Instant {
+        self.end_reason.get_or_insert(reason);
+        self.backend.stop_requested();
+        // Notify all workbenches that they are stopping
+        for id in bench_ids {
+            self.backend.workbench_stopping(id);
+        }
+        self.stop_signal.store(true, Ordering::Relaxed);
+        Instant::now()
+    }
+
+    fn spawn_workbench(&mut self, device: DeviceInfo, seeds: Seeds, counter_range: Range<u64>) {
         let xpub = self.xpub.clone();
         let prefixes = self.prefixes.clone();
         let max_depth = self.max_depth;
+        let template = self.checkpoint.template();
         let event_tx = self.event_tx.clone();
         let stop_signal = Arc::clone(&self.stop_signal);
-
-        // Create bench_name with device_index for GPUs
-        let bench_name = match &device {
-            DeviceInfo::Gpu { device_index, .. } => {
-                format!("{}_{}", device_index, device.name())
-            }
-            DeviceInfo::Cpu { .. } => device.name().to_string(),
+        let prefix_filter = Arc::clone(&self.prefix_filter);
+        let placement = match device {
+            DeviceInfo::Cpu { .. } => self.core_plan.cpu.clone(),
+            DeviceInfo::Gpu { .. } => self.core_plan.gpu.clone(),
         };
+        let throttle = self.cpu_throttle.clone();
+
+        let bench_name = device.bench_id();
 
         // Notify that workbench is starting
-        self.backend.workbench_starting(&bench_name);
+        self.backend.workbench_starting(&bench_name, &counter_range);
 
         let thread_name = format!("{}-bench", bench_name);
         thread::Builder::new()
             .name(thread_name.clone())
             .spawn(move || {
-                let seed0 = rand::random::<u32>() & 0x7FFFFFFF;
-                let seed1 = rand::random::<u32>() & 0x7FFFFFFF;
-                let config = WorkbenchConfig::new(xpub, prefixes, seed0, seed1, max_depth);
+                let config = WorkbenchConfig::new(
+                    xpub,
+                    prefixes,
+                    seeds.seed0,
+                    seeds.seed1,
+                    max_depth,
+                    counter_range,
+                    template,
+                )
+                .with_prefix_filter(prefix_filter)
+                .with_placement(placement)
+                .with_throttle(throttle);
                 let event_sender = EventSender::new(event_tx, bench_name);
 
                 let bench = WorkbenchFactory::create(
@@ -228,31 +363,134 @@ impl Orchestrator {
         if let Some(stats) = bench_stats.get_mut(&bench_id) {
             stats.total_generated += addresses_generated;
         }
+        self.keys_tried += addresses_generated;
 
         if last_log_time.elapsed() >= Duration::from_secs(STATUS_LOG_INTERVAL_SECS) {
             self.backend.log_status(bench_stats);
+            let addresses_per_sec: u64 = bench_stats
+                .values()
+                .map(|stats| stats.addresses_per_sec())
+                .sum();
+            if let Some(estimate) = self
+                .gap_estimator
+                .as_ref()
+                .and_then(|estimator| estimator.estimate(addresses_per_sec as f64))
+            {
+                self.backend.log_gap_estimate(&estimate);
+            }
             *last_log_time = Instant::now();
         }
     }
 
+    /// The time budget is watched by `run` while waiting for events
+    fn key_budget_spent(&self) -> Option<EndReason> {
+        self.budget
+            .keys
+            .filter(|&keys| self.keys_tried >= keys)
+            .map(EndReason::KeyBudget)
+    }
+
+    /// A longest-match search only stops once the whole target is found
     fn should_stop(&self) -> bool {
-        self.num_addresses > 0 && self.found_addresses >= self.num_addresses
+        match &self.leaderboard {
+            Some(leaderboard) => leaderboard.is_complete(),
+            None => self.quota.is_met(&self.found_by_prefix),
+        }
     }
 
-    fn handle_potential_match(&mut self, bench_id: String, path: [u32; 6], prefix_id: u8) {
-        // Get the prefix using prefix_id
-        let prefix = &self.prefixes[prefix_id as usize];
+    /// Once the run ends, matches and progress are no longer taken: a
+    /// resumed run searches their counters again
+    fn is_ending(&self) -> bool {
+        self.end_reason.is_some() || self.results_file_failed || self.should_stop()
+    }
+
+    fn handle_potential_match(&mut self, bench_id: String, path: KeyPath, prefix_id: u32) {
+        // Sent before the workbenches saw the prefix dropped
+        if self
+            .quota
+            .is_prefix_met(prefix_id as usize, &self.found_by_prefix)
+        {
+            return;
+        }
 
         // Validate and get address in one derivation to avoid double derivation
         match self
             .ground_truth_validator
-            .validate_and_get_address(prefix, &path)
+            .validate_and_get_address(&self.prefixes[prefix_id as usize], &path)
         {
             Ok(Some(address)) => {
-                // Match confirmed - log it and increment counter
+                // A candidate only counts if it makes the leaderboard, under
+                // the prefix of all the characters it shares with the target
+                let prefix_id = match &mut self.leaderboard {
+                    Some(leaderboard) => {
+                        let matched = matched_chars(&address, leaderboard.target());
+                        if !leaderboard.offer(LeaderboardEntry {
+                            address: address.clone(),
+                            matched_chars: matched,
+                            path,
+                            bench_id: bench_id.clone(),
+                        }) {
+                            return;
+                        }
+                        self.prefix_filter
+                            .drop_below(leaderboard.required_prefix_id());
+                        leaderboard.prefix_id(matched)
+                    }
+                    None => prefix_id,
+                };
+                let prefix = &self.prefixes[prefix_id as usize];
+
+                // Match confirmed - log it with its wallet and increment counter
+                let descriptor = self
+                    .descriptor_key
+                    .wallet_descriptor(&prefix.address_type, &path);
+
+                // Saved before anything else, a crash must not lose it
+                let mut save_error = None;
+                if let Some(results_file) = &mut self.results_file {
+                    let derivation_path = format_derivation_path(
+                        &path,
+                        &self.checkpoint.template(),
+                        self.descriptor_key.origin.as_ref(),
+                    );
+                    let record = FoundRecord {
+                        address: &address,
+                        address_type: prefix.address_type.name(),
+                        prefix: prefix.as_str(),
+                        derivation_path: &derivation_path,
+                        index: path.index(),
+                        bench_id: &bench_id,
+                        descriptor: &descriptor,
+                    };
+                    if let Err(e) = results_file.append(&record) {
+                        save_error = Some(format!(
+                            "Failed to save {} ({}, index {}) to '{}': {}",
+                            address,
+                            derivation_path,
+                            path.index(),
+                            results_file.path().display(),
+                            e
+                        ));
+                    }
+                }
+                // Shown even when it could not be saved, the run then stops
                 self.backend
-                    .log_found_address(&bench_id, &address, &path, prefix_id);
+                    .log_found_address(&bench_id, &address, &path, prefix_id, &descriptor);
+                if let Some(error) = save_error {
+                    self.backend.log_error(&error);
+                    self.results_file_failed = true;
+                }
+                if let Some(leaderboard) = &self.leaderboard {
+                    self.backend.log_leaderboard(leaderboard);
+                }
                 self.found_addresses += 1;
+                self.found_by_prefix[prefix_id as usize] += 1;
+                if self
+                    .quota
+                    .is_prefix_met(prefix_id as usize, &self.found_by_prefix)
+                {
+                    self.prefix_filter.drop_prefix(prefix_id);
+                }
             }
             Ok(None) => {
                 // False positive from range matching - not a real match
@@ -264,6 +502,29 @@ impl Orchestrator {
         }
     }
 
+    fn handle_processed(&mut self, bench_id: String, processed_counter: u64) {
+        // Matches past the requested number, or after the run ended, are
+        // discarded: keep them in the part a resumed run searches again
+        if self.is_ending() {
+            return;
+        }
+        self.checkpoint
+            .record_progress(&bench_id, processed_counter);
+    }
+
+    /// A failed save is only reported: the previous checkpoint stays intact
+    fn save_checkpoint(&mut self) {
+        if let Some(path) = &self.checkpoint_path {
+            if let Err(e) = self.checkpoint.save(path) {
+                self.backend.log_error(&format!(
+                    "Failed to save checkpoint '{}': {}",
+                    path.display(),
+                    e
+                ));
+            }
+        }
+    }
+
     fn handle_stopped(&mut self, bench_id: String, total_generated: u64, elapsed: Duration) {
         self.backend
             .workbench_stopped(&bench_id, total_generated, elapsed);
@@ -281,7 +542,8 @@ pub fn run_workbench(
 
     workbench.start();
 
-    while !stop_signal.load(Ordering::Relaxed) {
+    // Until a stop, or until the workbench ran out of counters
+    while !stop_signal.load(Ordering::Relaxed) && !workbench.is_done() {
         thread::sleep(Duration::from_millis(100));
     }
 
@@ -296,23 +558,65 @@ pub fn run_workbench(
 mod tests {
     use super::*;
     use crate::extended_public_key::ExtendedPubKey;
+    use crate::network::Network;
     use crate::null_backend::NullBackend;
+    use crate::path_template::PathTemplate;
 
     fn create_test_orchestrator(num_addresses: u32) -> (Orchestrator, Arc<AtomicBool>) {
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
+        create_orchestrator(
+            Quota::Total(num_addresses),
+            prefixes,
+            None,
+            Budget::default(),
+        )
+    }
+
+    fn create_orchestrator(
+        quota: Quota,
+        prefixes: Vec<Prefix>,
+        leaderboard: Option<Leaderboard>,
+        budget: Budget,
+    ) -> (Orchestrator, Arc<AtomicBool>) {
         let xpub_str = "xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn";
         let xpub = ExtendedPubKey::from_str(xpub_str).unwrap();
-        let prefixes = vec![Prefix::new("1").unwrap()];
         let stop_signal = Arc::new(AtomicBool::new(false));
-        let ground_truth_validator = GroundTruthValidator::new(xpub_str).unwrap();
+        let ground_truth_validator = GroundTruthValidator::new(xpub_str, Network::Mainnet).unwrap();
         let backend: Box<dyn UiBackend> = Box::new(NullBackend::new(Arc::clone(&stop_signal)));
+        let descriptor_key = DescriptorKey {
+            origin: None,
+            xpub: xpub_str.to_string(),
+        };
+
+        let checkpoint = Checkpoint::new(
+            xpub.fingerprint(),
+            &prefixes,
+            PathTemplate::default(),
+            10000,
+            Seeds {
+                seed0: 1000,
+                seed1: 2000,
+            },
+            &(0..u64::MAX),
+            &["cpu_2".to_string()],
+        );
 
         let orchestrator = Orchestrator::new(
             xpub,
             prefixes,
+            checkpoint,
+            None,
             10000,
-            num_addresses,
+            quota,
+            None,
+            leaderboard,
+            budget,
+            CorePlan::default(),
+            CpuThrottle::default(),
             Arc::clone(&stop_signal),
             ground_truth_validator,
+            descriptor_key,
+            None,
             backend,
         );
 
@@ -378,7 +682,7 @@ mod tests {
     #[test]
     fn test_handle_potential_match_derives_address() {
         let (mut orch, _) = create_test_orchestrator(1);
-        let path = [1000, 2000, 0, 0, 0, 0];
+        let path = KeyPath::from_slice(&[1000, 2000, 0, 0, 0, 0]);
 
         orch.handle_potential_match("cpu".to_string(), path, 0);
 
@@ -388,6 +692,91 @@ mod tests {
         assert!(orch.should_stop());
     }
 
+    #[test]
+    fn test_unsaved_result_is_still_counted_and_stops_the_search() {
+        let (mut orch, _) = create_test_orchestrator(2);
+        let results_path = std::env::temp_dir().join(format!(
+            "address-artisan-unwritable-{}.csv",
+            std::process::id()
+        ));
+        std::fs::write(&results_path, "").unwrap();
+        orch.results_file = Some(ResultsFile::read_only(&results_path));
+
+        let path = KeyPath::from_slice(&[1000, 2000, 0, 0, 0, 0]);
+        orch.handle_potential_match("cpu".to_string(), path, 0);
+
+        // Logged like any other hit, then the run stops with an error
+        assert_eq!(orch.found_addresses, 1);
+        assert!(orch.results_file_failed);
+        assert!(!orch.should_stop());
+
+        // Its counter stays in the part a resumed run searches again
+        orch.handle_processed("cpu_2".to_string(), 9000);
+        assert_eq!(orch.checkpoint.bench("cpu_2").unwrap().processed_counter, 0);
+        std::fs::remove_file(&results_path).unwrap();
+    }
+
+    #[test]
+    fn test_handle_processed_records_progress_until_stop() {
+        let (mut orch, _) = create_test_orchestrator(1);
+
+        orch.handle_processed("cpu_2".to_string(), 5000);
+        assert_eq!(
+            orch.checkpoint.bench("cpu_2").unwrap().processed_counter,
+            5000
+        );
+
+        // Once enough addresses are found, later matches are discarded
+        orch.handle_potential_match(
+            "cpu_2".to_string(),
+            KeyPath::from_slice(&[1000, 2000, 0, 0, 0, 0]),
+            0,
+        );
+        orch.handle_processed("cpu_2".to_string(), 9000);
+        assert_eq!(
+            orch.checkpoint.bench("cpu_2").unwrap().processed_counter,
+            5000
+        );
+    }
+
+    #[test]
+    fn test_nothing_is_recorded_once_the_run_ends() {
+        let (mut orch, _) = create_test_orchestrator(0);
+        orch.stop_workbenches(&[], EndReason::Interrupted);
+
+        orch.handle_processed("cpu_2".to_string(), 5000);
+        assert_eq!(orch.checkpoint.bench("cpu_2").unwrap().processed_counter, 0);
+    }
+
+    #[test]
+    fn test_key_budget() {
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
+        let budget = Budget {
+            time: None,
+            keys: Some(1000),
+        };
+        let (mut orch, _) = create_orchestrator(Quota::Total(1), prefixes, None, budget);
+        let mut bench_stats = HashMap::new();
+        let mut last_log_time = Instant::now();
+
+        // Counted over every workbench
+        orch.handle_progress("cpu".to_string(), 600, &mut bench_stats, &mut last_log_time);
+        assert_eq!(orch.key_budget_spent(), None);
+        orch.handle_progress(
+            "gpu0".to_string(),
+            400,
+            &mut bench_stats,
+            &mut last_log_time,
+        );
+        assert_eq!(orch.key_budget_spent(), Some(EndReason::KeyBudget(1000)));
+
+        orch.stop_workbenches(&[], EndReason::KeyBudget(1000));
+        // Only the first stop condition counts
+        orch.stop_workbenches(&[], EndReason::Interrupted);
+        assert_eq!(orch.end_reason, Some(EndReason::KeyBudget(1000)));
+        assert!(!orch.found_any());
+    }
+
     #[test]
     fn test_handle_stopped_does_not_panic() {
         let (mut orch, _) = create_test_orchestrator(1);
@@ -399,10 +788,17 @@ mod tests {
     fn test_spawn_workbench_sends_started_event() {
         let (mut orch, stop_signal) = create_test_orchestrator(1);
 
-        orch.spawn_workbench(DeviceInfo::Cpu {
-            name: "cpu_2".to_string(),
-            threads: 2,
-        });
+        orch.spawn_workbench(
+            DeviceInfo::Cpu {
+                name: "cpu_2".to_string(),
+                threads: 2,
+            },
+            Seeds {
+                seed0: 1000,
+                seed1: 2000,
+            },
+            0..u64::MAX,
+        );
 
         let event = orch.event_rx.recv_timeout(Duration::from_secs(2)).unwrap();
 
@@ -416,10 +812,36 @@ mod tests {
         stop_signal.store(true, Ordering::Relaxed);
     }
 
+    #[test]
+    fn test_run_ends_once_the_range_is_searched() {
+        let prefixes = vec![Prefix::new("1zzzzzz", Network::Mainnet).unwrap()];
+        let (mut orch, stop_signal) =
+            create_orchestrator(Quota::Total(1), prefixes, None, Budget::default());
+        orch.checkpoint = Checkpoint::new(
+            orch.xpub.fingerprint(),
+            &orch.prefixes,
+            PathTemplate::default(),
+            10000,
+            Seeds { seed0: 1, seed1: 2 },
+            &(0..1000),
+            &["cpu_2".to_string()],
+        );
+
+        let end_reason = orch.run(vec![DeviceInfo::Cpu {
+            name: "cpu_2".to_string(),
+            threads: 2,
+        }]);
+
+        assert_eq!(end_reason, EndReason::Exhausted);
+        assert!(!stop_signal.load(Ordering::Relaxed));
+        let bench = orch.checkpoint.bench("cpu_2").unwrap();
+        assert!(bench.remaining_range().is_empty());
+    }
+
     #[test]
     fn test_num_addresses_stops_after_one() {
         let (mut orch, _) = create_test_orchestrator(1);
-        let path = [1000, 2000, 0, 0, 0, 0];
+        let path = KeyPath::from_slice(&[1000, 2000, 0, 0, 0, 0]);
 
         // First match should trigger stop condition
         orch.handle_potential_match("cpu".to_string(), path, 0);
@@ -430,9 +852,9 @@ mod tests {
     #[test]
     fn test_num_addresses_stops_after_multiple() {
         let (mut orch, _) = create_test_orchestrator(3);
-        let path1 = [1000, 2000, 0, 0, 0, 0];
-        let path2 = [1001, 2001, 0, 0, 0, 0];
-        let path3 = [1002, 2002, 0, 0, 0, 0];
+        let path1 = KeyPath::from_slice(&[1000, 2000, 0, 0, 0, 0]);
+        let path2 = KeyPath::from_slice(&[1001, 2001, 0, 0, 0, 0]);
+        let path3 = KeyPath::from_slice(&[1002, 2002, 0, 0, 0, 0]);
 
         // First match should not trigger stop
         orch.handle_potential_match("cpu".to_string(), path1, 0);
@@ -456,7 +878,7 @@ mod tests {
 
         // Test multiple matches, should never trigger stop
         for i in 0..10 {
-            let path = [1000 + i, 2000 + i, 0, 0, 0, 0];
+            let path = KeyPath::from_slice(&[1000 + i, 2000 + i, 0, 0, 0, 0]);
             orch.handle_potential_match("cpu".to_string(), path, 0);
             assert!(!orch.should_stop(), "Should not stop at match {}", i + 1);
             assert_eq!(orch.found_addresses, i + 1);
@@ -481,7 +903,7 @@ mod tests {
             orch.event_tx
                 .send(WorkbenchEvent::PotentialMatch {
                     bench_id: "test".to_string(),
-                    path: *path,
+                    path: KeyPath::from_slice(path),
                     prefix_id: 0,
                 })
                 .unwrap();
@@ -516,4 +938,70 @@ mod tests {
             "Should indicate stop after reaching limit"
         );
     }
+
+    #[test]
+    fn test_per_prefix_quotas() {
+        let prefixes = vec![
+            Prefix::new("1", Network::Mainnet).unwrap(),
+            Prefix::new("1", Network::Mainnet).unwrap(),
+        ];
+        let (mut orch, _) = create_orchestrator(
+            Quota::PerPrefix(vec![1, 2]),
+            prefixes,
+            None,
+            Budget::default(),
+        );
+        let path = |i: u32| KeyPath::from_slice(&[1000 + i, 2000, 0, 0, 0, 0]);
+
+        // The cheap prefix is met first: dropped, the search goes on
+        orch.handle_potential_match("cpu".to_string(), path(0), 0);
+        assert!(!orch.prefix_filter.is_wanted(0));
+        assert!(!orch.should_stop());
+        orch.handle_potential_match("cpu".to_string(), path(1), 0);
+        assert_eq!(orch.found_by_prefix, vec![1, 0]);
+
+        orch.handle_potential_match("cpu".to_string(), path(2), 1);
+        assert!(!orch.should_stop());
+        orch.handle_potential_match("cpu".to_string(), path(3), 1);
+        assert_eq!(orch.found_by_prefix, vec![1, 2]);
+        assert!(orch.should_stop());
+    }
+
+    #[test]
+    fn test_longest_match_keeps_a_leaderboard() {
+        use crate::longest_match::{target_prefixes, LEADERBOARD_SIZE};
+
+        // Every P2PKH address shares at least the '1'
+        let (min_chars, prefixes) =
+            target_prefixes("1BitcoinEater", Some(1), Network::Mainnet).unwrap();
+        let leaderboard = Leaderboard::new("1BitcoinEater", min_chars);
+        let (mut orch, _) = create_orchestrator(
+            Quota::Total(1),
+            prefixes,
+            Some(leaderboard),
+            Budget::default(),
+        );
+
+        for i in 0..30 {
+            let path = KeyPath::from_slice(&[1000 + i, 2000, 0, 0, 0, 0]);
+            orch.handle_potential_match("cpu".to_string(), path, 0);
+            // The same address again, reported for another length
+            orch.handle_potential_match("cpu".to_string(), path, 0);
+        }
+
+        // Never stops on the number of addresses
+        assert!(!orch.should_stop());
+        let leaderboard = orch.leaderboard.as_ref().unwrap();
+        let entries = leaderboard.entries();
+        assert_eq!(entries.len(), LEADERBOARD_SIZE);
+        assert!(entries
+            .windows(2)
+            .all(|pair| pair[0].matched_chars >= pair[1].matched_chars));
+        assert!(orch.found_addresses >= LEADERBOARD_SIZE as u32);
+        assert!(orch.found_addresses <= 30);
+        let required = leaderboard.required_prefix_id();
+        assert!(!orch.prefix_filter.is_wanted(required - 1));
+        assert!(orch.prefix_filter.is_wanted(required));
+        assert!(leaderboard.required_chars() > 1);
+    }
 }
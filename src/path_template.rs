@@ -0,0 +1,271 @@
+use crate::constants::NON_HARDENED_MAX_INDEX;
+use crate::seeds::Seeds;
+use std::fmt;
+use std::ops::Deref;
+
+/// Most levels a searched path can have
+pub const MAX_PATH_LEVELS: usize = 8;
+
+/// A path below the xpub, stored inline so building one never allocates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyPath {
+    levels: [u32; MAX_PATH_LEVELS],
+    len: usize,
+}
+
+impl KeyPath {
+    /// Panics past `MAX_PATH_LEVELS` levels
+    pub fn from_slice(levels: &[u32]) -> Self {
+        let mut path = KeyPath {
+            levels: [0; MAX_PATH_LEVELS],
+            len: levels.len(),
+        };
+        path.levels[..levels.len()].copy_from_slice(levels);
+        path
+    }
+
+    /// The last level, the address index of the wallet
+    pub fn index(&self) -> u32 {
+        self.levels[self.len - 1]
+    }
+
+    /// Every level but the last
+    pub fn parent(&self) -> &[u32] {
+        &self.levels[..self.len - 1]
+    }
+
+    /// The path one level below, at `index`. Panics past `MAX_PATH_LEVELS`
+    /// levels.
+    pub fn child(&self, index: u32) -> KeyPath {
+        let mut child = *self;
+        child.levels[self.len] = index;
+        child.len += 1;
+        child
+    }
+}
+
+impl<const N: usize> PartialEq<[u32; N]> for KeyPath {
+    fn eq(&self, other: &[u32; N]) -> bool {
+        **self == *other
+    }
+}
+
+impl Deref for KeyPath {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        &self.levels[..self.len]
+    }
+}
+
+/// One level of a path template
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathLevel {
+    /// Always the same index, e.g. the change branch
+    Fixed(u32),
+    /// A seed of the run: `s0` or `s1`
+    Seed(usize),
+    /// Counted by the search: `i` is the address index, below the max
+    /// depth, `a` and `b` the next digits of the counter
+    Index,
+    A,
+    B,
+}
+
+/// Layout of the searched paths, e.g. `s0/s1/b/a/0/i`. The address index
+/// `i` is always the last level: the GPU derives it from a cached parent
+/// keyed by the `(b, a)` digits of the counter, whichever levels they are
+/// at. A template without `b` (or `a`) searches a smaller counter space
+/// where that digit stays 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathTemplate {
+    levels: [PathLevel; MAX_PATH_LEVELS],
+    len: usize,
+}
+
+impl PathTemplate {
+    /// Parses levels separated by '/': `s0` or `s1` for a seed, `b`, `a`
+    /// and `i` for the counted levels and a number for a fixed index
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let levels = template
+            .split('/')
+            .map(|level| match level.trim() {
+                "s0" => Ok(PathLevel::Seed(0)),
+                "s1" => Ok(PathLevel::Seed(1)),
+                "i" => Ok(PathLevel::Index),
+                "a" => Ok(PathLevel::A),
+                "b" => Ok(PathLevel::B),
+                fixed => match fixed.parse::<u32>() {
+                    Ok(index) if index <= NON_HARDENED_MAX_INDEX => Ok(PathLevel::Fixed(index)),
+                    Ok(_) => Err(format!(
+                        "Level '{}' must be <= {}: an xpub only derives non-hardened levels",
+                        fixed, NON_HARDENED_MAX_INDEX
+                    )),
+                    Err(_) => Err(format!(
+                        "Unknown level '{}', expected s0, s1, b, a, i or a number",
+                        fixed
+                    )),
+                },
+            })
+            .collect::<Result<Vec<PathLevel>, String>>()?;
+
+        if levels.len() > MAX_PATH_LEVELS {
+            return Err(format!(
+                "A path template has at most {} levels",
+                MAX_PATH_LEVELS
+            ));
+        }
+        if levels.last() != Some(&PathLevel::Index) {
+            return Err("The last level of a path template must be the address index 'i'".into());
+        }
+        for level in [PathLevel::Index, PathLevel::A, PathLevel::B] {
+            if levels.iter().filter(|l| **l == level).count() > 1 {
+                return Err(format!(
+                    "Level '{}' appears more than once",
+                    level_name(&level)
+                ));
+            }
+        }
+        if levels.contains(&PathLevel::B) && !levels.contains(&PathLevel::A) {
+            return Err("Level 'b' counts after 'a': use 'a' first".to_string());
+        }
+
+        let mut template = PathTemplate {
+            levels: [PathLevel::Index; MAX_PATH_LEVELS],
+            len: levels.len(),
+        };
+        template.levels[..levels.len()].copy_from_slice(&levels);
+        Ok(template)
+    }
+
+    pub fn levels(&self) -> &[PathLevel] {
+        &self.levels[..self.len]
+    }
+
+    /// Number of counted levels above the address index, `a` and `b`
+    pub fn outer_counted_levels(&self) -> u32 {
+        self.levels()
+            .iter()
+            .filter(|level| matches!(level, PathLevel::A | PathLevel::B))
+            .count() as u32
+    }
+
+    /// Levels derived again each time the counter moves to the next `(b, a)`
+    /// parent: `a` and every level below it but the address index
+    pub fn parent_derivations(&self) -> u32 {
+        match self
+            .levels()
+            .iter()
+            .position(|level| *level == PathLevel::A)
+        {
+            Some(a) => (self.len - 1 - a) as u32,
+            None => 0,
+        }
+    }
+
+    /// The path of the counter digits `b`, `a` and `index`
+    pub fn path(&self, seeds: &Seeds, b: u32, a: u32, index: u32) -> KeyPath {
+        let mut path = KeyPath {
+            levels: [0; MAX_PATH_LEVELS],
+            len: self.len,
+        };
+        for (value, level) in path.levels.iter_mut().zip(self.levels()) {
+            *value = match level {
+                PathLevel::Fixed(fixed) => *fixed,
+                PathLevel::Seed(0) => seeds.seed0,
+                PathLevel::Seed(_) => seeds.seed1,
+                PathLevel::Index => index,
+                PathLevel::A => a,
+                PathLevel::B => b,
+            };
+        }
+        path
+    }
+
+    /// The path of the parent shared by every address index of `(b, a)`,
+    /// the key the GPU caches
+    pub fn parent_path(&self, seeds: &Seeds, b: u32, a: u32) -> KeyPath {
+        let path = self.path(seeds, b, a, 0);
+        KeyPath::from_slice(path.parent())
+    }
+}
+
+/// The layout searched before templates existed
+impl Default for PathTemplate {
+    fn default() -> Self {
+        PathTemplate::parse("s0/s1/b/a/0/i").expect("the default template is valid")
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let levels: Vec<String> = self.levels().iter().map(level_name).collect();
+        write!(f, "{}", levels.join("/"))
+    }
+}
+
+fn level_name(level: &PathLevel) -> String {
+    match level {
+        PathLevel::Fixed(index) => index.to_string(),
+        PathLevel::Seed(seed) => format!("s{}", seed),
+        PathLevel::Index => "i".to_string(),
+        PathLevel::A => "a".to_string(),
+        PathLevel::B => "b".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: Seeds = Seeds {
+        seed0: 1000,
+        seed1: 2000,
+    };
+
+    #[test]
+    fn test_default_template_builds_classic_paths() {
+        let template = PathTemplate::default();
+
+        assert_eq!(template.to_string(), "s0/s1/b/a/0/i");
+        assert_eq!(template.outer_counted_levels(), 2);
+        assert_eq!(template.parent_derivations(), 2);
+        assert_eq!(*template.path(&SEEDS, 3, 4, 5), [1000, 2000, 3, 4, 0, 5]);
+        assert_eq!(*template.parent_path(&SEEDS, 3, 4), [1000, 2000, 3, 4, 0]);
+    }
+
+    #[test]
+    fn test_shorter_templates() {
+        let template = PathTemplate::parse("s0/a/0/i").unwrap();
+        assert_eq!(template.outer_counted_levels(), 1);
+        assert_eq!(*template.path(&SEEDS, 0, 4, 5), [1000, 4, 0, 5]);
+
+        let template = PathTemplate::parse("a/1/i").unwrap();
+        assert_eq!(*template.path(&SEEDS, 0, 4, 5), [4, 1, 5]);
+        assert_eq!(template.path(&SEEDS, 0, 4, 5).index(), 5);
+
+        let template = PathTemplate::parse("s1/i").unwrap();
+        assert_eq!(template.outer_counted_levels(), 0);
+        assert_eq!(template.parent_derivations(), 0);
+        assert_eq!(*template.path(&SEEDS, 0, 0, 5), [2000, 5]);
+        assert_eq!(template.parent_path(&SEEDS, 0, 0).len(), 1);
+    }
+
+    #[test]
+    fn test_refuses_invalid_templates() {
+        for (template, error) in [
+            ("s0/s1/b/a/i/0", "last level"),
+            ("s0/i/i", "more than once"),
+            ("s0/b/0/i", "use 'a' first"),
+            ("s0/x/i", "Unknown level 'x'"),
+            ("s0/2147483648/i", "non-hardened"),
+            ("0/0/0/0/0/0/0/0/i", "at most 8 levels"),
+        ] {
+            assert!(
+                PathTemplate::parse(template).unwrap_err().contains(error),
+                "{}",
+                template
+            );
+        }
+    }
+}
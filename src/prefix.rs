@@ -1,5 +1,27 @@
+use crate::network::Network;
 use num_bigint::BigUint;
 use num_traits::{One, ToPrimitive, Zero};
+use sha2::{Digest, Sha256};
+
+const VALID_BASE58_CHARS: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Separator between the start and the end of a pattern: `1Bob...Artisan`
+const SUFFIX_SEPARATOR: &str = "...";
+
+/// 58^10 still fits in 59 bits, so the GPU can reduce the address one nibble
+/// at a time in a ulong without overflowing
+const MAX_P2PKH_SUFFIX_LEN: usize = 10;
+/// 6 checksum characters + 32 characters for the 160 bits of the hash160
+const MAX_P2WPKH_SUFFIX_LEN: usize = 38;
+const BECH32_CHECKSUM_LEN: usize = 6;
+/// bech32 groups of a P2WPKH hash160 (160 bits) and of a P2TR x-only
+/// output key (256 bits + 4 padding bits)
+const P2WPKH_DATA_GROUPS: usize = 32;
+const P2TR_DATA_GROUPS: usize = 52;
+
+/// Every letter doubles the variants, 2^16 keeps the range computation fast
+const MAX_CASE_VARIANTS: usize = 1 << 16;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Hash160Range {
@@ -13,10 +35,97 @@ impl Hash160Range {
     }
 }
 
+/// Range of BIP86 tweaked x-only output keys, what P2TR addresses encode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputKeyRange {
+    pub low: [u8; 32],
+    pub high: [u8; 32],
+}
+
+impl OutputKeyRange {
+    pub fn new(low: [u8; 32], high: [u8; 32]) -> Self {
+        Self { low, high }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AddressType {
     P2PKH,
     P2WPKH,
+    /// BIP49 nested SegWit: P2SH wrapping the P2WPKH redeem script
+    /// `0014<hash160(pubkey)>`. Its addresses encode the hash160 of that
+    /// script, which is what its ranges are compared against.
+    P2SHP2WPKH,
+    P2TR,
+}
+
+impl AddressType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AddressType::P2PKH => "P2PKH",
+            AddressType::P2WPKH => "P2WPKH",
+            AddressType::P2SHP2WPKH => "P2SH-P2WPKH",
+            AddressType::P2TR => "P2TR",
+        }
+    }
+}
+
+/// Hash160 check for the characters at the end of an address.
+///
+/// Address endings do not map to hash160 ranges: they depend on the address
+/// checksum, so every candidate has to be checked individually.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SuffixCheck {
+    /// The base58check address read as a base58 number, modulo 58^len,
+    /// must equal `remainder`
+    Base58 {
+        version: u8,
+        modulus: u64,
+        remainder: u64,
+    },
+    /// The low bits of the hash160 hold the data characters before the
+    /// checksum; the 30-bit bech32 checksum holds the last 6 characters
+    Bech32 {
+        data_mask: [u8; 20],
+        data_value: [u8; 20],
+        checksum_mask: u32,
+        checksum_value: u32,
+        polymod_start: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suffix {
+    pub suffix_str: String,
+    pub check: SuffixCheck,
+}
+
+impl Suffix {
+    pub fn matches(&self, pubkey_hash: &[u8; 20]) -> bool {
+        match &self.check {
+            SuffixCheck::Base58 {
+                version,
+                modulus,
+                remainder,
+            } => base58_address_remainder(*version, pubkey_hash, *modulus) == *remainder,
+            SuffixCheck::Bech32 {
+                data_mask,
+                data_value,
+                checksum_mask,
+                checksum_value,
+                polymod_start,
+            } => {
+                let data_matches = pubkey_hash
+                    .iter()
+                    .zip(data_mask.iter().zip(data_value.iter()))
+                    .all(|(byte, (mask, value))| byte & mask == *value);
+
+                data_matches
+                    && bech32_checksum(pubkey_hash, *polymod_start) & checksum_mask
+                        == *checksum_value
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -24,65 +133,402 @@ pub struct Prefix {
     pub prefix_str: String,
     pub address_type: AddressType,
     pub ranges: Vec<Hash160Range>,
+    /// P2TR prefixes match output keys instead of hash160s
+    pub output_key_ranges: Vec<OutputKeyRange>,
+    pub suffix: Option<Suffix>,
+    pub case_insensitive: bool,
 }
 
 impl Prefix {
-    pub fn new(prefix_str: &str) -> Result<Self, String> {
-        if prefix_str.is_empty() {
+    pub fn new(pattern: &str, network: Network) -> Result<Self, String> {
+        let prefix = Self::parse(pattern, network)?;
+        prefix.check_reachable()?;
+        Ok(prefix)
+    }
+
+    /// Base58 prefixes can ask for a start no address of their type has,
+    /// e.g. '3z': P2SH addresses only go up to about '3R'
+    fn check_reachable(&self) -> Result<(), String> {
+        let base58 = matches!(
+            self.address_type,
+            AddressType::P2PKH | AddressType::P2SHP2WPKH
+        );
+        if base58 && self.ranges.is_empty() {
+            return Err(format!("No address can start with '{}'", self.start()));
+        }
+        Ok(())
+    }
+
+    fn parse(pattern: &str, network: Network) -> Result<Self, String> {
+        if pattern.is_empty() {
             return Err("Prefix cannot be empty".to_string());
         }
 
+        // "1Bob...Artisan" anchors "1Bob" at the start and "Artisan" at the end
+        let (prefix_str, suffix_str) = match pattern.split_once(SUFFIX_SEPARATOR) {
+            Some((start, end)) => (start, Some(end)),
+            None => (pattern, None),
+        };
+
+        let hrp = network.bech32_hrp();
+        let p2wpkh_start = format!("{}1q", hrp);
+        let p2tr_start = format!("{}1p", hrp);
+
         // Detect address type automatically and validate
-        let address_type = if let Some(bech32_part) = prefix_str.strip_prefix("bc1q") {
+        let address_type = if let Some(bech32_part) = prefix_str.strip_prefix(&p2wpkh_start) {
             // Validate bech32 characters
-            const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
             for c in bech32_part.chars() {
                 if !BECH32_CHARSET.contains(c) {
                     return Err(format!("Invalid bech32 character: '{}'", c));
                 }
             }
+            if bech32_part.len() > P2WPKH_DATA_GROUPS {
+                return Err(format!(
+                    "P2WPKH prefix can have at most {} characters after '{}'",
+                    P2WPKH_DATA_GROUPS, p2wpkh_start
+                ));
+            }
             AddressType::P2WPKH
-        } else if prefix_str.starts_with("1") {
+        } else if let Some(bech32m_part) = prefix_str.strip_prefix(&p2tr_start) {
+            // bech32m uses the same charset as bech32
+            for c in bech32m_part.chars() {
+                if !BECH32_CHARSET.contains(c) {
+                    return Err(format!("Invalid bech32m character: '{}'", c));
+                }
+            }
+            if bech32m_part.len() > P2TR_DATA_GROUPS {
+                return Err(format!(
+                    "P2TR prefix can have at most {} characters after '{}'",
+                    P2TR_DATA_GROUPS, p2tr_start
+                ));
+            }
+            AddressType::P2TR
+        } else if prefix_str.starts_with(network.p2pkh_leading_chars()) {
             // Validate base58 characters
-            const VALID_BASE58_CHARS: &str =
-                "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
             for c in prefix_str.chars() {
                 if !VALID_BASE58_CHARS.contains(c) {
                     return Err(format!("Invalid base58 character: '{}'", c));
                 }
             }
             AddressType::P2PKH
+        } else if prefix_str.starts_with(network.p2sh_leading_chars()) {
+            for c in prefix_str.chars() {
+                if !VALID_BASE58_CHARS.contains(c) {
+                    return Err(format!("Invalid base58 character: '{}'", c));
+                }
+            }
+            AddressType::P2SHP2WPKH
         } else {
-            return Err("Prefix must start with '1' (P2PKH) or 'bc1q' (P2WPKH)".to_string());
+            return Err(format!(
+                "Prefix must start with {} (P2PKH), {} (P2SH-P2WPKH), '{}' (P2WPKH) or '{}' (P2TR)",
+                quote_chars(network.p2pkh_leading_chars()),
+                quote_chars(network.p2sh_leading_chars()),
+                p2wpkh_start,
+                p2tr_start
+            ));
         };
 
         // Calculate ranges based on address type
-        let ranges = match address_type {
-            AddressType::P2PKH => Self::get_p2pkh_ranges(prefix_str),
-            AddressType::P2WPKH => Self::get_p2wpkh_ranges(prefix_str),
+        let (ranges, output_key_ranges) = match address_type {
+            AddressType::P2PKH => (
+                Self::get_base58_ranges(prefix_str, network.p2pkh_version()),
+                Vec::new(),
+            ),
+            AddressType::P2SHP2WPKH => (
+                Self::get_base58_ranges(prefix_str, network.p2sh_version()),
+                Vec::new(),
+            ),
+            AddressType::P2WPKH => (
+                Self::get_p2wpkh_ranges(&prefix_str[p2wpkh_start.len()..]),
+                Vec::new(),
+            ),
+            AddressType::P2TR => (
+                Vec::new(),
+                Self::get_p2tr_ranges(&prefix_str[p2tr_start.len()..]),
+            ),
+        };
+
+        let suffix = match suffix_str {
+            Some(suffix_str) => Some(Self::get_suffix(suffix_str, &address_type, network)?),
+            None => None,
         };
 
         Ok(Self {
-            prefix_str: prefix_str.to_string(),
+            prefix_str: pattern.to_string(),
             address_type,
-            ranges,
+            ranges: coalesce_ranges(ranges),
+            output_key_ranges,
+            suffix,
+            case_insensitive: false,
         })
     }
 
+    /// Like `new`, but any casing of the pattern matches: `1love` also
+    /// finds `1LoVe`. Base58 patterns are expanded into every valid casing
+    /// and their ranges merged; bech32 addresses are always lowercase.
+    pub fn new_case_insensitive(pattern: &str, network: Network) -> Result<Self, String> {
+        let lowercase = pattern.to_ascii_lowercase();
+        if lowercase.starts_with(&format!("{}1", network.bech32_hrp())) {
+            return Ok(Self {
+                case_insensitive: true,
+                ..Self::new(&lowercase, network)?
+            });
+        }
+
+        if pattern.contains(SUFFIX_SEPARATOR) {
+            return Err("Case-insensitive base58 patterns cannot have a suffix".to_string());
+        }
+
+        let mut address_type = AddressType::P2PKH;
+        let mut ranges = Vec::new();
+        // The first character picks the address type and is never uppercase
+        // ('m' and 'n' on test networks), only the rest is expanded
+        let lead = pattern.get(..1).unwrap_or_default().to_ascii_lowercase();
+        for variant in Self::casing_variants(&pattern[lead.len()..])? {
+            let prefix = Self::parse(&format!("{}{}", lead, variant), network)?;
+            address_type = prefix.address_type;
+            ranges.extend(prefix.ranges);
+        }
+
+        let prefix = Self {
+            prefix_str: pattern.to_string(),
+            address_type,
+            ranges: coalesce_ranges(ranges),
+            output_key_ranges: Vec::new(),
+            suffix: None,
+            case_insensitive: true,
+        };
+        prefix.check_reachable()?;
+        Ok(prefix)
+    }
+
+    /// The full pattern as given by the user, e.g. `1Bob...Artisan`
     pub fn as_str(&self) -> &str {
         &self.prefix_str
     }
 
+    /// The part of the pattern anchored at the start of the address
+    pub fn start(&self) -> &str {
+        match self.prefix_str.split_once(SUFFIX_SEPARATOR) {
+            Some((start, _)) => start,
+            None => &self.prefix_str,
+        }
+    }
+
+    /// The part of the pattern anchored at the end of the address, if any
+    pub fn end(&self) -> Option<&str> {
+        self.suffix
+            .as_ref()
+            .map(|suffix| suffix.suffix_str.as_str())
+    }
+
+    /// Whether an encoded address starts and ends like this pattern
+    pub fn matches_address(&self, address: &str) -> bool {
+        let start = self.start();
+        let start_matches = if self.case_insensitive {
+            address
+                .get(..start.len())
+                .is_some_and(|address_start| address_start.eq_ignore_ascii_case(start))
+        } else {
+            address.starts_with(start)
+        };
+
+        start_matches && self.end().is_none_or(|end| address.ends_with(end))
+    }
+
+    /// The ranges are sorted and disjoint: the last one starting at or
+    /// below the hash is the only one that can hold it
     pub fn matches_pattern(&self, pubkey_hash: &[u8; 20]) -> bool {
-        self.ranges
+        let next = self
+            .ranges
+            .partition_point(|range| range.low <= *pubkey_hash);
+        next.checked_sub(1)
+            .is_some_and(|i| *pubkey_hash <= self.ranges[i].high)
+            && self
+                .suffix
+                .as_ref()
+                .is_none_or(|suffix| suffix.matches(pubkey_hash))
+    }
+
+    /// Chance that a random key matches: the share of hash160s (or output
+    /// keys) in the ranges, times the share ending like the suffix
+    pub fn match_probability(&self) -> f64 {
+        let share = self
+            .ranges
             .iter()
-            .any(|range| pubkey_hash >= &range.low && pubkey_hash <= &range.high)
+            .map(|range| range_share(&range.low, &range.high))
+            .chain(
+                self.output_key_ranges
+                    .iter()
+                    .map(|range| range_share(&range.low, &range.high)),
+            )
+            .sum::<f64>();
+
+        share * self.suffix_share()
+    }
+
+    /// Chance that a key of the ranges also ends like the suffix
+    pub fn suffix_share(&self) -> f64 {
+        match self.suffix.as_ref().map(|suffix| &suffix.check) {
+            None => 1.0,
+            Some(SuffixCheck::Base58 { modulus, .. }) => 1.0 / *modulus as f64,
+            Some(SuffixCheck::Bech32 {
+                data_mask,
+                checksum_mask,
+                ..
+            }) => {
+                let bits = data_mask.iter().map(|b| b.count_ones()).sum::<u32>()
+                    + checksum_mask.count_ones();
+                0.5f64.powi(bits as i32)
+            }
+        }
     }
 
-    fn get_p2pkh_ranges(prefix: &str) -> Vec<Hash160Range> {
+    pub fn matches_output_key(&self, output_key: &[u8; 32]) -> bool {
+        self.output_key_ranges
+            .iter()
+            .any(|range| output_key >= &range.low && output_key <= &range.high)
+    }
+
+    fn casing_variants(pattern: &str) -> Result<Vec<String>, String> {
+        let mut choices = Vec::with_capacity(pattern.len());
+        for c in pattern.chars() {
+            let mut casings: Vec<char> = [c.to_ascii_lowercase(), c.to_ascii_uppercase()]
+                .into_iter()
+                .filter(|casing| VALID_BASE58_CHARS.contains(*casing))
+                .collect();
+            casings.dedup();
+
+            if casings.is_empty() {
+                return Err(format!("Invalid base58 character: '{}'", c));
+            }
+            choices.push(casings);
+        }
+
+        choices
+            .iter()
+            .try_fold(1usize, |count, casings| count.checked_mul(casings.len()))
+            .filter(|&count| count <= MAX_CASE_VARIANTS)
+            .ok_or_else(|| {
+                format!(
+                    "Too many casings: case-insensitive patterns can have at most {} variants",
+                    MAX_CASE_VARIANTS
+                )
+            })?;
+
+        let mut variants = vec![String::with_capacity(pattern.len())];
+        for casings in choices {
+            variants = variants
+                .into_iter()
+                .flat_map(|variant| {
+                    casings.iter().map(move |&casing| {
+                        let mut variant = variant.clone();
+                        variant.push(casing);
+                        variant
+                    })
+                })
+                .collect();
+        }
+
+        Ok(variants)
+    }
+
+    fn get_suffix(
+        suffix_str: &str,
+        address_type: &AddressType,
+        network: Network,
+    ) -> Result<Suffix, String> {
+        if suffix_str.is_empty() {
+            return Err("Suffix cannot be empty".to_string());
+        }
+
+        let check = match address_type {
+            AddressType::P2TR => {
+                return Err("P2TR patterns cannot have a suffix".to_string());
+            }
+            AddressType::P2PKH | AddressType::P2SHP2WPKH => {
+                if suffix_str.len() > MAX_P2PKH_SUFFIX_LEN {
+                    return Err(format!(
+                        "Base58 suffix can have at most {} characters",
+                        MAX_P2PKH_SUFFIX_LEN
+                    ));
+                }
+
+                let mut remainder = 0u64;
+                for c in suffix_str.chars() {
+                    let digit = VALID_BASE58_CHARS
+                        .find(c)
+                        .ok_or_else(|| format!("Invalid base58 character: '{}'", c))?;
+                    remainder = remainder * 58 + digit as u64;
+                }
+
+                let version = match address_type {
+                    AddressType::P2SHP2WPKH => network.p2sh_version(),
+                    _ => network.p2pkh_version(),
+                };
+
+                SuffixCheck::Base58 {
+                    version,
+                    modulus: 58u64.pow(suffix_str.len() as u32),
+                    remainder,
+                }
+            }
+            AddressType::P2WPKH => {
+                if suffix_str.len() > MAX_P2WPKH_SUFFIX_LEN {
+                    return Err(format!(
+                        "P2WPKH suffix can have at most {} characters",
+                        MAX_P2WPKH_SUFFIX_LEN
+                    ));
+                }
+
+                let mut digits = Vec::with_capacity(suffix_str.len());
+                for c in suffix_str.chars() {
+                    let digit = BECH32_CHARSET
+                        .find(c)
+                        .ok_or_else(|| format!("Invalid bech32 character: '{}'", c))?;
+                    digits.push(digit as u8);
+                }
+
+                // The last 6 characters are always the checksum, anything
+                // before them is the end of the hash160
+                let checksum_len = digits.len().min(BECH32_CHECKSUM_LEN);
+                let (data_digits, checksum_digits) = digits.split_at(digits.len() - checksum_len);
+
+                let mut checksum_value = 0u32;
+                for &digit in checksum_digits {
+                    checksum_value = (checksum_value << 5) | digit as u32;
+                }
+                let checksum_mask = (1u32 << (5 * checksum_len)) - 1;
+
+                let mut mask_groups = [0u8; 32];
+                let mut value_groups = [0u8; 32];
+                let first_group = 32 - data_digits.len();
+                mask_groups[first_group..].fill(31);
+                value_groups[first_group..].copy_from_slice(data_digits);
+
+                SuffixCheck::Bech32 {
+                    data_mask: groups_to_hash160(&mask_groups),
+                    data_value: groups_to_hash160(&value_groups),
+                    checksum_mask,
+                    checksum_value,
+                    polymod_start: bech32_polymod_start(network.bech32_hrp()),
+                }
+            }
+        };
+
+        Ok(Suffix {
+            suffix_str: suffix_str.to_string(),
+            check,
+        })
+    }
+
+    /// Hash160 ranges of the base58check addresses with this version byte
+    /// that start with `prefix`. Leading '1's encode leading zero bytes, so
+    /// only version 0 (P2PKH) addresses can have more than one of them.
+    fn get_base58_ranges(prefix: &str, version: u8) -> Vec<Hash160Range> {
         let mut non_ones = prefix;
         let mut ones_count = 0;
-        while non_ones.starts_with('1') {
+        while version == 0 && non_ones.starts_with('1') {
             non_ones = &non_ones[1..];
             ones_count += 1;
         }
@@ -100,17 +546,26 @@ impl Prefix {
             BigUint::zero()
         };
 
-        let ceiling_shift = 200u32 - (ones_count as u32 * 8);
-        let ceiling = (BigUint::one() << ceiling_shift) - BigUint::one();
-
-        let floor = if non_ones.is_empty() {
-            BigUint::zero()
+        // The 25 bytes (version, hash160, checksum) of every address with
+        // this version byte, or with exactly `ones_count` leading zero bytes
+        let (floor, ceiling) = if version > 0 {
+            let floor = BigUint::from(version) << 192u32;
+            let ceiling = (BigUint::from(version as u32 + 1) << 192u32) - BigUint::one();
+            (floor, ceiling)
         } else {
-            let floor_shift = 192u32 - (ones_count as u32 * 8);
-            BigUint::one() << floor_shift
+            let ceiling_shift = 200u32 - (ones_count as u32 * 8);
+            let ceiling = (BigUint::one() << ceiling_shift) - BigUint::one();
+
+            let floor = if non_ones.is_empty() {
+                BigUint::zero()
+            } else {
+                let floor_shift = 192u32 - (ones_count as u32 * 8);
+                BigUint::one() << floor_shift
+            };
+            (floor, ceiling)
         };
 
-        let mut b58pow = 0;
+        let mut b58pow = 0usize;
         let mut temp = ceiling.clone();
         let fifty_eight = BigUint::from(58u32);
         while temp >= fifty_eight {
@@ -119,7 +574,10 @@ impl Prefix {
         }
         let b58ceil = temp.to_usize().unwrap_or(0);
 
-        let k = b58pow - non_ones.len();
+        // As long as a whole address or longer, nothing left to search
+        let Some(k) = b58pow.checked_sub(non_ones.len()) else {
+            return Vec::new();
+        };
 
         let (mut low, mut high) = if n > BigUint::zero() {
             let multiplier = fifty_eight.pow(k as u32);
@@ -159,23 +617,24 @@ impl Prefix {
             low = floor.clone();
         }
 
-        low = low.max(floor.clone());
-        high = high.min(ceiling.clone());
-
-        let mut final_ranges = vec![Hash160Range::new(
-            Self::address_range_to_hash160_range(&low),
-            Self::address_range_to_hash160_range(&high),
-        )];
-
+        let mut bounds = vec![(low.max(floor.clone()), high.min(ceiling.clone()))];
         if check_upper {
-            low2 = low2.max(floor);
-            high2 = high2.min(ceiling);
-            final_ranges.push(Hash160Range::new(
-                Self::address_range_to_hash160_range(&low2),
-                Self::address_range_to_hash160_range(&high2),
-            ));
+            bounds.push((low2.max(floor), high2.min(ceiling)));
         }
 
+        // Clamping empties the ranges that lie entirely outside the version
+        // byte's addresses: no testnet address starts with 'mA'
+        let mut final_ranges: Vec<Hash160Range> = bounds
+            .into_iter()
+            .filter(|(low, high)| low <= high)
+            .map(|(low, high)| {
+                Hash160Range::new(
+                    Self::address_range_to_hash160_range(&low),
+                    Self::address_range_to_hash160_range(&high),
+                )
+            })
+            .collect();
+
         final_ranges.dedup();
         final_ranges
     }
@@ -203,21 +662,37 @@ impl Prefix {
         result
     }
 
-    fn get_p2wpkh_ranges(prefix: &str) -> Vec<Hash160Range> {
-        // bech32 charset for encoding/decoding
-        const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
-
-        // Remove 'bc1q' prefix to get the actual bech32 string
-        let bech32_prefix = if let Some(stripped) = prefix.strip_prefix("bc1q") {
-            stripped
-        } else {
-            return Vec::new(); // Invalid prefix
-        };
-
+    /// `bech32_prefix` is the part of the prefix after 'bc1q'
+    fn get_p2wpkh_ranges(bech32_prefix: &str) -> Vec<Hash160Range> {
         if bech32_prefix.is_empty() {
             return vec![Hash160Range::new([0u8; 20], [0xff; 20])];
         }
 
+        let (minimum_int, maximum_int) =
+            Self::bech32_data_bounds(bech32_prefix, P2WPKH_DATA_GROUPS);
+
+        // Convert to hash160 ranges (20 bytes)
+        let low = Self::biguint_to_20_bytes(&minimum_int);
+        let high = Self::biguint_to_20_bytes(&maximum_int);
+
+        vec![Hash160Range::new(low, high)]
+    }
+
+    /// `bech32m_prefix` is the part of the prefix after 'bc1p'
+    fn get_p2tr_ranges(bech32m_prefix: &str) -> Vec<OutputKeyRange> {
+        // The 52 groups carry 260 bits: the output key plus 4 padding bits
+        let (minimum_int, maximum_int) = Self::bech32_data_bounds(bech32m_prefix, P2TR_DATA_GROUPS);
+        let padding_bits = P2TR_DATA_GROUPS * 5 - 256;
+
+        let low = Self::biguint_to_32_bytes(&(minimum_int >> padding_bits));
+        let high = Self::biguint_to_32_bytes(&(maximum_int >> padding_bits));
+
+        vec![OutputKeyRange::new(low, high)]
+    }
+
+    /// Smallest and largest data encoded by `groups` bech32 characters that
+    /// start with `prefix`: the prefix padded with 'q' (0) and 'l' (31)
+    fn bech32_data_bounds(prefix: &str, groups: usize) -> (BigUint, BigUint) {
         let bech32_to_int = |s: &str| -> Option<BigUint> {
             let mut value = BigUint::zero();
             let base = BigUint::from(32u32);
@@ -228,19 +703,24 @@ impl Prefix {
             Some(value)
         };
 
-        // Calculate minimum and maximum by padding with 'q' (0) and 'l' (31)
-        let remaining_len = 32 - bech32_prefix.len();
-        let minimum_str = format!("{}{}", bech32_prefix, "q".repeat(remaining_len));
-        let maximum_str = format!("{}{}", bech32_prefix, "l".repeat(remaining_len));
+        let remaining_len = groups - prefix.len();
+        let minimum_str = format!("{}{}", prefix, "q".repeat(remaining_len));
+        let maximum_str = format!("{}{}", prefix, "l".repeat(remaining_len));
 
         let minimum_int = bech32_to_int(&minimum_str).unwrap_or_else(BigUint::zero);
         let maximum_int = bech32_to_int(&maximum_str).unwrap_or_else(BigUint::zero);
 
-        // Convert to hash160 ranges (20 bytes)
-        let low = Self::biguint_to_20_bytes(&minimum_int);
-        let high = Self::biguint_to_20_bytes(&maximum_int);
+        (minimum_int, maximum_int)
+    }
 
-        vec![Hash160Range::new(low, high)]
+    fn biguint_to_32_bytes(num: &BigUint) -> [u8; 32] {
+        let bytes = num.to_bytes_be();
+        let mut result = [0u8; 32];
+
+        let offset = 32 - bytes.len();
+        result[offset..].copy_from_slice(&bytes);
+
+        result
     }
 
     fn biguint_to_20_bytes(num: &BigUint) -> [u8; 20] {
@@ -254,13 +734,149 @@ impl Prefix {
     }
 }
 
+/// `['m', 'n']` as "'m' or 'n'", for error messages
+fn quote_chars(chars: &[char]) -> String {
+    chars
+        .iter()
+        .map(|c| format!("'{}'", c))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// Share of the keys from `low` to `high`, inclusive bounds of equal length
+pub fn range_share(low: &[u8], high: &[u8]) -> f64 {
+    let width = BigUint::from_bytes_be(high) - BigUint::from_bytes_be(low) + 1u32;
+    width.to_f64().unwrap_or(0.0) / 2f64.powi(8 * low.len() as i32)
+}
+
+/// Sorts the ranges and merges the ones that overlap or touch, so they can
+/// be binary searched
+fn coalesce_ranges(mut ranges: Vec<Hash160Range>) -> Vec<Hash160Range> {
+    ranges.sort_by_key(|range| range.low);
+
+    let mut coalesced: Vec<Hash160Range> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(last) if hash160_successor(&last.high).is_none_or(|next| range.low <= next) => {
+                last.high = last.high.max(range.high);
+            }
+            _ => coalesced.push(range),
+        }
+    }
+    coalesced
+}
+
+/// The hash160 right after `hash`, `None` for the highest one
+fn hash160_successor(hash: &[u8; 20]) -> Option<[u8; 20]> {
+    let mut next = *hash;
+    for byte in next.iter_mut().rev() {
+        let (value, overflow) = byte.overflowing_add(1);
+        *byte = value;
+        if !overflow {
+            return Some(next);
+        }
+    }
+    None
+}
+
+/// Base58check address (version byte, hash160 and checksum) read as a
+/// base58 number, modulo `modulus`. Reduced one nibble at a time, exactly
+/// like the GPU does, so `modulus` must stay below 2^60.
+fn base58_address_remainder(version: u8, pubkey_hash: &[u8; 20], modulus: u64) -> u64 {
+    let mut payload = [0u8; 21];
+    payload[0] = version;
+    payload[1..].copy_from_slice(pubkey_hash);
+    let checksum = Sha256::digest(Sha256::digest(payload));
+
+    let mut remainder = 0u64;
+    for &byte in payload.iter().chain(checksum[..4].iter()) {
+        remainder = ((remainder << 4) | (byte >> 4) as u64) % modulus;
+        remainder = ((remainder << 4) | (byte & 0x0f) as u64) % modulus;
+    }
+    remainder
+}
+
+fn bech32_polymod_step(checksum: u32, value: u8) -> u32 {
+    const GENERATORS: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+    let top = checksum >> 25;
+    let mut checksum = ((checksum & 0x1ffffff) << 5) ^ value as u32;
+    for (i, generator) in GENERATORS.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            checksum ^= generator;
+        }
+    }
+    checksum
+}
+
+/// Polymod state after the expanded HRP and the witness version 0, the part
+/// of the checksum input that is the same for every address
+fn bech32_polymod_start(hrp: &str) -> u32 {
+    let mut checksum = 1u32;
+    for byte in hrp.bytes() {
+        checksum = bech32_polymod_step(checksum, byte >> 5);
+    }
+    checksum = bech32_polymod_step(checksum, 0);
+    for byte in hrp.bytes() {
+        checksum = bech32_polymod_step(checksum, byte & 31);
+    }
+    bech32_polymod_step(checksum, 0)
+}
+
+/// The 30-bit bech32 (witness v0) checksum of a P2WPKH address: its 6
+/// characters, 5 bits each, first character in the highest bits
+fn bech32_checksum(pubkey_hash: &[u8; 20], polymod_start: u32) -> u32 {
+    let mut checksum = polymod_start;
+    for group in hash160_to_groups(pubkey_hash) {
+        checksum = bech32_polymod_step(checksum, group);
+    }
+    for _ in 0..BECH32_CHECKSUM_LEN {
+        checksum = bech32_polymod_step(checksum, 0);
+    }
+    checksum ^ 1
+}
+
+fn hash160_to_groups(pubkey_hash: &[u8; 20]) -> [u8; 32] {
+    let mut groups = [0u8; 32];
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    let mut group_index = 0;
+    for &byte in pubkey_hash {
+        accumulator = (accumulator << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            groups[group_index] = ((accumulator >> bits) & 31) as u8;
+            group_index += 1;
+        }
+    }
+    groups
+}
+
+fn groups_to_hash160(groups: &[u8; 32]) -> [u8; 20] {
+    let mut result = [0u8; 20];
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    let mut byte_index = 0;
+    for &group in groups {
+        accumulator = (accumulator << 5) | group as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result[byte_index] = (accumulator >> bits) as u8;
+            byte_index += 1;
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_prefix_1() {
-        let prefix = Prefix::new("1").unwrap();
+        let prefix = Prefix::new("1", Network::Mainnet).unwrap();
         assert_eq!(prefix.ranges.len(), 1);
 
         let expected_low = [
@@ -278,7 +894,7 @@ mod tests {
 
     #[test]
     fn test_prefix_1a_uppercase() {
-        let prefix = Prefix::new("1A").unwrap();
+        let prefix = Prefix::new("1A", Network::Mainnet).unwrap();
         assert_eq!(prefix.ranges.len(), 2);
 
         // Range 1
@@ -310,7 +926,7 @@ mod tests {
 
     #[test]
     fn test_prefix_1a() {
-        let prefix = Prefix::new("1a").unwrap();
+        let prefix = Prefix::new("1a", Network::Mainnet).unwrap();
         assert_eq!(prefix.ranges.len(), 1);
 
         let expected_low = [
@@ -328,7 +944,7 @@ mod tests {
 
     #[test]
     fn test_prefix_1ab() {
-        let prefix = Prefix::new("1ab").unwrap();
+        let prefix = Prefix::new("1ab", Network::Mainnet).unwrap();
         assert_eq!(prefix.ranges.len(), 1);
 
         let expected_low = [
@@ -346,7 +962,7 @@ mod tests {
 
     #[test]
     fn test_prefix_1seaasses_uppercase() {
-        let prefix = Prefix::new("1SEAASSES").unwrap();
+        let prefix = Prefix::new("1SEAASSES", Network::Mainnet).unwrap();
         assert_eq!(prefix.ranges.len(), 1);
 
         let expected_low = [
@@ -365,19 +981,19 @@ mod tests {
     // P2WPKH tests
     #[test]
     fn test_address_type_detection_p2pkh() {
-        let prefix = Prefix::new("1abc").unwrap();
+        let prefix = Prefix::new("1abc", Network::Mainnet).unwrap();
         assert_eq!(prefix.address_type, AddressType::P2PKH);
     }
 
     #[test]
     fn test_address_type_detection_p2wpkh() {
-        let prefix = Prefix::new("bc1qaaa").unwrap();
+        let prefix = Prefix::new("bc1qaaa", Network::Mainnet).unwrap();
         assert_eq!(prefix.address_type, AddressType::P2WPKH);
     }
 
     #[test]
     fn test_p2wpkh_prefix_all_addresses() {
-        let prefix = Prefix::new("bc1q").unwrap();
+        let prefix = Prefix::new("bc1q", Network::Mainnet).unwrap();
         assert_eq!(prefix.address_type, AddressType::P2WPKH);
         assert_eq!(prefix.ranges.len(), 1);
 
@@ -390,7 +1006,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_prefix_aaa() {
-        let prefix = Prefix::new("bc1qaaa").unwrap();
+        let prefix = Prefix::new("bc1qaaa", Network::Mainnet).unwrap();
         assert_eq!(prefix.address_type, AddressType::P2WPKH);
         assert_eq!(prefix.ranges.len(), 1);
 
@@ -410,7 +1026,7 @@ mod tests {
 
     #[test]
     fn test_p2wpkh_prefix_xyz() {
-        let prefix = Prefix::new("bc1qxyz").unwrap();
+        let prefix = Prefix::new("bc1qxyz", Network::Mainnet).unwrap();
         assert_eq!(prefix.address_type, AddressType::P2WPKH);
         assert_eq!(prefix.ranges.len(), 1);
 
@@ -431,39 +1047,584 @@ mod tests {
     // Validation tests
     #[test]
     fn test_invalid_prefix_empty() {
-        let result = Prefix::new("");
+        let result = Prefix::new("", Network::Mainnet);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("cannot be empty"));
     }
 
     #[test]
     fn test_invalid_prefix_wrong_start() {
-        let result = Prefix::new("3abc");
+        let result = Prefix::new("2abc", Network::Mainnet);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("must start with"));
     }
 
     #[test]
     fn test_invalid_p2pkh_character() {
-        let result = Prefix::new("1abc0"); // '0' is not valid in base58
+        let result = Prefix::new("1abc0", Network::Mainnet); // '0' is not valid in base58
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Invalid base58 character"));
     }
 
     #[test]
     fn test_invalid_p2wpkh_character() {
-        let result = Prefix::new("bc1qabc"); // 'b' and 'c' are not valid in bech32
+        let result = Prefix::new("bc1qabc", Network::Mainnet); // 'b' and 'c' are not valid in bech32
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Invalid bech32 character"));
     }
 
+    #[test]
+    fn test_p2wpkh_prefix_too_long() {
+        assert!(Prefix::new(&format!("bc1q{}", "q".repeat(32)), Network::Mainnet).is_ok());
+        assert!(
+            Prefix::new(&format!("bc1q{}", "q".repeat(33)), Network::Mainnet)
+                .unwrap_err()
+                .contains("at most 32 characters")
+        );
+    }
+
+    // Suffix tests
+    const P2PKH_ADDRESS: &str = "16EhLAUerc8rnmdHvBh1ABjEsddTom3FyZ";
+    const P2WPKH_ADDRESS: &str = "bc1q89hm8k39a388dju9fysuk6dsm6eerzj860sxx4";
+
+    fn p2pkh_hash160(address: &str) -> [u8; 20] {
+        let bytes = bs58::decode(address).into_vec().unwrap();
+        bytes[1..21].try_into().unwrap()
+    }
+
+    fn p2wpkh_hash160(address: &str) -> [u8; 20] {
+        let address = address
+            .parse::<bitcoin::Address<bitcoin::address::NetworkUnchecked>>()
+            .unwrap()
+            .assume_checked();
+        address.script_pubkey().as_bytes()[2..22]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_suffix_pattern_parsing() {
+        let prefix = Prefix::new("1Bob...Artisan", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2PKH);
+        assert_eq!(prefix.as_str(), "1Bob...Artisan");
+        assert_eq!(prefix.start(), "1Bob");
+        assert_eq!(prefix.end(), Some("Artisan"));
+        assert_eq!(
+            prefix.ranges,
+            Prefix::new("1Bob", Network::Mainnet).unwrap().ranges
+        );
+
+        let prefix = Prefix::new("bc1q...xyz", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2WPKH);
+        assert_eq!(prefix.start(), "bc1q");
+        assert_eq!(prefix.end(), Some("xyz"));
+
+        let prefix = Prefix::new("1abc", Network::Mainnet).unwrap();
+        assert_eq!(prefix.start(), "1abc");
+        assert_eq!(prefix.end(), None);
+    }
+
+    #[test]
+    fn test_p2pkh_suffix_remainder() {
+        let prefix = Prefix::new("1...zz", Network::Mainnet).unwrap();
+        let suffix = prefix.suffix.unwrap();
+        assert_eq!(
+            suffix.check,
+            SuffixCheck::Base58 {
+                version: 0x00,
+                modulus: 58 * 58,
+                remainder: 57 * 58 + 57,
+            }
+        );
+    }
+
+    #[test]
+    fn test_p2pkh_suffix_matches() {
+        let hash = p2pkh_hash160(P2PKH_ADDRESS);
+
+        for pattern in ["1...Z", "1...FyZ", "1...Tom3FyZ", "16Eh...3FyZ"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            assert!(prefix.matches_pattern(&hash), "{} should match", pattern);
+        }
+
+        for pattern in ["1...Y", "1...FzZ", "1...1om3FyZ", "17...FyZ"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            assert!(
+                !prefix.matches_pattern(&hash),
+                "{} should not match",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_p2wpkh_suffix_matches() {
+        let hash = p2wpkh_hash160(P2WPKH_ADDRESS);
+
+        // Up to 6 characters only touch the checksum, more reach the hash160
+        for pattern in [
+            "bc1q...4",
+            "bc1q...sxx4",
+            "bc1q...60sxx4",
+            "bc1q...j860sxx4",
+        ] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            assert!(prefix.matches_pattern(&hash), "{} should match", pattern);
+        }
+
+        for pattern in ["bc1q...5", "bc1q...qxx4", "bc1q...q0sxx4", "bc1qq...4"] {
+            let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+            assert!(
+                !prefix.matches_pattern(&hash),
+                "{} should not match",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_suffix_matches_agree_with_addresses() {
+        use bitcoin::hashes::Hash;
+        use rand::Rng;
+
+        let mut rng = rand::rng();
+        let p2pkh_suffixes = ["1...A", "1...1", "1...z", "1...Ab"];
+        let p2wpkh_suffixes = ["bc1q...q", "bc1q...l", "bc1q...qp", "bc1q...ac8m9d0"];
+
+        for _ in 0..2000 {
+            let hash: [u8; 20] = rng.random();
+            let pubkey_hash = bitcoin::PubkeyHash::from_byte_array(hash);
+            let program = bitcoin::WitnessProgram::new(bitcoin::WitnessVersion::V0, &hash).unwrap();
+            let p2pkh = bitcoin::Address::p2pkh(pubkey_hash, bitcoin::NetworkKind::Main);
+            let p2wpkh =
+                bitcoin::Address::from_witness_program(program, bitcoin::KnownHrp::Mainnet);
+
+            for pattern in p2pkh_suffixes {
+                let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+                assert_eq!(
+                    prefix.matches_pattern(&hash),
+                    p2pkh.to_string().ends_with(prefix.end().unwrap()),
+                    "{} disagrees with {}",
+                    pattern,
+                    p2pkh
+                );
+            }
+
+            for pattern in p2wpkh_suffixes {
+                let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+                assert_eq!(
+                    prefix.matches_pattern(&hash),
+                    p2wpkh.to_string().ends_with(prefix.end().unwrap()),
+                    "{} disagrees with {}",
+                    pattern,
+                    p2wpkh
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_suffix() {
+        assert!(Prefix::new("1...", Network::Mainnet)
+            .unwrap_err()
+            .contains("Suffix cannot be empty"));
+        assert!(Prefix::new("1...0", Network::Mainnet)
+            .unwrap_err()
+            .contains("Invalid base58 character"));
+        assert!(Prefix::new("bc1q...b", Network::Mainnet)
+            .unwrap_err()
+            .contains("Invalid bech32 character"));
+        assert!(Prefix::new("1...ABCDEFGHJKL", Network::Mainnet)
+            .unwrap_err()
+            .contains("at most 10 characters"));
+    }
+
+    // P2TR tests
+    #[test]
+    fn test_p2tr_prefix_parsing() {
+        let prefix = Prefix::new("bc1p", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2TR);
+        assert!(prefix.ranges.is_empty());
+        assert_eq!(
+            prefix.output_key_ranges,
+            vec![OutputKeyRange::new([0u8; 32], [0xff; 32])]
+        );
+
+        // 'x' is 6 (00110): the output key starts with those 5 bits
+        let prefix = Prefix::new("bc1px", Network::Mainnet).unwrap();
+        let mut low = [0u8; 32];
+        let mut high = [0xff; 32];
+        low[0] = 0b0011_0000;
+        high[0] = 0b0011_0111;
+        assert_eq!(
+            prefix.output_key_ranges,
+            vec![OutputKeyRange::new(low, high)]
+        );
+    }
+
+    #[test]
+    fn test_p2tr_matches_agree_with_addresses() {
+        use rand::Rng;
+
+        let mut rng = rand::rng();
+        let patterns = ["bc1pq", "bc1pl", "bc1pxy", "bc1p5"];
+
+        for _ in 0..2000 {
+            let output_key: [u8; 32] = rng.random();
+            let program =
+                bitcoin::WitnessProgram::new(bitcoin::WitnessVersion::V1, &output_key).unwrap();
+            let address =
+                bitcoin::Address::from_witness_program(program, bitcoin::KnownHrp::Mainnet)
+                    .to_string();
+
+            for pattern in patterns {
+                let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+                assert_eq!(
+                    prefix.matches_output_key(&output_key),
+                    address.starts_with(pattern),
+                    "{} disagrees with {}",
+                    pattern,
+                    address
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_p2tr() {
+        assert!(Prefix::new("bc1pb", Network::Mainnet)
+            .unwrap_err()
+            .contains("Invalid bech32m character"));
+        assert!(Prefix::new("bc1pxyz...q", Network::Mainnet)
+            .unwrap_err()
+            .contains("P2TR patterns cannot have a suffix"));
+        assert!(
+            Prefix::new(&format!("bc1p{}", "q".repeat(53)), Network::Mainnet)
+                .unwrap_err()
+                .contains("at most 52 characters")
+        );
+    }
+
+    // Case-insensitive tests
+    #[test]
+    fn test_casing_variants() {
+        let mut variants = Prefix::casing_variants("1ab").unwrap();
+        variants.sort();
+        assert_eq!(variants, vec!["1AB", "1Ab", "1aB", "1ab"]);
+
+        // 'l' and 'I' only exist in one casing, digits in none
+        assert_eq!(Prefix::casing_variants("1li").unwrap(), vec!["1Li"]);
+        assert!(Prefix::casing_variants("10")
+            .unwrap_err()
+            .contains("Invalid base58 character"));
+    }
+
+    #[test]
+    fn test_coalesce_ranges() {
+        let range = |low: u8, high: u8| {
+            let mut low_bytes = [0u8; 20];
+            let mut high_bytes = [0u8; 20];
+            low_bytes[19] = low;
+            high_bytes[19] = high;
+            Hash160Range::new(low_bytes, high_bytes)
+        };
+
+        // Overlapping and adjacent ranges merge, the gap at 9 stays
+        let ranges = vec![range(10, 20), range(0, 3), range(15, 30), range(4, 8)];
+        assert_eq!(coalesce_ranges(ranges), vec![range(0, 8), range(10, 30)]);
+
+        let full = Hash160Range::new([0u8; 20], [0xff; 20]);
+        assert_eq!(coalesce_ranges(vec![full.clone(), range(1, 2)]), vec![full]);
+    }
+
+    #[test]
+    fn test_case_insensitive_ranges_are_coalesced() {
+        let prefix = Prefix::new_case_insensitive("1ab", Network::Mainnet).unwrap();
+        assert!(prefix.case_insensitive);
+        assert_eq!(prefix.as_str(), "1ab");
+
+        let variant_ranges: usize = ["1ab", "1aB", "1Ab", "1AB"]
+            .iter()
+            .map(|variant| Prefix::new(variant, Network::Mainnet).unwrap().ranges.len())
+            .sum();
+        assert!(prefix.ranges.len() <= variant_ranges);
+        assert!(prefix
+            .ranges
+            .windows(2)
+            .all(|pair| pair[0].high < pair[1].low));
+    }
+
+    #[test]
+    fn test_case_insensitive_matches_agree_with_addresses() {
+        use bitcoin::hashes::Hash;
+        use rand::Rng;
+
+        let mut rng = rand::rng();
+        let prefix = Prefix::new_case_insensitive("1a", Network::Mainnet).unwrap();
+
+        for _ in 0..2000 {
+            let hash: [u8; 20] = rng.random();
+            let pubkey_hash = bitcoin::PubkeyHash::from_byte_array(hash);
+            let address =
+                bitcoin::Address::p2pkh(pubkey_hash, bitcoin::NetworkKind::Main).to_string();
+
+            assert_eq!(
+                prefix.matches_pattern(&hash),
+                address.starts_with("1a") || address.starts_with("1A"),
+                "1a (any case) disagrees with {}",
+                address
+            );
+            assert_eq!(
+                prefix.matches_pattern(&hash),
+                prefix.matches_address(&address)
+            );
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_love() {
+        let prefix = Prefix::new_case_insensitive("1love", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2PKH);
+
+        assert!(prefix.matches_address("1LoveXXXXXXXXXXXXXXXXXXXXXXXXXXXX"));
+        assert!(prefix.matches_address("1LOVEXXXXXXXXXXXXXXXXXXXXXXXXXXXX"));
+        assert!(!prefix.matches_address("1LiveXXXXXXXXXXXXXXXXXXXXXXXXXXXX"));
+    }
+
+    #[test]
+    fn test_case_insensitive_p2wpkh_is_lowercased() {
+        let prefix = Prefix::new_case_insensitive("BC1QXYZ...SXX4", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2WPKH);
+        assert_eq!(prefix.start(), "bc1qxyz");
+        assert_eq!(prefix.end(), Some("sxx4"));
+        assert_eq!(
+            prefix.ranges,
+            Prefix::new("bc1qxyz", Network::Mainnet).unwrap().ranges
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_invalid() {
+        assert!(Prefix::new_case_insensitive("1abc...xyz", Network::Mainnet)
+            .unwrap_err()
+            .contains("cannot have a suffix"));
+        assert!(Prefix::new_case_insensitive("2abc", Network::Mainnet)
+            .unwrap_err()
+            .contains("Prefix must start with"));
+        assert!(
+            Prefix::new_case_insensitive("1abcdefghjkmnpqrstuvwxyz", Network::Mainnet)
+                .unwrap_err()
+                .contains("Too many casings")
+        );
+    }
+
+    // P2SH-P2WPKH tests
+    #[test]
+    fn test_address_type_detection_p2sh_p2wpkh() {
+        let prefix = Prefix::new("3Bob", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2SHP2WPKH);
+        assert!(!prefix.ranges.is_empty());
+        assert_ne!(
+            prefix.ranges,
+            Prefix::new("1Bob", Network::Mainnet).unwrap().ranges
+        );
+    }
+
+    #[test]
+    fn test_p2sh_p2wpkh_prefix_all_addresses() {
+        // Every version 0x05 address starts with '3'
+        let prefix = Prefix::new("3", Network::Mainnet).unwrap();
+        assert_eq!(
+            prefix.ranges,
+            vec![Hash160Range::new([0u8; 20], [0xff; 20])]
+        );
+    }
+
+    #[test]
+    fn test_p2sh_p2wpkh_matches_agree_with_addresses() {
+        use bitcoin::hashes::Hash;
+        use rand::Rng;
+
+        let mut rng = rand::rng();
+        let patterns = ["3A", "3J9", "3Nx", "3R", "3...A", "3E...z"];
+
+        for _ in 0..2000 {
+            let hash: [u8; 20] = rng.random();
+            let script_hash = bitcoin::ScriptHash::from_byte_array(hash);
+            let address = bitcoin::Address::p2sh_from_hash(script_hash, bitcoin::NetworkKind::Main)
+                .to_string();
+
+            for pattern in patterns {
+                let prefix = Prefix::new(pattern, Network::Mainnet).unwrap();
+                assert_eq!(
+                    prefix.matches_pattern(&hash),
+                    prefix.matches_address(&address),
+                    "{} disagrees with {}",
+                    pattern,
+                    address
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_p2sh_p2wpkh() {
+        // P2SH addresses only reach about "3R" on their second character
+        assert!(Prefix::new("3z", Network::Mainnet)
+            .unwrap_err()
+            .contains("No address can start with '3z'"));
+        assert!(Prefix::new("3abc0", Network::Mainnet)
+            .unwrap_err()
+            .contains("Invalid base58 character"));
+        assert!(Prefix::new("3...ABCDEFGHJKL", Network::Mainnet)
+            .unwrap_err()
+            .contains("at most 10 characters"));
+    }
+
+    #[test]
+    fn test_case_insensitive_p2sh_p2wpkh() {
+        let prefix = Prefix::new_case_insensitive("3job", Network::Mainnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2SHP2WPKH);
+        assert!(prefix.matches_address("3JoBXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX"));
+        assert!(!prefix.matches_address("1JoBXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX"));
+
+        // Some casings are out of reach ('3q'), the others still match
+        let prefix = Prefix::new_case_insensitive("3q", Network::Mainnet).unwrap();
+        assert_eq!(
+            prefix.ranges,
+            Prefix::new("3Q", Network::Mainnet).unwrap().ranges
+        );
+        assert!(Prefix::new_case_insensitive("3z", Network::Mainnet)
+            .unwrap_err()
+            .contains("No address can start with"));
+    }
+
+    // Test network tests
+    #[test]
+    fn test_testnet_prefix_parsing() {
+        let testnet = Network::Testnet;
+        for (pattern, address_type) in [
+            ("mxb", AddressType::P2PKH),
+            ("n", AddressType::P2PKH),
+            ("2N", AddressType::P2SHP2WPKH),
+            ("tb1qxy", AddressType::P2WPKH),
+            ("tb1pxy", AddressType::P2TR),
+        ] {
+            let prefix = Prefix::new(pattern, testnet).unwrap();
+            assert_eq!(prefix.address_type, address_type, "{}", pattern);
+        }
+
+        let prefix = Prefix::new("bcrt1qxy", Network::Regtest).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2WPKH);
+        assert_eq!(
+            prefix.ranges,
+            Prefix::new("bc1qxy", Network::Mainnet).unwrap().ranges
+        );
+
+        let error = Prefix::new("1abc", testnet).unwrap_err();
+        assert!(error.contains("'m' or 'n' (P2PKH), '2' (P2SH-P2WPKH), 'tb1q' (P2WPKH)"));
+        assert!(Prefix::new("bc1qxy", testnet).is_err());
+        // Testnet P2PKH addresses go from about 'mfW' to 'n4r'
+        assert!(Prefix::new("mA", testnet)
+            .unwrap_err()
+            .contains("No address can start with 'mA'"));
+        assert!(Prefix::new("tb1qxy", Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_testnet_matches_agree_with_addresses() {
+        use bitcoin::hashes::Hash;
+        use rand::Rng;
+
+        let mut rng = rand::rng();
+        let patterns = [
+            (Network::Testnet, "mx"),
+            (Network::Testnet, "n1"),
+            (Network::Testnet, "mz...A"),
+            (Network::Signet, "2N"),
+            (Network::Signet, "2Mz"),
+            (Network::Signet, "2...z"),
+            (Network::Testnet, "tb1qxy"),
+            (Network::Signet, "tb1q...q"),
+            (Network::Regtest, "bcrt1q...a8"),
+        ];
+
+        for _ in 0..2000 {
+            let hash: [u8; 20] = rng.random();
+
+            for (network, pattern) in patterns {
+                let prefix = Prefix::new(pattern, network).unwrap();
+                let bitcoin_network = bitcoin::Network::from(network);
+                let address = match prefix.address_type {
+                    AddressType::P2PKH => bitcoin::Address::p2pkh(
+                        bitcoin::PubkeyHash::from_byte_array(hash),
+                        bitcoin_network,
+                    ),
+                    AddressType::P2SHP2WPKH => bitcoin::Address::p2sh_from_hash(
+                        bitcoin::ScriptHash::from_byte_array(hash),
+                        bitcoin_network,
+                    ),
+                    _ => {
+                        let program =
+                            bitcoin::WitnessProgram::new(bitcoin::WitnessVersion::V0, &hash)
+                                .unwrap();
+                        bitcoin::Address::from_witness_program(program, bitcoin_network)
+                    }
+                }
+                .to_string();
+
+                assert_eq!(
+                    prefix.matches_pattern(&hash),
+                    prefix.matches_address(&address),
+                    "{} disagrees with {}",
+                    pattern,
+                    address
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_testnet() {
+        let prefix = Prefix::new_case_insensitive("MxY", Network::Testnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2PKH);
+        assert!(prefix.matches_address("mXyXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX"));
+
+        let prefix = Prefix::new_case_insensitive("TB1QXY", Network::Testnet).unwrap();
+        assert_eq!(prefix.address_type, AddressType::P2WPKH);
+        assert_eq!(prefix.start(), "tb1qxy");
+    }
+
+    #[test]
+    fn test_match_probability() {
+        let close = |a: f64, b: f64| (a - b).abs() <= b * 1e-9;
+
+        // Every bech32 character after the witness version holds 5 bits
+        let prefix = Prefix::new("bc1qxy", Network::Mainnet).unwrap();
+        assert!(close(prefix.match_probability(), 1.0 / 1024.0));
+        let prefix = Prefix::new("bc1px", Network::Mainnet).unwrap();
+        assert!(close(prefix.match_probability(), 1.0 / 32.0));
+        assert!(close(
+            Prefix::new("bc1q...q", Network::Mainnet)
+                .unwrap()
+                .match_probability(),
+            1.0 / 32.0
+        ));
+
+        // The casings of a case-insensitive prefix add up
+        let exact = Prefix::new("1ab", Network::Mainnet).unwrap();
+        let any_case = Prefix::new_case_insensitive("1ab", Network::Mainnet).unwrap();
+        assert!(any_case.match_probability() > 3.0 * exact.match_probability());
+        assert!(exact.match_probability() > 0.0);
+    }
+
     #[test]
     fn test_p2wpkh_valid_charset() {
         // All these should be valid bech32 characters
         let valid_chars = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
         for ch in valid_chars.chars() {
             let prefix_str = format!("bc1q{}", ch);
-            let result = Prefix::new(&prefix_str);
+            let result = Prefix::new(&prefix_str, Network::Mainnet);
             assert!(
                 result.is_ok(),
                 "Character '{}' should be valid in bech32",
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// The prefixes the workbenches still report, shared with the orchestrator
+/// that drops them during the search: a prefix whose quota is met, or the
+/// lengths the longest-match leaderboard no longer takes. A dropped prefix
+/// stays in the range tables, its matches are just not sent.
+#[derive(Debug)]
+pub struct PrefixFilter {
+    wanted: Vec<AtomicBool>,
+    /// Bumped on every change, so the GPU only uploads new flags
+    generation: AtomicU32,
+}
+
+impl PrefixFilter {
+    /// Every prefix wanted
+    pub fn new(prefix_count: usize) -> Self {
+        PrefixFilter {
+            wanted: (0..prefix_count).map(|_| AtomicBool::new(true)).collect(),
+            generation: AtomicU32::new(0),
+        }
+    }
+
+    pub fn is_wanted(&self, prefix_id: u32) -> bool {
+        self.wanted
+            .get(prefix_id as usize)
+            .is_some_and(|wanted| wanted.load(Ordering::Relaxed))
+    }
+
+    pub fn drop_prefix(&self, prefix_id: u32) {
+        if let Some(wanted) = self.wanted.get(prefix_id as usize) {
+            if wanted.swap(false, Ordering::Relaxed) {
+                self.generation.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Drops the prefixes of lower ids than `prefix_id`
+    pub fn drop_below(&self, prefix_id: u32) {
+        for id in 0..prefix_id {
+            self.drop_prefix(id);
+        }
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// One byte per prefix id, 1 when wanted, for the GPU kernel
+    pub fn flags(&self) -> Vec<u8> {
+        self.wanted
+            .iter()
+            .map(|wanted| u8::from(wanted.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropped_prefixes() {
+        let filter = PrefixFilter::new(4);
+        assert_eq!(filter.flags(), vec![1, 1, 1, 1]);
+
+        filter.drop_prefix(2);
+        assert!(!filter.is_wanted(2));
+        assert_eq!(filter.generation(), 1);
+        // Already dropped: nothing changes
+        filter.drop_prefix(2);
+        assert_eq!(filter.generation(), 1);
+
+        filter.drop_below(2);
+        assert_eq!(filter.flags(), vec![0, 0, 0, 1]);
+        assert_eq!(filter.generation(), 3);
+        assert!(!filter.is_wanted(4));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::prefix::{AddressType, Prefix};
+use std::collections::BTreeMap;
+
+/// A range of the table and where its prefix ids start in `candidates`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableRange<const N: usize> {
+    pub low: [u8; N],
+    pub high: [u8; N],
+    pub first_candidate: u32,
+    pub candidate_count: u32,
+}
+
+/// The ranges of many prefixes split where they overlap, so that no two
+/// ranges of the table intersect. Sorted by their low bound, a key is looked
+/// up by binary search whatever the number of prefixes. Each range lists the
+/// prefixes covering it: usually one, several when a word starts another.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangeTable<const N: usize> {
+    ranges: Vec<TableRange<N>>,
+    candidates: Vec<u32>,
+}
+
+impl<const N: usize> RangeTable<N> {
+    /// `entries` are `(low, high, prefix_id)`, inclusive bounds
+    pub fn new(entries: &[([u8; N], [u8; N], u32)]) -> Self {
+        // The set of covering prefixes can only change at these points
+        let mut points: Vec<[u8; N]> = entries
+            .iter()
+            .flat_map(|(low, high, _)| [Some(*low), successor(high)])
+            .flatten()
+            .collect();
+        points.sort_unstable();
+        points.dedup();
+
+        let mut starts: Vec<&([u8; N], [u8; N], u32)> = entries.iter().collect();
+        starts.sort_unstable_by_key(|(low, _, _)| *low);
+        let mut ends: Vec<([u8; N], u32)> = entries
+            .iter()
+            .filter_map(|(_, high, prefix_id)| Some((successor(high)?, *prefix_id)))
+            .collect();
+        ends.sort_unstable();
+
+        let mut table = RangeTable::default();
+        // Covering prefix ids, counted as a prefix may have touching ranges
+        let mut active: BTreeMap<u32, usize> = BTreeMap::new();
+        let (mut next_start, mut next_end) = (0, 0);
+        for (i, point) in points.iter().enumerate() {
+            while next_start < starts.len() && starts[next_start].0 == *point {
+                *active.entry(starts[next_start].2).or_insert(0) += 1;
+                next_start += 1;
+            }
+            while next_end < ends.len() && ends[next_end].0 == *point {
+                let prefix_id = ends[next_end].1;
+                if let Some(count) = active.get_mut(&prefix_id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        active.remove(&prefix_id);
+                    }
+                }
+                next_end += 1;
+            }
+            if active.is_empty() {
+                continue;
+            }
+
+            let high = match points.get(i + 1) {
+                Some(next) => predecessor(next).expect("points are sorted and distinct"),
+                None => [0xff; N],
+            };
+            table.push(*point, high, active.keys().copied());
+        }
+        table
+    }
+
+    /// Appends a range, merged into the previous one when it touches it and
+    /// has the same prefixes
+    fn push(&mut self, low: [u8; N], high: [u8; N], prefix_ids: impl Iterator<Item = u32>) {
+        let first_candidate = self.candidates.len();
+        self.candidates.extend(prefix_ids);
+        let candidate_count = self.candidates.len() - first_candidate;
+
+        if let Some(last) = self.ranges.last_mut() {
+            let last_candidates = last.first_candidate as usize
+                ..last.first_candidate as usize + last.candidate_count as usize;
+            if successor(&last.high) == Some(low)
+                && self.candidates[last_candidates] == self.candidates[first_candidate..]
+            {
+                last.high = high;
+                self.candidates.truncate(first_candidate);
+                return;
+            }
+        }
+
+        self.ranges.push(TableRange {
+            low,
+            high,
+            first_candidate: first_candidate as u32,
+            candidate_count: candidate_count as u32,
+        });
+    }
+
+    /// Ids of the prefixes whose ranges hold `key`, in prefix order
+    pub fn lookup(&self, key: &[u8; N]) -> &[u32] {
+        let next = self.ranges.partition_point(|range| range.low <= *key);
+        match next.checked_sub(1).map(|i| &self.ranges[i]) {
+            Some(range) if *key <= range.high => {
+                let first = range.first_candidate as usize;
+                &self.candidates[first..first + range.candidate_count as usize]
+            }
+            _ => &[],
+        }
+    }
+
+    pub fn ranges(&self) -> &[TableRange<N>] {
+        &self.ranges
+    }
+
+    pub fn candidates(&self) -> &[u32] {
+        &self.candidates
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+/// One table per key a prefix is compared against
+#[derive(Clone, Debug, Default)]
+pub struct PrefixTables {
+    /// P2PKH and P2WPKH: the hash160 of the public key
+    pub pubkey_hash: RangeTable<20>,
+    /// P2SH-P2WPKH: the hash160 of the redeem script
+    pub script_hash: RangeTable<20>,
+    /// P2TR: the tweaked output key
+    pub output_key: RangeTable<32>,
+}
+
+impl PrefixTables {
+    pub fn new(prefixes: &[Prefix]) -> Self {
+        let hash160_entries = |address_types: &[AddressType]| -> Vec<([u8; 20], [u8; 20], u32)> {
+            prefixes
+                .iter()
+                .enumerate()
+                .filter(|(_, prefix)| address_types.contains(&prefix.address_type))
+                .flat_map(|(prefix_id, prefix)| {
+                    prefix
+                        .ranges
+                        .iter()
+                        .map(move |range| (range.low, range.high, prefix_id as u32))
+                })
+                .collect()
+        };
+        let output_key_entries: Vec<([u8; 32], [u8; 32], u32)> = prefixes
+            .iter()
+            .enumerate()
+            .flat_map(|(prefix_id, prefix)| {
+                prefix
+                    .output_key_ranges
+                    .iter()
+                    .map(move |range| (range.low, range.high, prefix_id as u32))
+            })
+            .collect();
+
+        PrefixTables {
+            pubkey_hash: RangeTable::new(&hash160_entries(&[
+                AddressType::P2PKH,
+                AddressType::P2WPKH,
+            ])),
+            script_hash: RangeTable::new(&hash160_entries(&[AddressType::P2SHP2WPKH])),
+            output_key: RangeTable::new(&output_key_entries),
+        }
+    }
+
+    /// Calls `on_match` with the id of every prefix matching the keys of an
+    /// address. The table narrows the prefixes down to the few covering the
+    /// key, which then check it themselves, suffix included. The keys no
+    /// prefix needs may be None.
+    pub fn for_each_match(
+        &self,
+        prefixes: &[Prefix],
+        pubkey_hash: Option<&[u8; 20]>,
+        script_hash: Option<&[u8; 20]>,
+        output_key: Option<&[u8; 32]>,
+        mut on_match: impl FnMut(u32),
+    ) {
+        for (table, hash) in [
+            (&self.pubkey_hash, pubkey_hash),
+            (&self.script_hash, script_hash),
+        ] {
+            let Some(hash) = hash else { continue };
+            for &prefix_id in table.lookup(hash) {
+                if prefixes[prefix_id as usize].matches_pattern(hash) {
+                    on_match(prefix_id);
+                }
+            }
+        }
+        if let Some(output_key) = output_key {
+            for &prefix_id in self.output_key.lookup(output_key) {
+                if prefixes[prefix_id as usize].matches_output_key(output_key) {
+                    on_match(prefix_id);
+                }
+            }
+        }
+    }
+}
+
+/// The key right after `key`, None for the highest one
+fn successor<const N: usize>(key: &[u8; N]) -> Option<[u8; N]> {
+    let mut next = *key;
+    for byte in next.iter_mut().rev() {
+        let (value, overflow) = byte.overflowing_add(1);
+        *byte = value;
+        if !overflow {
+            return Some(next);
+        }
+    }
+    None
+}
+
+/// The key right before `key`, None for the lowest one
+fn predecessor<const N: usize>(key: &[u8; N]) -> Option<[u8; N]> {
+    let mut previous = *key;
+    for byte in previous.iter_mut().rev() {
+        let (value, underflow) = byte.overflowing_sub(1);
+        *byte = value;
+        if !underflow {
+            return Some(previous);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+
+    fn key(first: u8) -> [u8; 2] {
+        [first, 0]
+    }
+
+    #[test]
+    fn test_overlapping_ranges_are_split() {
+        // Prefix 0 covers 10..=40, prefix 1 covers 20..=29 inside it
+        let table = RangeTable::new(&[
+            (key(10), [40, 0xff], 0),
+            (key(20), [29, 0xff], 1),
+            (key(50), [50, 0xff], 2),
+        ]);
+
+        assert_eq!(table.ranges().len(), 4);
+        assert_eq!(table.lookup(&key(9)), &[] as &[u32]);
+        assert_eq!(table.lookup(&key(10)), &[0]);
+        assert_eq!(table.lookup(&key(25)), &[0, 1]);
+        assert_eq!(table.lookup(&[29, 0xff]), &[0, 1]);
+        assert_eq!(table.lookup(&key(30)), &[0]);
+        assert_eq!(table.lookup(&[40, 0xff]), &[0]);
+        assert_eq!(table.lookup(&key(41)), &[] as &[u32]);
+        assert_eq!(table.lookup(&key(50)), &[2]);
+    }
+
+    #[test]
+    fn test_touching_ranges_of_a_prefix_are_merged() {
+        let table = RangeTable::new(&[
+            (key(10), [19, 0xff], 0),
+            (key(20), [29, 0xff], 0),
+            (key(30), [0xff, 0xff], 1),
+        ]);
+
+        assert_eq!(
+            table.ranges()[0],
+            TableRange {
+                low: key(10),
+                high: [29, 0xff],
+                first_candidate: 0,
+                candidate_count: 1
+            }
+        );
+        assert_eq!(table.lookup(&[0xff, 0xff]), &[1]);
+    }
+
+    #[test]
+    fn test_matches_like_the_prefixes() {
+        let words = ["1Bob", "1Bobby", "1Ann", "1Eve", "bc1qxy", "3Bob"];
+        let prefixes: Vec<Prefix> = words
+            .iter()
+            .map(|word| Prefix::new(word, Network::Mainnet).unwrap())
+            .collect();
+        let tables = PrefixTables::new(&prefixes);
+
+        // Keys at the bounds of every range, and right outside them
+        let mut hashes = Vec::new();
+        for prefix in &prefixes {
+            for range in &prefix.ranges {
+                hashes.extend([Some(range.low), Some(range.high)]);
+                hashes.extend([predecessor(&range.low), successor(&range.high)]);
+            }
+        }
+
+        for hash in hashes.into_iter().flatten() {
+            let mut matched = Vec::new();
+            tables.for_each_match(&prefixes, Some(&hash), Some(&hash), None, |id| {
+                matched.push(id)
+            });
+
+            let expected: Vec<u32> =
+                (0..prefixes.len() as u32)
+                    .filter(|id| prefixes[*id as usize].address_type != AddressType::P2SHP2WPKH)
+                    .chain((0..prefixes.len() as u32).filter(|id| {
+                        prefixes[*id as usize].address_type == AddressType::P2SHP2WPKH
+                    }))
+                    .filter(|id| prefixes[*id as usize].matches_pattern(&hash))
+                    .collect();
+            assert_eq!(matched, expected);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/// Addresses the search finds before it stops
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Quota {
+    /// Of all the prefixes together, 0 = never stop
+    Total(u32),
+    /// Of each prefix, by prefix id, 0 = never met
+    PerPrefix(Vec<u32>),
+}
+
+impl Quota {
+    /// Whether the search is done with `found` addresses of each prefix
+    pub fn is_met(&self, found: &[u32]) -> bool {
+        match self {
+            Quota::Total(0) => false,
+            Quota::Total(total) => found.iter().sum::<u32>() >= *total,
+            Quota::PerPrefix(quotas) => (0..quotas.len()).all(|id| self.is_prefix_met(id, found)),
+        }
+    }
+
+    /// Whether more matches of the prefix are of no use. Only per-prefix
+    /// quotas are met by one prefix.
+    pub fn is_prefix_met(&self, prefix_id: usize, found: &[u32]) -> bool {
+        match self {
+            Quota::Total(_) => false,
+            Quota::PerPrefix(quotas) => {
+                quotas[prefix_id] > 0
+                    && found.get(prefix_id).copied().unwrap_or(0) >= quotas[prefix_id]
+            }
+        }
+    }
+}
+
+/// Splits a `pattern:quota` prefix argument, e.g. "1Bob:2"
+pub fn split_quota(text: &str) -> Result<(&str, Option<u32>), String> {
+    match text.rsplit_once(':') {
+        Some((pattern, quota)) => match quota.parse::<u32>() {
+            Ok(quota) if quota > 0 => Ok((pattern, Some(quota))),
+            _ => Err(format!(
+                "Invalid quota '{}' of '{}', expected a number of addresses from 1",
+                quota, pattern
+            )),
+        },
+        None => Ok((text, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_quota() {
+        assert_eq!(split_quota("1Bob:2"), Ok(("1Bob", Some(2))));
+        assert_eq!(split_quota("1Bob...Eve"), Ok(("1Bob...Eve", None)));
+        assert!(split_quota("1Bob:0").is_err());
+        assert!(split_quota("1Bob:").is_err());
+        assert!(split_quota("1Bob:x").is_err());
+    }
+
+    #[test]
+    fn test_per_prefix_quotas() {
+        let quota = Quota::PerPrefix(vec![1, 3]);
+        assert!(!quota.is_met(&[0, 5]));
+        assert!(quota.is_prefix_met(1, &[0, 5]));
+        assert!(!quota.is_prefix_met(0, &[0, 5]));
+        assert!(quota.is_met(&[1, 3]));
+
+        // A prefix without a limit keeps the search going
+        assert!(!Quota::PerPrefix(vec![1, 0]).is_met(&[1, 100]));
+
+        assert!(Quota::Total(3).is_met(&[1, 2]));
+        assert!(!Quota::Total(3).is_prefix_met(0, &[3, 0]));
+        assert!(!Quota::Total(0).is_met(&[100]));
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADER: &str =
+    "address,type,prefix,derivation path,index,xpub fingerprint,timestamp,bench id,descriptor";
+const FINGERPRINT_COLUMN: usize = 5;
+
+/// A confirmed match, as written to the results file
+pub struct FoundRecord<'a> {
+    pub address: &'a str,
+    pub address_type: &'a str,
+    pub prefix: &'a str,
+    pub derivation_path: &'a str,
+    pub index: u32,
+    pub bench_id: &'a str,
+    pub descriptor: &'a str,
+}
+
+/// CSV file the found addresses are appended to as soon as they are
+/// confirmed, so a crash or a closed terminal never loses one. Every row
+/// holds the fingerprint of the xpub it was derived from.
+pub struct ResultsFile {
+    file: File,
+    path: PathBuf,
+    fingerprint: String,
+}
+
+impl ResultsFile {
+    /// Opens the file for appending, creating it if needed. Refuses a file
+    /// holding results of another xpub.
+    pub fn open(path: &Path, fingerprint: [u8; 4]) -> Result<Self, String> {
+        let fingerprint: String = fingerprint.iter().map(|b| format!("{:02x}", b)).collect();
+        Self::open_with_fingerprint(path, fingerprint)
+    }
+
+    fn open_with_fingerprint(path: &Path, fingerprint: String) -> Result<Self, String> {
+        let is_new = match File::open(path) {
+            Ok(existing) => Self::check_fingerprint(existing, &fingerprint)
+                .map_err(|e| format!("Results file '{}' {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+            Err(e) => return Err(format!("Failed to read '{}': {}", path.display(), e)),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?;
+        let mut results_file = ResultsFile {
+            file,
+            path: path.to_path_buf(),
+            fingerprint,
+        };
+
+        if is_new {
+            results_file
+                .write_line(HEADER)
+                .map_err(|e| format!("Failed to write to '{}': {}", path.display(), e))?;
+        }
+
+        Ok(results_file)
+    }
+
+    /// Whether the file is empty, erroring if it is not a results file or
+    /// if one of its rows has another fingerprint
+    fn check_fingerprint(existing: File, fingerprint: &str) -> Result<bool, String> {
+        let mut lines = BufReader::new(existing).lines();
+        match lines.next() {
+            None => return Ok(true),
+            Some(Ok(header)) if header == HEADER => {}
+            Some(_) => return Err("is not an address-artisan results file".to_string()),
+        }
+
+        for line in lines {
+            let line = line.map_err(|e| format!("cannot be read: {}", e))?;
+            match line.split(',').nth(FINGERPRINT_COLUMN) {
+                Some(row_fingerprint) if row_fingerprint == fingerprint => {}
+                Some(row_fingerprint) => {
+                    return Err(format!(
+                        "holds results of xpub {}, not of this one ({}). Use another file",
+                        row_fingerprint, fingerprint
+                    ))
+                }
+                None => return Err(format!("has an invalid row: {}", line)),
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Appends the match and syncs it to disk before returning
+    pub fn append(&mut self, record: &FoundRecord) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let line = format!(
+            "{},{},{},{},{},{},{},{},{}",
+            record.address,
+            record.address_type,
+            record.prefix,
+            record.derivation_path,
+            record.index,
+            self.fingerprint,
+            timestamp,
+            record.bench_id,
+            record.descriptor
+        );
+        self.write_line(&line)
+    }
+
+    /// Appends a row of another results file as is
+    fn append_row(&mut self, row: &str) -> io::Result<()> {
+        self.write_line(row)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A results file every write to fails
+    #[cfg(test)]
+    pub fn read_only(path: &Path) -> Self {
+        ResultsFile {
+            file: File::open(path).unwrap(),
+            path: path.to_path_buf(),
+            fingerprint: "00000000".to_string(),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", line)?;
+        self.file.sync_data()
+    }
+}
+
+/// What a merge added to its output
+pub struct MergeSummary {
+    pub merged: usize,
+    pub duplicates: usize,
+}
+
+/// Appends the rows of `inputs` to `output`, keeping one row per address:
+/// overlapping shards or a file merged twice add no duplicate. Every file
+/// must hold results of the same xpub.
+pub fn merge(inputs: &[PathBuf], output: &Path) -> Result<MergeSummary, String> {
+    let mut input_rows = Vec::new();
+    let mut first_fingerprint: Option<(&str, &Path)> = None;
+    for input in inputs {
+        input_rows.push((input, read_rows(input)?));
+    }
+    for (input, rows) in &input_rows {
+        for row in rows {
+            let fingerprint = row_fingerprint(row);
+            match first_fingerprint {
+                None => first_fingerprint = Some((fingerprint, input)),
+                Some((first, _)) if first == fingerprint => {}
+                Some((first, first_input)) => {
+                    return Err(format!(
+                        "'{}' holds results of xpub {}, '{}' of xpub {}. Merge the results of one xpub at a time",
+                        first_input.display(),
+                        first,
+                        input.display(),
+                        fingerprint
+                    ))
+                }
+            }
+        }
+    }
+
+    let mut summary = MergeSummary {
+        merged: 0,
+        duplicates: 0,
+    };
+    let Some((fingerprint, _)) = first_fingerprint else {
+        return Ok(summary);
+    };
+
+    let mut results_file = ResultsFile::open_with_fingerprint(output, fingerprint.to_string())?;
+    let mut addresses: HashSet<String> = read_rows(output)?
+        .iter()
+        .map(|row| row_address(row).to_string())
+        .collect();
+    for row in input_rows.iter().flat_map(|(_, rows)| rows) {
+        if addresses.insert(row_address(row).to_string()) {
+            results_file
+                .append_row(row)
+                .map_err(|e| format!("Failed to write to '{}': {}", output.display(), e))?;
+            summary.merged += 1;
+        } else {
+            summary.duplicates += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The rows of a results file, without its header
+fn read_rows(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let mut lines = content.lines();
+    match lines.next() {
+        None => return Ok(Vec::new()),
+        Some(header) if header == HEADER => {}
+        Some(_) => {
+            return Err(format!(
+                "'{}' is not an address-artisan results file",
+                path.display()
+            ))
+        }
+    }
+
+    lines
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if line.split(',').nth(FINGERPRINT_COLUMN).is_some() {
+                Ok(line.to_string())
+            } else {
+                Err(format!("'{}' has an invalid row: {}", path.display(), line))
+            }
+        })
+        .collect()
+}
+
+fn row_address(row: &str) -> &str {
+    row.split(',').next().unwrap_or_default()
+}
+
+fn row_fingerprint(row: &str) -> &str {
+    row.split(',').nth(FINGERPRINT_COLUMN).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "address-artisan-{}-{}.csv",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn record<'a>(address: &'a str) -> FoundRecord<'a> {
+        FoundRecord {
+            address,
+            address_type: "P2PKH",
+            prefix: "1A",
+            derivation_path: "xpub'/1/2/3/4",
+            index: 5,
+            bench_id: "CPU",
+            descriptor: "pkh(xpub/1/2/3/4/0/*)#checksum",
+        }
+    }
+
+    #[test]
+    fn test_appends_rows_after_header() {
+        let path = temp_path("append");
+        let fingerprint = [0xd3, 0x4d, 0xb3, 0x3f];
+
+        let mut results_file = ResultsFile::open(&path, fingerprint).unwrap();
+        results_file.append(&record("1Afirst")).unwrap();
+        drop(results_file);
+
+        // Reopening with the same xpub keeps the earlier rows
+        let mut results_file = ResultsFile::open(&path, fingerprint).unwrap();
+        results_file.append(&record("1Asecond")).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], HEADER);
+        assert!(lines[1].starts_with("1Afirst,P2PKH,1A,xpub'/1/2/3/4,5,d34db33f,"));
+        assert!(lines[2].starts_with("1Asecond,"));
+        assert!(lines[2].ends_with(",CPU,pkh(xpub/1/2/3/4/0/*)#checksum"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refuses_file_of_another_xpub() {
+        let path = temp_path("other-xpub");
+
+        let mut results_file = ResultsFile::open(&path, [0xd3, 0x4d, 0xb3, 0x3f]).unwrap();
+        results_file.append(&record("1Afirst")).unwrap();
+        drop(results_file);
+
+        let error = ResultsFile::open(&path, [0x01, 0x02, 0x03, 0x04])
+            .err()
+            .unwrap();
+        assert!(error.contains("holds results of xpub d34db33f, not of this one (01020304)"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_merge_drops_duplicate_addresses() {
+        let fingerprint = [0xd3, 0x4d, 0xb3, 0x3f];
+        let shard0 = temp_path("merge-shard0");
+        let shard1 = temp_path("merge-shard1");
+        let output = temp_path("merge-output");
+
+        let mut results_file = ResultsFile::open(&shard0, fingerprint).unwrap();
+        results_file.append(&record("1Afirst")).unwrap();
+        results_file.append(&record("1Asecond")).unwrap();
+        let mut results_file = ResultsFile::open(&shard1, fingerprint).unwrap();
+        results_file.append(&record("1Asecond")).unwrap();
+        results_file.append(&record("1Athird")).unwrap();
+
+        let summary = merge(&[shard0.clone(), shard1.clone()], &output).unwrap();
+        assert_eq!((summary.merged, summary.duplicates), (3, 1));
+
+        // Merging again adds nothing
+        let summary = merge(&[shard0.clone(), shard1.clone()], &output).unwrap();
+        assert_eq!((summary.merged, summary.duplicates), (0, 4));
+
+        let content = std::fs::read_to_string(&output).unwrap();
+        let addresses: Vec<&str> = content.lines().skip(1).map(row_address).collect();
+        assert_eq!(addresses, vec!["1Afirst", "1Asecond", "1Athird"]);
+
+        for path in [shard0, shard1, output] {
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_merge_refuses_results_of_several_xpubs() {
+        let shard0 = temp_path("merge-xpub0");
+        let shard1 = temp_path("merge-xpub1");
+        let output = temp_path("merge-xpub-output");
+
+        let mut results_file = ResultsFile::open(&shard0, [0xd3, 0x4d, 0xb3, 0x3f]).unwrap();
+        results_file.append(&record("1Afirst")).unwrap();
+        let mut results_file = ResultsFile::open(&shard1, [0x01, 0x02, 0x03, 0x04]).unwrap();
+        results_file.append(&record("1Asecond")).unwrap();
+
+        let error = merge(&[shard0.clone(), shard1.clone()], &output)
+            .err()
+            .unwrap();
+        assert!(error.contains("of xpub d34db33f"));
+        assert!(error.contains("of xpub 01020304"));
+        assert!(!output.exists());
+
+        for path in [shard0, shard1] {
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_refuses_other_files() {
+        let path = temp_path("other-file");
+        std::fs::write(&path, "some notes\n").unwrap();
+
+        let error = ResultsFile::open(&path, [0xd3, 0x4d, 0xb3, 0x3f])
+            .err()
+            .unwrap();
+        assert!(error.contains("is not an address-artisan results file"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "some notes\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use crate::constants::NON_HARDENED_MAX_INDEX;
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+
+/// First two indexes of every searched path, shared by all the devices of
+/// a run. With the same seeds, max depth and devices, a run explores the
+/// same paths again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Seeds {
+    pub seed0: u32,
+    pub seed1: u32,
+}
+
+impl Seeds {
+    pub fn random() -> Self {
+        Seeds {
+            seed0: rand::random::<u32>() & NON_HARDENED_MAX_INDEX,
+            seed1: rand::random::<u32>() & NON_HARDENED_MAX_INDEX,
+        }
+    }
+
+    /// Seeds taken from the SHA256 of a user-chosen run key
+    pub fn from_run_key(run_key: &str) -> Self {
+        let hash = Sha256::digest(run_key.as_bytes());
+        Seeds {
+            seed0: u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])
+                & NON_HARDENED_MAX_INDEX,
+            seed1: u32::from_be_bytes([hash[4], hash[5], hash[6], hash[7]])
+                & NON_HARDENED_MAX_INDEX,
+        }
+    }
+
+    /// Parses `seed0,seed1`
+    pub fn parse(seeds: &str) -> Result<Self, String> {
+        let (seed0, seed1) = seeds
+            .split_once(',')
+            .ok_or_else(|| "Seeds should be given as 'seed0,seed1'".to_string())?;
+        let parse_seed = |seed: &str| -> Result<u32, String> {
+            let seed: u32 = seed
+                .trim()
+                .parse()
+                .map_err(|e: std::num::ParseIntError| format!("Invalid seed '{}': {}", seed, e))?;
+            if seed > NON_HARDENED_MAX_INDEX {
+                return Err(format!(
+                    "Seed {} must be <= {}",
+                    seed, NON_HARDENED_MAX_INDEX
+                ));
+            }
+            Ok(seed)
+        };
+
+        Ok(Seeds {
+            seed0: parse_seed(seed0)?,
+            seed1: parse_seed(seed1)?,
+        })
+    }
+}
+
+/// Splits the searched counters, the whole counter space or a shard of it,
+/// into one disjoint range per device, in device order, so no two devices
+/// derive the same path
+pub fn counter_ranges(search_range: &Range<u64>, devices: usize) -> Vec<Range<u64>> {
+    let (start, len) = (
+        search_range.start as u128,
+        (search_range.end - search_range.start) as u128,
+    );
+    let devices = devices as u128;
+    (0..devices)
+        .map(|i| (start + len * i / devices) as u64..(start + len * (i + 1) / devices) as u64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extended_public_key_path_walker::counter_space;
+    use crate::path_template::PathTemplate;
+
+    #[test]
+    fn test_from_run_key_is_stable() {
+        let seeds = Seeds::from_run_key("my vanity run");
+        assert_eq!(seeds, Seeds::from_run_key("my vanity run"));
+        assert_ne!(seeds, Seeds::from_run_key("another run"));
+        assert!(seeds.seed0 <= NON_HARDENED_MAX_INDEX);
+        assert!(seeds.seed1 <= NON_HARDENED_MAX_INDEX);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Seeds::parse("1949567566,243133792").unwrap(),
+            Seeds {
+                seed0: 1949567566,
+                seed1: 243133792
+            }
+        );
+        assert!(Seeds::parse("1949567566").is_err());
+        assert!(Seeds::parse("1,x").is_err());
+        assert!(Seeds::parse("2147483648,0")
+            .unwrap_err()
+            .contains("must be <="));
+    }
+
+    #[test]
+    fn test_counter_ranges_are_disjoint_and_cover_the_space() {
+        for max_depth in [1, 1000] {
+            let space = counter_space(&PathTemplate::default(), max_depth);
+            let ranges = counter_ranges(&(0..space), 3);
+            assert_eq!(ranges.len(), 3);
+            assert_eq!(ranges[0].start, 0);
+            assert_eq!(ranges[0].end, ranges[1].start);
+            assert_eq!(ranges[1].end, ranges[2].start);
+            assert_eq!(ranges[2].end, space);
+        }
+
+        assert_eq!(counter_ranges(&(0..u64::MAX), 1), vec![0..u64::MAX]);
+    }
+
+    #[test]
+    fn test_counter_ranges_split_a_shard() {
+        assert_eq!(
+            counter_ranges(&(1000..1300), 3),
+            vec![1000..1100, 1100..1200, 1200..1300]
+        );
+    }
+}
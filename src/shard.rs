@@ -0,0 +1,115 @@
+use crate::extended_public_key_path_walker::counter_space;
+use crate::path_template::PathTemplate;
+use std::ops::Range;
+
+/// Slice of the counter space searched by one machine. Every machine of a
+/// search uses the same seeds, so disjoint shards never derive the same path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Shard {
+    /// The `index`-th of `count` equal slices, counted from 0
+    Index { index: u64, count: u64 },
+    /// An explicit range of counters
+    Range(Range<u64>),
+}
+
+impl Shard {
+    /// Parses `index/count` or `start..end`
+    pub fn parse(shard: &str) -> Result<Self, String> {
+        let parse_number = |number: &str| -> Result<u64, String> {
+            number
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid shard number '{}': {}", number, e))
+        };
+
+        if let Some((start, end)) = shard.split_once("..") {
+            let range = parse_number(start)?..parse_number(end)?;
+            if range.is_empty() {
+                return Err(format!(
+                    "Shard range {}..{} is empty",
+                    range.start, range.end
+                ));
+            }
+            Ok(Shard::Range(range))
+        } else if let Some((index, count)) = shard.split_once('/') {
+            let (index, count) = (parse_number(index)?, parse_number(count)?);
+            if index >= count {
+                return Err(format!(
+                    "Shard index {} must be below the shard count {} (shards are counted from 0)",
+                    index, count
+                ));
+            }
+            Ok(Shard::Index { index, count })
+        } else {
+            Err("Shards should be given as 'index/count' or 'start..end'".to_string())
+        }
+    }
+
+    /// The counters of the shard for this path template and max depth
+    pub fn counter_range(
+        &self,
+        template: &PathTemplate,
+        max_depth: u32,
+    ) -> Result<Range<u64>, String> {
+        let space = counter_space(template, max_depth);
+        match self {
+            Shard::Index { index, count } => {
+                let (space, index, count) = (space as u128, *index as u128, *count as u128);
+                Ok((space * index / count) as u64..(space * (index + 1) / count) as u64)
+            }
+            Shard::Range(range) if range.end <= space => Ok(range.clone()),
+            Shard::Range(range) => Err(format!(
+                "Shard range {}..{} goes past the {} counters of path template {} with max depth {}",
+                range.start, range.end, space, template, max_depth
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Shard::parse("2/8").unwrap(),
+            Shard::Index { index: 2, count: 8 }
+        );
+        assert_eq!(Shard::parse("100..200").unwrap(), Shard::Range(100..200));
+        assert!(Shard::parse("8/8").unwrap_err().contains("counted from 0"));
+        assert!(Shard::parse("200..100").unwrap_err().contains("is empty"));
+        assert!(Shard::parse("2").is_err());
+        assert!(Shard::parse("a/8").is_err());
+    }
+
+    #[test]
+    fn test_index_shards_are_disjoint_and_cover_the_space() {
+        let shards: Vec<Range<u64>> = (0..3)
+            .map(|index| {
+                Shard::Index { index, count: 3 }
+                    .counter_range(&PathTemplate::default(), 1)
+                    .unwrap()
+            })
+            .collect();
+
+        assert_eq!(shards[0].start, 0);
+        assert_eq!(shards[0].end, shards[1].start);
+        assert_eq!(shards[1].end, shards[2].start);
+        assert_eq!(shards[2].end, counter_space(&PathTemplate::default(), 1));
+    }
+
+    #[test]
+    fn test_range_must_fit_the_max_depth() {
+        let template = PathTemplate::parse("s0/a/0/i").unwrap();
+        let end = counter_space(&template, 1);
+        assert_eq!(
+            Shard::Range(5..end).counter_range(&template, 1).unwrap(),
+            5..end
+        );
+        assert!(Shard::Range(5..end + 1)
+            .counter_range(&template, 1)
+            .unwrap_err()
+            .contains("goes past"));
+    }
+}
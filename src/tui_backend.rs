@@ -1,5 +1,14 @@
-use crate::display_backend::{BenchStats, UiBackend};
-use crate::prefix::{AddressType, Prefix};
+use crate::budget::EndReason;
+use crate::constants::DEFAULT_MAX_DEPTH;
+use crate::cpu_throttle::{CpuThrottle, CPU_LIMIT_STEP, MAX_CPU_LIMIT};
+use crate::descriptor::KeyOrigin;
+use crate::difficulty::{format_count, format_odds, Difficulty, LISTED_PREFIXES};
+use crate::display_backend::{format_derivation_path, BenchStats, UiBackend};
+use crate::gap_estimate::{format_estimate, GapEstimate};
+use crate::longest_match::Leaderboard;
+use crate::path_template::{KeyPath, PathTemplate};
+use crate::prefix::Prefix;
+use crate::seeds::Seeds;
 use crossterm::{
     cursor::Show,
     event::{self, Event, KeyCode, KeyModifiers},
@@ -9,13 +18,14 @@ use crossterm::{
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Layout},
-    style::{Modifier, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, ListState, Paragraph, Row, Table, TableState, Wrap},
     Terminal,
 };
 use std::collections::HashMap;
 use std::io::{self, stdout};
+use std::ops::Range;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
@@ -24,7 +34,7 @@ use std::time::Duration;
 // TUI Layout Constants
 const EVENT_POLL_INTERVAL_MS: u64 = 250;
 const EVENT_POLL_INTERVAL_EXIT_MS: u64 = 10; // Faster polling when exiting
-const CONFIG_SECTION_HEIGHT: u16 = 5; // 3 lines of content + 2 for borders
+const CONFIG_SECTION_HEIGHT: u16 = 8; // 6 lines of content + 2 for borders
 
 // Table Column Widths
 const WORKBENCH_ID_COLUMN_WIDTH: u16 = 30;
@@ -35,11 +45,6 @@ const WORKBENCH_RUNTIME_COLUMN_WIDTH: u16 = 10;
 const WORKBENCH_ID_MAX_DISPLAY_LEN: usize = 30;
 const WORKBENCH_ID_TRUNCATE_LEN: usize = 27;
 
-/// Formats a BIP32 derivation path for display
-fn format_derivation_path(path: &[u32; 6]) -> String {
-    format!("xpub'/{}/{}/{}/{}", path[0], path[1], path[2], path[3])
-}
-
 /// Gets all workbench IDs from both workbench_status and bench_stats, sorted
 fn get_all_workbench_ids(
     workbench_status: &HashMap<String, WorkbenchStatus>,
@@ -58,6 +63,11 @@ struct TuiState {
     config: Arc<ConfigInfo>,
     prefixes: Arc<Vec<Prefix>>,
     bench_stats: HashMap<String, BenchStats>,
+    gap_estimate: Option<GapEstimate>,
+    leaderboard: Option<Leaderboard>,
+    end_reason: Option<EndReason>,
+    /// Shown in the TUI, the last one, and printed on exit
+    errors: Vec<String>,
     workbench_status: HashMap<String, WorkbenchStatus>,
     found_addresses: Vec<FoundAddress>,
     workbenches_list_state: ListState,
@@ -92,16 +102,21 @@ enum ActiveList {
 
 #[derive(Clone)]
 struct ConfigInfo {
+    key_origin: Option<KeyOrigin>,
+    seeds: Option<Seeds>,
+    template: PathTemplate,
     max_depth: u32,
     cpu_threads: u32,
+    difficulty: Difficulty,
 }
 
 #[derive(Clone)]
 struct FoundAddress {
     address: String,
     bench_id: String,
-    path: [u32; 6],
-    prefix_id: u8,
+    path: KeyPath,
+    prefix_id: u32,
+    descriptor: String,
 }
 
 pub struct TuiBackend {
@@ -113,7 +128,7 @@ pub struct TuiBackend {
 }
 
 impl TuiBackend {
-    pub fn new(stop_signal: Arc<AtomicBool>) -> io::Result<Self> {
+    pub fn new(stop_signal: Arc<AtomicBool>, cpu_throttle: CpuThrottle) -> io::Result<Self> {
         // Setup terminal
         enable_raw_mode()?;
         let mut stdout = stdout();
@@ -127,11 +142,19 @@ impl TuiBackend {
 
         let state = Arc::new(Mutex::new(TuiState {
             config: Arc::new(ConfigInfo {
+                key_origin: None,
+                seeds: None,
+                template: PathTemplate::default(),
                 max_depth: 0,
                 cpu_threads: 0,
+                difficulty: Difficulty::new(&[]),
             }),
             prefixes: Arc::new(Vec::new()),
             bench_stats: HashMap::new(),
+            gap_estimate: None,
+            leaderboard: None,
+            end_reason: None,
+            errors: Vec::new(),
             workbench_status: HashMap::new(),
             found_addresses: Vec::new(),
             workbenches_list_state,
@@ -152,6 +175,7 @@ impl TuiBackend {
             if let Err(e) = run_event_loop(
                 state_clone,
                 stop_signal_clone,
+                cpu_throttle,
                 render_requested_clone,
                 exit_requested_clone,
             ) {
@@ -170,18 +194,30 @@ impl TuiBackend {
 }
 
 impl UiBackend for TuiBackend {
-    fn start(&mut self, prefixes: &[Prefix], max_depth: u32, cpu_threads: u32) {
+    fn start(
+        &mut self,
+        prefixes: &[Prefix],
+        key_origin: Option<&KeyOrigin>,
+        seeds: Seeds,
+        template: &PathTemplate,
+        max_depth: u32,
+        cpu_threads: u32,
+    ) {
         let mut state = self.state.lock().expect("TUI state mutex poisoned");
         state.config = Arc::new(ConfigInfo {
+            key_origin: key_origin.cloned(),
+            seeds: Some(seeds),
+            template: *template,
             max_depth,
             cpu_threads,
+            difficulty: Difficulty::new(prefixes),
         });
         state.prefixes = Arc::new(prefixes.to_vec());
         drop(state);
         self.render_requested.store(true, Ordering::Relaxed);
     }
 
-    fn workbench_starting(&mut self, bench_id: &str) {
+    fn workbench_starting(&mut self, bench_id: &str, _counter_range: &Range<u64>) {
         let mut state = self.state.lock().expect("TUI state mutex poisoned");
         state
             .workbench_status
@@ -206,13 +242,35 @@ impl UiBackend for TuiBackend {
         self.render_requested.store(true, Ordering::Relaxed);
     }
 
-    fn log_found_address(&mut self, bench_id: &str, address: &str, path: &[u32; 6], prefix_id: u8) {
+    fn log_gap_estimate(&mut self, estimate: &GapEstimate) {
+        let mut state = self.state.lock().expect("TUI state mutex poisoned");
+        state.gap_estimate = Some(*estimate);
+        drop(state);
+        self.render_requested.store(true, Ordering::Relaxed);
+    }
+
+    fn log_leaderboard(&mut self, leaderboard: &Leaderboard) {
+        let mut state = self.state.lock().expect("TUI state mutex poisoned");
+        state.leaderboard = Some(leaderboard.clone());
+        drop(state);
+        self.render_requested.store(true, Ordering::Relaxed);
+    }
+
+    fn log_found_address(
+        &mut self,
+        bench_id: &str,
+        address: &str,
+        path: &KeyPath,
+        prefix_id: u32,
+        descriptor: &str,
+    ) {
         let mut state = self.state.lock().expect("TUI state mutex poisoned");
         state.found_addresses.push(FoundAddress {
             address: address.to_string(),
             bench_id: bench_id.to_string(),
             path: *path,
             prefix_id,
+            descriptor: descriptor.to_string(),
         });
         drop(state);
         self.render_requested.store(true, Ordering::Relaxed);
@@ -222,10 +280,17 @@ impl UiBackend for TuiBackend {
         // Could add to an errors list in TuiState if needed
     }
 
-    fn log_false_positive(&mut self, _bench_id: &str, _path: &[u32; 6]) {
+    fn log_false_positive(&mut self, _bench_id: &str, _path: &KeyPath) {
         // Could add to a false positives list if needed
     }
 
+    fn log_error(&mut self, message: &str) {
+        let mut state = self.state.lock().expect("TUI state mutex poisoned");
+        state.errors.push(message.to_string());
+        drop(state);
+        self.render_requested.store(true, Ordering::Relaxed);
+    }
+
     fn stop_requested(&mut self) {
         // TUI will display this via the stop_signal being set
     }
@@ -248,7 +313,10 @@ impl UiBackend for TuiBackend {
         self.render_requested.store(true, Ordering::Relaxed);
     }
 
-    fn final_status(&mut self) {
+    fn final_status(&mut self, reason: EndReason) {
+        let mut state = self.state.lock().expect("TUI state mutex poisoned");
+        state.end_reason = Some(reason);
+        drop(state);
         // Signal the event loop to exit - all workbenches have stopped
         self.exit_requested.store(true, Ordering::Relaxed);
     }
@@ -271,19 +339,81 @@ impl Drop for TuiBackend {
 
         // Print found addresses after exiting TUI in CSV format
         let state = self.state.lock().expect("TUI state mutex poisoned");
+        if let Some(reason) = state.end_reason {
+            println!("\nSearch ended: {}", reason);
+        }
+        for error in &state.errors {
+            eprintln!("Error: {}", error);
+        }
+        if let Some(seeds) = state.config.seeds {
+            let template = state.config.template;
+            let template_arg = if template == PathTemplate::default() {
+                String::new()
+            } else {
+                format!(" --path-template {}", template)
+            };
+            println!(
+                "\nSeeds: {0},{1} (--seeds {0},{1}{2} searches the same paths again)",
+                seeds.seed0, seeds.seed1, template_arg
+            );
+        }
+        if !state.prefixes.is_empty() {
+            println!();
+            for line in state
+                .config
+                .difficulty
+                .summary_lines(&state.prefixes, LISTED_PREFIXES)
+            {
+                println!("{}", line);
+            }
+            let addresses_per_sec: u64 = state
+                .bench_stats
+                .values()
+                .map(|stats| stats.addresses_per_sec())
+                .sum();
+            if let Some(eta) = state.config.difficulty.eta_secs(addresses_per_sec as f64) {
+                println!(
+                    "Expected time to a hit: {} at {} addr/s",
+                    format_estimate(eta),
+                    format_count(addresses_per_sec as f64)
+                );
+            }
+        }
+        if let Some(leaderboard) = &state.leaderboard {
+            println!("\nLongest matches of {}:", leaderboard.target());
+            for (rank, entry) in leaderboard.entries().iter().enumerate() {
+                println!(
+                    "{:>2}. {} ({} characters, {})",
+                    rank + 1,
+                    entry.address,
+                    entry.matched_chars,
+                    format_derivation_path(
+                        &entry.path,
+                        &state.config.template,
+                        state.config.key_origin.as_ref(),
+                    )
+                );
+            }
+        }
         if !state.found_addresses.is_empty() {
-            println!("\naddress,type,prefix,derivation path,index");
+            println!("\naddress,type,prefix,derivation path,index,descriptor");
             for item in &state.found_addresses {
-                let derivation_path = format_derivation_path(&item.path);
+                let derivation_path = format_derivation_path(
+                    &item.path,
+                    &state.config.template,
+                    state.config.key_origin.as_ref(),
+                );
                 let prefix = &state.prefixes[item.prefix_id as usize];
                 let prefix_str = prefix.as_str();
-                let address_type_str = match prefix.address_type {
-                    AddressType::P2PKH => "P2PKH",
-                    AddressType::P2WPKH => "P2WPKH",
-                };
+                let address_type_str = prefix.address_type.name();
                 println!(
-                    "{},{},{},{},{}",
-                    item.address, address_type_str, prefix_str, derivation_path, item.path[5]
+                    "{},{},{},{},{},{}",
+                    item.address,
+                    address_type_str,
+                    prefix_str,
+                    derivation_path,
+                    item.path.index(),
+                    item.descriptor
                 );
             }
         }
@@ -293,6 +423,7 @@ impl Drop for TuiBackend {
 fn run_event_loop(
     state: Arc<Mutex<TuiState>>,
     stop_signal: Arc<AtomicBool>,
+    cpu_throttle: CpuThrottle,
     render_requested: Arc<AtomicBool>,
     exit_requested: Arc<AtomicBool>,
 ) -> io::Result<()> {
@@ -312,6 +443,9 @@ fn run_event_loop(
                 config,
                 prefixes,
                 bench_stats,
+                gap_estimate,
+                leaderboard,
+                last_error,
                 workbench_status,
                 found_addresses,
                 workbenches_selected,
@@ -323,6 +457,9 @@ fn run_event_loop(
                     state.config.clone(),
                     state.prefixes.clone(),
                     state.bench_stats.clone(),
+                    state.gap_estimate,
+                    state.leaderboard.clone(),
+                    state.errors.last().cloned(),
                     state.workbench_status.clone(),
                     state.found_addresses.clone(),
                     state.workbenches_list_state.selected(),
@@ -335,7 +472,9 @@ fn run_event_loop(
             // Render UI using copied data (no lock held)
             terminal.draw(|frame| {
                 // Calculate exact layout heights
-                let config_height = CONFIG_SECTION_HEIGHT;
+                let config_height = CONFIG_SECTION_HEIGHT
+                    + u16::from(leaderboard.is_some())
+                    + u16::from(last_error.is_some());
 
                 // Workbenches: header(1) + data rows + totals(1) + borders(2)
                 // Count both starting and running workbenches
@@ -363,26 +502,104 @@ fn run_event_loop(
                 } else {
                     "Prefixes"
                 };
-                let prefixes_str = prefixes
+                let mut prefixes_str = prefixes
                     .iter()
-                    .map(|p| p.as_str())
+                    .zip(&config.difficulty.probabilities)
+                    .take(LISTED_PREFIXES)
+                    .map(|(p, probability)| {
+                        format!("{} (1 in {})", p.as_str(), format_count(1.0 / probability))
+                    })
                     .collect::<Vec<_>>()
                     .join(", ");
+                if prefixes.len() > LISTED_PREFIXES {
+                    prefixes_str
+                        .push_str(&format!(" and {} more", prefixes.len() - LISTED_PREFIXES));
+                }
+                let addresses_per_sec: u64 = bench_stats
+                    .values()
+                    .map(|stats| stats.addresses_per_sec())
+                    .sum();
+                let difficulty_str = match config.difficulty.eta_secs(addresses_per_sec as f64) {
+                    Some(eta) => format!(
+                        ": {}, a hit every {}",
+                        format_odds(config.difficulty.combined),
+                        format_estimate(eta)
+                    ),
+                    None => format!(": {}", format_odds(config.difficulty.combined)),
+                };
 
-                let config_lines = vec![
+                let mut config_lines = vec![
                     Line::from(vec![
                         Span::styled(prefix_label, Style::default().add_modifier(Modifier::BOLD)),
                         Span::raw(format!(": {}", prefixes_str)),
                     ]),
+                    Line::from(vec![
+                        Span::styled("Difficulty", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(difficulty_str),
+                    ]),
+                ];
+                if let Some(leaderboard) = &leaderboard {
+                    let best = match leaderboard.entries().first() {
+                        Some(best) => format!(
+                            ": {} ({} of {} characters)",
+                            &best.address[..best.matched_chars],
+                            best.matched_chars,
+                            leaderboard.target().len()
+                        ),
+                        None => ": none yet".to_string(),
+                    };
+                    config_lines.push(Line::from(vec![
+                        Span::styled(
+                            "Longest match",
+                            Style::default().add_modifier(Modifier::BOLD),
+                        ),
+                        Span::raw(format!(
+                            "{}, {} on the board, reporting from {} characters",
+                            best,
+                            leaderboard.entries().len(),
+                            leaderboard.required_chars()
+                        )),
+                    ]));
+                }
+                config_lines.extend([
+                    Line::from(vec![
+                        Span::styled("Seeds", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(match config.seeds {
+                            Some(seeds) => format!(": {}, {}", seeds.seed0, seeds.seed1),
+                            None => String::new(),
+                        }),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Path", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(format!(": {}", config.template)),
+                    ]),
                     Line::from(vec![
                         Span::styled("Max depth", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(format!(": {}", config.max_depth)),
+                        Span::raw(match gap_estimate {
+                            Some(estimate) => format!(
+                                ": {} (hit below it in {}, {} with max depth {})",
+                                config.max_depth,
+                                format_estimate(estimate.bounded_secs),
+                                format_estimate(estimate.default_layout_secs),
+                                DEFAULT_MAX_DEPTH
+                            ),
+                            None => format!(": {}", config.max_depth),
+                        }),
                     ]),
                     Line::from(vec![
                         Span::styled("CPU threads", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(format!(": {}", config.cpu_threads)),
+                        Span::raw(format_cpu_threads(config.cpu_threads, &cpu_throttle)),
                     ]),
-                ];
+                ]);
+                if let Some(error) = &last_error {
+                    config_lines.push(Line::from(vec![
+                        Span::styled(
+                            "Error",
+                            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(format!(": {}", error), Style::default().fg(Color::Red)),
+                    ]));
+                }
                 let config_widget = Paragraph::new(config_lines)
                     .block(
                         Block::default()
@@ -417,7 +634,7 @@ fn run_event_loop(
                     if let Some(stats) = bench_stats.get(bench_id) {
                         // Has stats - show them
                         let runtime = stats.runtime_secs();
-                        let hashrate = stats.total_generated.checked_div(runtime).unwrap_or(0);
+                        let hashrate = stats.addresses_per_sec();
 
                         total_generated += stats.total_generated;
                         total_hashrate += hashrate as f64;
@@ -503,14 +720,16 @@ fn run_event_loop(
                         mut max_idx,
                     ),
                      item| {
-                        let derivation_path = format_derivation_path(&item.path);
-                        let index_str = item.path[5].to_string();
+                        let derivation_path =
+                            format_derivation_path(
+                            &item.path,
+                            &config.template,
+                            config.key_origin.as_ref(),
+                        );
+                        let index_str = item.path.index().to_string();
                         let prefix = &prefixes[item.prefix_id as usize];
                         let prefix_str = prefix.as_str().to_string();
-                        let address_type_str = match prefix.address_type {
-                            AddressType::P2PKH => "P2PKH",
-                            AddressType::P2WPKH => "P2WPKH",
-                        };
+                        let address_type_str = prefix.address_type.name();
 
                         // Update max lengths
                         max_addr = max_addr.max(item.address.len());
@@ -569,7 +788,7 @@ fn run_event_loop(
 
                 // Instructions at the bottom
                 let instructions = Paragraph::new(
-                    "Tab: Switch between Workbenches/Found | ↑↓: Navigate | Ctrl+C: Stop",
+                    "Tab: Switch between Workbenches/Found | ↑↓: Navigate | +/-: CPU limit | Ctrl+C: Stop",
                 );
                 frame.render_widget(instructions, chunks[4]);
             })?;
@@ -591,6 +810,15 @@ fn run_event_loop(
                             stop_signal.store(true, Ordering::Relaxed);
                             render_requested.store(true, Ordering::Relaxed); // Force render to show stopping status
                         }
+                        // The CPU workers pick the new limit up after their chunk
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            cpu_throttle.adjust(CPU_LIMIT_STEP as i32);
+                            render_requested.store(true, Ordering::Relaxed);
+                        }
+                        KeyCode::Char('-') => {
+                            cpu_throttle.adjust(-(CPU_LIMIT_STEP as i32));
+                            render_requested.store(true, Ordering::Relaxed);
+                        }
                         _ => {
                             // Handle other keys and request render only if state changed
                             let mut state = state.lock().expect("TUI state mutex poisoned");
@@ -616,6 +844,16 @@ fn run_event_loop(
     Ok(())
 }
 
+/// The CPU threads, with their limit and priority when throttled
+fn format_cpu_threads(cpu_threads: u32, cpu_throttle: &CpuThrottle) -> String {
+    let limit = cpu_throttle.limit();
+    match (limit < MAX_CPU_LIMIT, cpu_throttle.is_background()) {
+        (false, false) => format!(": {}", cpu_threads),
+        (true, false) => format!(": {}, limited to {}%", cpu_threads, limit),
+        (_, true) => format!(": {}, limited to {}%, background", cpu_threads, limit),
+    }
+}
+
 /// Handles keyboard input events and returns whether the state changed
 fn handle_key_event(state: &mut TuiState, key_code: KeyCode) -> bool {
     match key_code {
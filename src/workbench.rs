@@ -1,5 +1,7 @@
 pub trait Workbench: Send + Sync {
     fn start(&self);
     fn wait(&self);
+    /// Whether the workbench searched its whole counter range, or gave up
+    fn is_done(&self) -> bool;
     fn total_generated(&self) -> u64;
 }
@@ -1,6 +1,13 @@
 use crate::constants::NON_HARDENED_MAX_INDEX;
+use crate::cpu_throttle::CpuThrottle;
+use crate::cpu_topology::ThreadPlacement;
 use crate::extended_public_key::ExtendedPubKey;
+use crate::extended_public_key_path_walker::counter_space;
+use crate::path_template::PathTemplate;
 use crate::prefix::Prefix;
+use crate::prefix_filter::PrefixFilter;
+use std::ops::Range;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct WorkbenchConfig {
@@ -9,6 +16,18 @@ pub struct WorkbenchConfig {
     pub seed0: u32,
     pub seed1: u32,
     pub max_depth: u32,
+    /// Counters this workbench derives, disjoint from the other devices'
+    pub counter_range: Range<u64>,
+    /// Layout of the derived paths
+    pub template: PathTemplate,
+    /// Matches of dropped prefixes are not reported. Updated by the
+    /// orchestrator while searching.
+    pub prefix_filter: Arc<PrefixFilter>,
+    /// Cores the workbench's threads run on, anywhere by default
+    pub placement: ThreadPlacement,
+    /// CPU limit and priority of the CPU workbench's threads, unthrottled
+    /// by default
+    pub throttle: CpuThrottle,
 }
 
 impl WorkbenchConfig {
@@ -18,6 +37,8 @@ impl WorkbenchConfig {
         seed0: u32,
         seed1: u32,
         max_depth: u32,
+        counter_range: Range<u64>,
+        template: PathTemplate,
     ) -> Self {
         assert!(
             seed0 <= NON_HARDENED_MAX_INDEX,
@@ -31,20 +52,49 @@ impl WorkbenchConfig {
             max_depth <= NON_HARDENED_MAX_INDEX,
             "max_depth must be <= 0x7FFFFFFF"
         );
+        assert!(
+            counter_range.end <= counter_space(&template, max_depth),
+            "counter_range must end within the counter space"
+        );
 
+        let prefix_filter = Arc::new(PrefixFilter::new(prefixes.len()));
         Self {
             xpub,
             prefixes,
             seed0,
             seed1,
             max_depth,
+            counter_range,
+            template,
+            prefix_filter,
+            placement: ThreadPlacement::default(),
+            throttle: CpuThrottle::default(),
         }
     }
+
+    /// Shares the orchestrator's prefix filter
+    pub fn with_prefix_filter(mut self, prefix_filter: Arc<PrefixFilter>) -> Self {
+        self.prefix_filter = prefix_filter;
+        self
+    }
+
+    pub fn with_placement(mut self, placement: ThreadPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Shares the throttle adjusted from the TUI
+    pub fn with_throttle(mut self, throttle: CpuThrottle) -> Self {
+        self.throttle = throttle;
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::network::Network;
+    use crate::path_template::PathTemplate;
 
     #[test]
     fn test_valid_seeds() {
@@ -61,9 +111,17 @@ mod tests {
         use crate::prefix::Prefix;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefixes = vec![Prefix::new("1").unwrap()];
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
 
-        WorkbenchConfig::new(xpub, prefixes, 0x80000000, 1000, 1000);
+        WorkbenchConfig::new(
+            xpub,
+            prefixes,
+            0x80000000,
+            1000,
+            1000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
     }
 
     #[test]
@@ -73,9 +131,17 @@ mod tests {
         use crate::prefix::Prefix;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefixes = vec![Prefix::new("1").unwrap()];
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
 
-        WorkbenchConfig::new(xpub, prefixes, 1000, 0x80000000, 1000);
+        WorkbenchConfig::new(
+            xpub,
+            prefixes,
+            1000,
+            0x80000000,
+            1000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
     }
 
     #[test]
@@ -85,9 +151,37 @@ mod tests {
         use crate::prefix::Prefix;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefixes = vec![Prefix::new("1").unwrap()];
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
 
-        WorkbenchConfig::new(xpub, prefixes, 1000, 1000, 0x80000000);
+        WorkbenchConfig::new(
+            xpub,
+            prefixes,
+            1000,
+            1000,
+            0x80000000,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "counter_range must end within the counter space")]
+    fn test_invalid_counter_range() {
+        use crate::extended_public_key::ExtendedPubKey;
+        use crate::prefix::Prefix;
+
+        let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
+
+        WorkbenchConfig::new(
+            xpub,
+            prefixes,
+            1000,
+            1000,
+            1,
+            0..u64::MAX,
+            PathTemplate::default(),
+        );
     }
 
     #[test]
@@ -96,7 +190,7 @@ mod tests {
         use crate::prefix::Prefix;
 
         let xpub = ExtendedPubKey::from_str("xpub6CbJVZm8i81HtKFhs61SQw5tR7JxPMdYmZbrhx7UeFdkPG75dX2BNctqPdFxHLU1bKXLPotWbdfNVWmea1g3ggzEGnDAxKdpJcqCUpc5rNn").unwrap();
-        let prefixes = vec![Prefix::new("1").unwrap()];
+        let prefixes = vec![Prefix::new("1", Network::Mainnet).unwrap()];
 
         let config = WorkbenchConfig::new(
             xpub,
@@ -104,6 +198,8 @@ mod tests {
             NON_HARDENED_MAX_INDEX,
             NON_HARDENED_MAX_INDEX,
             NON_HARDENED_MAX_INDEX,
+            0..u64::MAX,
+            PathTemplate::default(),
         );
         assert_eq!(config.seed0, NON_HARDENED_MAX_INDEX);
         assert_eq!(config.seed1, NON_HARDENED_MAX_INDEX);